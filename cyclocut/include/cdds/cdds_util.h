@@ -27,4 +27,68 @@
 
 dds_entity_t cdds_create_blob_topic(dds_entity_t dp, char *topic_name, char *type_name, bool is_keyless);
 
+/* The samples of blob topics, as used by loans, topic filters, query conditions and the
+   dds_write/dds_dispose/dds_take family of functions. The payload is the CDR encapsulation
+   header followed by the serialized sample, or only by its key if key_only. It is allocated
+   with malloc, and freed with the sample. */
+typedef struct cdds_blob_sample
+{
+  unsigned char *payload;
+  uint32_t size;
+  bool key_only;
+} cdds_blob_sample_t;
+
+/* A key field at a fixed offset in the CDR payload of the samples: an element or array of
+   primitives, copied to the big-endian serialized key */
+typedef struct cdds_key_field
+{
+  uint32_t offset; /* offset from the end of the encapsulation header, in data samples */
+  uint32_t size;   /* size of a primitive element: 1, 2, 4 or 8 bytes */
+  uint32_t count;  /* number of consecutive elements */
+} cdds_key_field_t;
+
+/* Writes the big-endian serialized key of a sample (payload including the encapsulation
+   header, or only the key fields if key_only) into key, and returns its size even if it
+   exceeds key_capacity, or 0 if the key can't be extracted */
+typedef size_t (*cdds_key_extractor_fn)(void *arg, const unsigned char *payload, size_t size, bool key_only, unsigned char *key, size_t key_capacity);
+
+typedef struct cdds_key_descriptor
+{
+  /* The key fields, in the order of the key, used if extractor is NULL */
+  uint32_t nfields;
+  const cdds_key_field_t *fields;
+  /* Or a function extracting the serialized key */
+  cdds_key_extractor_fn extractor;
+  void *extractor_arg;
+  void (*extractor_arg_free)(void *arg); /* called with extractor_arg when the topic type is freed, or if the descriptor is invalid, may be NULL */
+  uint32_t max_key_size; /* max size of the key returned by extractor, 0 if unbounded */
+} cdds_key_descriptor_t;
+
+/* Creates a blob topic whose instances are identified by the key described by keys. The key
+   hashes are those of the original writers, as long as the key description matches their type.
+   The raw (not serialized) samples of a PSMX have no key, so they are dropped on keyed topics. */
+dds_entity_t cdds_create_keyed_blob_topic(dds_entity_t dp, char *topic_name, char *type_name, const cdds_key_descriptor_t *keys);
+
+/* The XTypes TypeInformation and TypeMapping of a type, serialized as in discovery (XCDR2
+   little-endian, without encapsulation header) */
+typedef struct cdds_type_information
+{
+  const unsigned char *type_information;
+  uint32_t type_information_size;
+  const unsigned char *type_mapping;
+  uint32_t type_mapping_size;
+} cdds_type_information_t;
+
+/* Creates a blob topic advertising the type information of type_info (copied), so that it
+   matches the peers requiring type assignability. The topic is keyless if keys is NULL. Returns
+   DDS_RETCODE_UNSUPPORTED for a non-NULL type_info if Cyclone DDS is built without the type
+   library. */
+dds_entity_t cdds_create_blob_topic_with_type_information(dds_entity_t dp, char *topic_name, char *type_name, const cdds_key_descriptor_t *keys, const cdds_type_information_t *type_info);
+
+struct ddsi_rdata;
+
+/* Copies the size bytes of a sample received in fragchain, as given to the from_ser operation of
+   serdatas, to buf. For the serdatas implemented in Rust, as the fragments are opaque there. */
+void cdds_copy_fragchain(const struct ddsi_rdata *fragchain, size_t size, unsigned char *buf);
+
 #endif /* ATOLAB_CDDS_UTIL_H_ */
@@ -18,6 +18,33 @@
 #include "dds/ddsc/dds_psmx.h"
 #include "dds/ddsi/ddsi_radmin.h"
 #include "dds/ddsi/ddsi_serdata.h"
+#include "dds/ddsrt/heap.h"
+#include "dds/ddsrt/md5.h"
+#include "dds/ddsrt/mh3.h"
+#ifdef DDS_HAS_TYPELIB
+#include "dds/ddsi/ddsi_typelib.h"
+#endif
+
+/* Size of the encapsulation header preceding the CDR data in the payloads */
+#define CDDS_ENCAPSULATION_HEADER_SIZE 4
+/* Encapsulation identifiers from CDR2_BE on are XCDR2 */
+#define CDDS_ENCAPSULATION_CDR2_BE 0x0006
+
+struct cdds_sertype
+{
+  struct ddsi_sertype c;
+  uint32_t nfields;
+  cdds_key_field_t *fields;
+  cdds_key_extractor_fn extractor;
+  void *extractor_arg;
+  void (*extractor_arg_free)(void *arg);
+  uint32_t max_key_size; /* 0 if unbounded */
+  /* The serialized XTypes type information, NULL if unknown */
+  unsigned char *type_information;
+  uint32_t type_information_size;
+  unsigned char *type_mapping;
+  uint32_t type_mapping_size;
+};
 
 struct cdds_ddsi_payload
 {
@@ -25,14 +52,30 @@ struct cdds_ddsi_payload
   size_t size;
   enum ddsi_serdata_kind kind;
   unsigned char *payload;
+  /* The key serialized in big-endian XCDR2, as used for the keyhash */
+  unsigned char *key;
+  size_t key_size;
+  bool key_md5;
 };
 
+static bool cdds_sertype_is_keyed(const struct cdds_sertype *st)
+{
+  return st->nfields > 0 || st->extractor != NULL;
+}
+
 static bool cdds_sertype_equal(const struct ddsi_sertype *acmn, const struct ddsi_sertype *bcmn)
 {
-  // no fields in stp beyond the common ones, and those are all checked for equality before this function is called
-  (void)acmn;
-  (void)bcmn;
-  return true;
+  // the common fields are all checked for equality before this function is called
+  const struct cdds_sertype *a = (const struct cdds_sertype *)acmn;
+  const struct cdds_sertype *b = (const struct cdds_sertype *)bcmn;
+  if (a->nfields != b->nfields || a->extractor != b->extractor || a->extractor_arg != b->extractor_arg || a->max_key_size != b->max_key_size)
+    return false;
+  if (a->nfields > 0 && memcmp(a->fields, b->fields, a->nfields * sizeof(*a->fields)) != 0)
+    return false;
+  if (a->type_information_size != b->type_information_size || a->type_mapping_size != b->type_mapping_size)
+    return false;
+  return (a->type_information_size == 0 || memcmp(a->type_information, b->type_information, a->type_information_size) == 0) &&
+         (a->type_mapping_size == 0 || memcmp(a->type_mapping, b->type_mapping, a->type_mapping_size) == 0);
 }
 
 static size_t get_hash(const char *source)
@@ -57,45 +100,90 @@ static uint32_t cdds_sertype_hash(const struct ddsi_sertype *tpcmn)
 
 static void cdds_sertype_free(struct ddsi_sertype *tpcmn)
 {
+  struct cdds_sertype *st = (struct cdds_sertype *)tpcmn;
+  if (st->extractor_arg_free != NULL)
+    st->extractor_arg_free(st->extractor_arg);
+  ddsrt_free(st->fields);
+  ddsrt_free(st->type_information);
+  ddsrt_free(st->type_mapping);
   ddsi_sertype_fini(tpcmn);
-  free(tpcmn);
+  ddsrt_free(st);
 }
 
 static void cdds_sertype_zero_samples(const struct ddsi_sertype *d, void *samples, size_t count)
 {
   (void)d;
-  (void)samples;
-  (void)count;
-  /* Not using code paths that rely on the samples getting zero'd out */
+  memset(samples, 0, count * sizeof(cdds_blob_sample_t));
 }
 
 static void cdds_sertype_realloc_samples(
     void **ptrs, const struct ddsi_sertype *d,
     void *old, size_t oldcount, size_t count)
 {
-  (void)(ptrs);
-  (void)(d);
-  (void)(old);
-  (void)(oldcount);
-  (void)(count);
-  /* Not using code paths that rely on this (loans, dispose, unregister with instance handle,
-     content filters) */
-  abort();
+  (void)d;
+  const size_t size = sizeof(cdds_blob_sample_t);
+  /* ddsrt_realloc aborts rather than returning NULL, as the operation can't fail */
+  char *new = (oldcount == count) ? (char *)old : (char *)ddsrt_realloc(old, size * count);
+  if (count > oldcount)
+    memset(new + size * oldcount, 0, size * (count - oldcount));
+  for (size_t i = 0; i < count; i++)
+    ptrs[i] = new + size * i;
 }
 
 static void cdds_sertype_free_samples(
     const struct ddsi_sertype *d, void **ptrs, size_t count,
     dds_free_op_t op)
 {
-  (void)(d);     // unused
-  (void)(ptrs);  // unused
-  (void)(count); // unused
-  /* Not using code paths that rely on this (dispose, unregister with instance handle, content
-     filters) */
-  assert(!(op & DDS_FREE_ALL_BIT));
-  (void)op;
+  (void)d;
+  if (count == 0)
+    return;
+  if (op & DDS_FREE_CONTENTS_BIT)
+  {
+    for (size_t i = 0; i < count; i++)
+    {
+      cdds_blob_sample_t *sample = (cdds_blob_sample_t *)ptrs[i];
+      ddsrt_free(sample->payload);
+      sample->payload = NULL;
+      sample->size = 0;
+    }
+  }
+  if (op & DDS_FREE_ALL_BIT)
+  {
+    // the samples were allocated at once, by realloc_samples
+    ddsrt_free(ptrs[0]);
+  }
 }
 
+#ifdef DDS_HAS_TYPELIB
+static ddsi_typeinfo_t *cdds_sertype_typeinfo(const struct ddsi_sertype *tpcmn)
+{
+  const struct cdds_sertype *st = (const struct cdds_sertype *)tpcmn;
+  if (st->type_information == NULL)
+    return NULL;
+  return ddsi_typeinfo_deser(st->type_information, st->type_information_size);
+}
+
+static ddsi_typeid_t *cdds_sertype_typeid(const struct ddsi_sertype *tpcmn, ddsi_typeid_kind_t kind)
+{
+  assert(kind == DDSI_TYPEID_KIND_MINIMAL || kind == DDSI_TYPEID_KIND_COMPLETE);
+  ddsi_typeinfo_t *type_info = cdds_sertype_typeinfo(tpcmn);
+  if (type_info == NULL)
+    return NULL;
+  ddsi_typeid_t *type_id = ddsi_typeinfo_typeid(type_info, kind);
+  ddsi_typeinfo_fini(type_info);
+  ddsrt_free(type_info);
+  return type_id;
+}
+
+static ddsi_typemap_t *cdds_sertype_typemap(const struct ddsi_sertype *tpcmn)
+{
+  const struct cdds_sertype *st = (const struct cdds_sertype *)tpcmn;
+  if (st->type_mapping == NULL)
+    return NULL;
+  return ddsi_typemap_deser(st->type_mapping, st->type_mapping_size);
+}
+#endif
+
 static const struct ddsi_sertype_ops cdds_sertype_ops = {
     .version = ddsi_sertype_v0,
     .arg = NULL,
@@ -104,18 +192,108 @@ static const struct ddsi_sertype_ops cdds_sertype_ops = {
     .realloc_samples = cdds_sertype_realloc_samples,
     .free_samples = cdds_sertype_free_samples,
     .equal = cdds_sertype_equal,
-    .hash = cdds_sertype_hash
-
-    /* Here .type_id, .type_map, .type_info and .derive_sertype are needed if we want full XTypes across the bridge */
+    .hash = cdds_sertype_hash,
+#ifdef DDS_HAS_TYPELIB
+    .type_id = cdds_sertype_typeid,
+    .type_map = cdds_sertype_typemap,
+    .type_info = cdds_sertype_typeinfo,
+#endif
+    /* No .derive_sertype: the payloads are written as they are, whatever the data representation */
 };
 
+static size_t align_to(size_t offset, size_t alignment)
+{
+  return (offset + alignment - 1) & ~(alignment - 1);
+}
+
+/* Size of the big-endian XCDR2 serialized key made of the fields, in which the alignment is at most 4 */
+static size_t cdds_key_fields_size(uint32_t nfields, const cdds_key_field_t *fields)
+{
+  size_t size = 0;
+  for (uint32_t i = 0; i < nfields; i++)
+    size = align_to(size, fields[i].size < 4 ? fields[i].size : 4) + (size_t)fields[i].size * fields[i].count;
+  return size;
+}
+
+/* Copies the key fields of a payload to key (zeroed, of the size given by cdds_key_fields_size).
+   The fields are at their offsets in data samples, and packed with the alignment of the
+   representation of the payload in key samples. */
+static bool cdds_key_from_fields(const struct cdds_sertype *st, const unsigned char *payload, size_t size, bool key_only, unsigned char *key)
+{
+  if (size < CDDS_ENCAPSULATION_HEADER_SIZE)
+    return false;
+  const uint16_t identifier = (uint16_t)((payload[0] << 8) | payload[1]);
+  const bool swap = (identifier & 1) != 0; // little-endian
+  const size_t max_alignment = identifier >= CDDS_ENCAPSULATION_CDR2_BE ? 4 : 8;
+  const unsigned char *data = payload + CDDS_ENCAPSULATION_HEADER_SIZE;
+  const size_t data_size = size - CDDS_ENCAPSULATION_HEADER_SIZE;
+  size_t src_off = 0;
+  size_t key_off = 0;
+  for (uint32_t i = 0; i < st->nfields; i++)
+  {
+    const cdds_key_field_t *field = &st->fields[i];
+    const size_t n = (size_t)field->size * field->count;
+    src_off = key_only ? align_to(src_off, field->size < max_alignment ? field->size : max_alignment) : field->offset;
+    if (n > data_size || src_off > data_size - n)
+      return false;
+    key_off = align_to(key_off, field->size < 4 ? field->size : 4);
+    for (uint32_t e = 0; e < field->count; e++)
+    {
+      const unsigned char *src = data + src_off + (size_t)e * field->size;
+      unsigned char *dst = key + key_off + (size_t)e * field->size;
+      for (uint32_t b = 0; b < field->size; b++)
+        dst[b] = swap ? src[field->size - 1 - b] : src[b];
+    }
+    src_off += n;
+    key_off += n;
+  }
+  return true;
+}
+
+/* Extracts the key of a serdata and derives its hash, returns false if the payload doesn't
+   contain a valid key */
+static bool cdds_serdata_init_key(struct cdds_ddsi_payload *zp)
+{
+  const struct cdds_sertype *st = (const struct cdds_sertype *)zp->sd.type;
+  zp->key = NULL;
+  zp->key_size = 0;
+  zp->key_md5 = st->max_key_size == 0 || st->max_key_size > DDS_FIXED_KEY_MAX_SIZE;
+  zp->sd.hash = st->c.serdata_basehash;
+  if (zp->sd.kind == SDK_EMPTY || zp->payload == NULL || !cdds_sertype_is_keyed(st))
+    return true;
+
+  const bool key_only = (zp->sd.kind == SDK_KEY);
+  if (st->extractor == NULL)
+  {
+    zp->key_size = st->max_key_size;
+    zp->key = (unsigned char *)ddsrt_calloc(1, zp->key_size);
+    if (!cdds_key_from_fields(st, zp->payload, zp->size, key_only, zp->key))
+      return false;
+  }
+  else
+  {
+    unsigned char buf[DDS_FIXED_KEY_MAX_SIZE];
+    size_t n = st->extractor(st->extractor_arg, zp->payload, zp->size, key_only, buf, sizeof(buf));
+    if (n == 0)
+      return false;
+    zp->key = (unsigned char *)ddsrt_malloc(n);
+    zp->key_size = n;
+    if (n <= sizeof(buf))
+      memcpy(zp->key, buf, n);
+    else if (st->extractor(st->extractor_arg, zp->payload, zp->size, key_only, zp->key, n) != n)
+      return false;
+  }
+  zp->sd.hash = ddsrt_mh3(zp->key, zp->key_size, 0) ^ st->c.serdata_basehash;
+  return true;
+}
+
 static bool cdds_serdata_eqkey(const struct ddsi_serdata *a, const struct ddsi_serdata *b)
 {
-  (void)(a);
-  (void)(b);
-  /* ROS 2 doesn't do keys in a meaningful way yet */
   CY_DEBUG("Called <cdds_serdata_eqkey>\n");
-  return true;
+  const struct cdds_ddsi_payload *za = (const struct cdds_ddsi_payload *)a;
+  const struct cdds_ddsi_payload *zb = (const struct cdds_ddsi_payload *)b;
+  /* Keyless topics have a single instance, with an empty key */
+  return za->key_size == zb->key_size && (za->key_size == 0 || memcmp(za->key, zb->key, za->key_size) == 0);
 }
 
 static uint32_t cdds_serdata_size(const struct ddsi_serdata *sd)
@@ -133,27 +311,73 @@ static void cdds_serdata_free(struct ddsi_serdata *sd)
   struct cdds_ddsi_payload *zp = (struct cdds_ddsi_payload *)sd;
   assert(zp != 0);
   // assert(zp->payload != 0);
-  free(zp->payload);
+  ddsrt_free(zp->payload);
   zp->payload = 0;
   zp->size = 0;
-  free(zp);
+  ddsrt_free(zp->key);
+  ddsrt_free(zp);
+}
+
+/* Returns the serdata, or NULL after freeing it if it doesn't contain a valid key */
+static struct ddsi_serdata *cdds_serdata_with_key(struct cdds_ddsi_payload *zp)
+{
+  if (!cdds_serdata_init_key(zp))
+  {
+    CY_DEBUG_WA("Failed to extract the key of a sample of %s\n", zp->sd.type->type_name);
+    cdds_serdata_free(&zp->sd);
+    return NULL;
+  }
+  return &zp->sd;
 }
 
 static void cdds_serdata_get_keyhash (const struct ddsi_serdata *serdata_common, struct ddsi_keyhash *buf, bool force_md5)
 {
   CY_DEBUG("Called <cdds_serdata_get_keyhash>\n");
-  // Don't have the type information needed to process the keyhash
-  memset(buf->value, 0, DDS_FIXED_KEY_MAX_SIZE);
+  const struct cdds_ddsi_payload *zp = (const struct cdds_ddsi_payload *)serdata_common;
+  if (force_md5 || zp->key_md5)
+  {
+    ddsrt_md5_state_t md5st;
+    ddsrt_md5_init(&md5st);
+    ddsrt_md5_append(&md5st, (const ddsrt_md5_byte_t *)zp->key, (uint32_t)zp->key_size);
+    ddsrt_md5_finish(&md5st, (ddsrt_md5_byte_t *)buf->value);
+  }
+  else
+  {
+    memset(buf->value, 0, DDS_FIXED_KEY_MAX_SIZE);
+    if (zp->key_size > 0)
+      memcpy(buf->value, zp->key, zp->key_size);
+  }
+}
+
+/* Writes the encapsulation header and the big-endian XCDR2 serialized key of a key sample */
+static void cdds_write_key_payload(unsigned char *payload, const unsigned char *key, size_t key_size)
+{
+  memset(payload, 0, CDDS_ENCAPSULATION_HEADER_SIZE);
+  payload[1] = CDDS_ENCAPSULATION_CDR2_BE;
+  if (key_size > 0)
+    memcpy(payload + CDDS_ENCAPSULATION_HEADER_SIZE, key, key_size);
+}
+
+/* Returns a key serdata whose payload is the big-endian XCDR2 serialized key */
+static struct ddsi_serdata *cdds_serdata_from_key(const struct ddsi_sertype *tpcmn, const unsigned char *key, size_t key_size)
+{
+  struct cdds_ddsi_payload *zp = (struct cdds_ddsi_payload *)ddsrt_malloc(sizeof(struct cdds_ddsi_payload));
+  ddsi_serdata_init(&zp->sd, tpcmn, SDK_KEY);
+  zp->kind = SDK_KEY;
+  zp->size = CDDS_ENCAPSULATION_HEADER_SIZE + key_size;
+  zp->payload = (unsigned char *)ddsrt_malloc(zp->size);
+  cdds_write_key_payload(zp->payload, key, key_size);
+  return cdds_serdata_with_key(zp);
 }
 
 static struct ddsi_serdata *cdds_serdata_from_ser_iov(const struct ddsi_sertype *tpcmn, enum ddsi_serdata_kind kind, ddsrt_msg_iovlen_t niov, const ddsrt_iovec_t *iov, size_t size)
 {
   CY_DEBUG_WA("==> <cdds_serdata_from_ser_iov> for %s -- size %zu\n", tpcmn->type_name, size);
-  struct cdds_ddsi_payload *zp = (struct cdds_ddsi_payload *)malloc(sizeof(struct cdds_ddsi_payload));
+  struct cdds_ddsi_payload *zp = (struct cdds_ddsi_payload *)ddsrt_malloc(sizeof(struct cdds_ddsi_payload));
   ddsi_serdata_init(&zp->sd, tpcmn, kind);
   zp->size = size;
   zp->kind = kind;
-  zp->payload = malloc(size);
+  zp->payload = ddsrt_malloc(size);
   int offset = 0;
   int csize = 0;
   int i = 0;
@@ -172,24 +396,15 @@ static struct ddsi_serdata *cdds_serdata_from_ser_iov(const struct ddsi_sertype
   case SDK_EMPTY:
     break;
   }
-  return (struct ddsi_serdata *)zp;
+  return cdds_serdata_with_key(zp);
 }
 
-static struct ddsi_serdata *cdds_serdata_from_ser(
-    const struct ddsi_sertype *tpcmn,
-    enum ddsi_serdata_kind kind,
-    const struct ddsi_rdata *fragchain, size_t size)
+void cdds_copy_fragchain(const struct ddsi_rdata *fragchain, size_t size, unsigned char *buf)
 {
-  CY_DEBUG_WA("Called <cdds_serdata_from_ser> for %s for %zu bytes\n", tpcmn->type_name, size);
-  struct cdds_ddsi_payload *csd = (struct cdds_ddsi_payload *)malloc(sizeof(struct cdds_ddsi_payload));
-  ddsi_serdata_init(&csd->sd, tpcmn, kind);
-  csd->payload = (unsigned char *)malloc(size);
-  csd->size = size;
-
   uint32_t off = 0;
   assert(fragchain->min == 0);
   assert(fragchain->maxp1 >= off);
-  unsigned char *cursor = csd->payload;
+  unsigned char *cursor = buf;
 
   while (fragchain)
   {
@@ -209,7 +424,22 @@ static struct ddsi_serdata *cdds_serdata_from_ser(
     fragchain = fragchain->nextfrag;
   }
   CY_DEBUG("Done Defragmenting!\n");
-  return &csd->sd;
+  (void)size;
+}
+
+static struct ddsi_serdata *cdds_serdata_from_ser(
+    const struct ddsi_sertype *tpcmn,
+    enum ddsi_serdata_kind kind,
+    const struct ddsi_rdata *fragchain, size_t size)
+{
+  CY_DEBUG_WA("Called <cdds_serdata_from_ser> for %s for %zu bytes\n", tpcmn->type_name, size);
+  struct cdds_ddsi_payload *csd = (struct cdds_ddsi_payload *)ddsrt_malloc(sizeof(struct cdds_ddsi_payload));
+  ddsi_serdata_init(&csd->sd, tpcmn, kind);
+  csd->payload = (unsigned char *)ddsrt_malloc(size);
+  csd->size = size;
+  csd->kind = kind;
+  cdds_copy_fragchain(fragchain, size, csd->payload);
+  return cdds_serdata_with_key(csd);
 }
 
 static struct ddsi_serdata *cdds_serdata_from_keyhash (
@@ -217,8 +447,11 @@ static struct ddsi_serdata *cdds_serdata_from_keyhash (
   const ddsi_keyhash_t *keyhash)
 {
   CY_DEBUG("Called <cdds_serdata_from_keyhash>\n");
-  // Don't have the type information needed to process the keyhash
-  return NULL;
+  const struct cdds_sertype *st = (const struct cdds_sertype *)tpcmn;
+  // The key can only be recovered if it is not an MD5 hash, it is zero-padded to its max size
+  if (!cdds_sertype_is_keyed(st) || st->max_key_size == 0 || st->max_key_size > DDS_FIXED_KEY_MAX_SIZE)
+    return NULL;
+  return cdds_serdata_from_key(tpcmn, keyhash->value, st->max_key_size);
 }
 
 static struct ddsi_serdata *cdds_serdata_to_untyped(const struct ddsi_serdata *psd)
@@ -226,13 +459,24 @@ static struct ddsi_serdata *cdds_serdata_to_untyped(const struct ddsi_serdata *p
 
   CY_DEBUG("Called <cdds_serdata_to_untyped>\n");
   struct cdds_ddsi_payload *sd = (struct cdds_ddsi_payload *)psd;
-  struct cdds_ddsi_payload *sd_tl = (struct cdds_ddsi_payload *)malloc(sizeof(struct cdds_ddsi_payload));
+  struct cdds_ddsi_payload *sd_tl = (struct cdds_ddsi_payload *)ddsrt_malloc(sizeof(struct cdds_ddsi_payload));
 
   ddsi_serdata_init(&sd_tl->sd, sd->sd.type, SDK_KEY);
   sd_tl->sd.type = NULL;
   sd_tl->sd.hash = sd->sd.hash;
   sd_tl->sd.timestamp.v = INT64_MIN;
   sd_tl->payload = NULL;
+  sd_tl->size = 0;
+  sd_tl->kind = SDK_KEY;
+  // The untyped serdata has no type, but still identifies the instance
+  sd_tl->key = NULL;
+  sd_tl->key_size = sd->key_size;
+  sd_tl->key_md5 = sd->key_md5;
+  if (sd->key_size > 0)
+  {
+    sd_tl->key = (unsigned char *)ddsrt_malloc(sd->key_size);
+    memcpy(sd_tl->key, sd->key, sd->key_size);
+  }
   return &sd_tl->sd;
 }
 
@@ -298,13 +542,18 @@ static struct ddsi_serdata *cdds_from_psmx (const struct ddsi_sertype *type, dds
       assert (0);
       return NULL;
   }
-  
-  struct cdds_ddsi_payload *zp = (struct cdds_ddsi_payload *)malloc(sizeof(struct cdds_ddsi_payload));
+
+  // The key of raw samples is unknown, so they would all be the same instance
+  if (is_raw && cdds_sertype_is_keyed((const struct cdds_sertype *)type))
+    return NULL;
+
+  struct cdds_ddsi_payload *zp = (struct cdds_ddsi_payload *)ddsrt_malloc(sizeof(struct cdds_ddsi_payload));
   ddsi_serdata_init(&zp->sd, type, kind);
   zp->kind = kind;
   
   if (is_raw)
   {
+    zp->payload = NULL;
     zp->size = metadata->sample_size;
     zp->sd.loan = loaned_sample;
     dds_loaned_sample_ref(zp->sd.loan);
@@ -312,13 +561,66 @@ static struct ddsi_serdata *cdds_from_psmx (const struct ddsi_sertype *type, dds
   else // serialized
   {
     zp->size = metadata->sample_size + 4;
-    zp->payload = malloc(zp->size);
+    zp->payload = ddsrt_malloc(zp->size);
     uint16_t *tmp = (uint16_t *) zp->payload;
     *(tmp++) = metadata->cdr_identifier;
     *(tmp++) = metadata->cdr_options;
     memcpy(zp->payload + 4, loaned_sample->sample_ptr, metadata->sample_size);
   }
-  return (struct ddsi_serdata *)zp;
+  return cdds_serdata_with_key(zp);
+}
+
+static struct ddsi_serdata *cdds_serdata_from_sample(const struct ddsi_sertype *tpcmn, enum ddsi_serdata_kind kind, const void *sample)
+{
+  CY_DEBUG("Called <cdds_serdata_from_sample>\n");
+  const cdds_blob_sample_t *s = (const cdds_blob_sample_t *)sample;
+  if (s->payload == NULL || s->size < CDDS_ENCAPSULATION_HEADER_SIZE || (kind == SDK_DATA && s->key_only))
+    return NULL;
+  ddsrt_iovec_t iov = {.iov_base = s->payload, .iov_len = (ddsrt_iov_len_t)s->size};
+  if (kind != SDK_KEY || s->key_only)
+    return cdds_serdata_from_ser_iov(tpcmn, kind, 1, &iov, s->size);
+
+  // The key of a complete sample (e.g. to dispose it), with the payload of a key sample
+  struct ddsi_serdata *data = cdds_serdata_from_ser_iov(tpcmn, SDK_DATA, 1, &iov, s->size);
+  if (data == NULL)
+    return NULL;
+  const struct cdds_ddsi_payload *zp = (const struct cdds_ddsi_payload *)data;
+  struct ddsi_serdata *key = cdds_serdata_from_key(tpcmn, zp->key, zp->key_size);
+  cdds_serdata_free(data);
+  return key;
+}
+
+static bool cdds_serdata_to_sample(const struct ddsi_serdata *serdata_common, void *sample, void **bufptr, void *buflim)
+{
+  CY_DEBUG("Called <cdds_serdata_to_sample>\n");
+  (void)buflim;
+  const struct cdds_ddsi_payload *zp = (const struct cdds_ddsi_payload *)serdata_common;
+  // The payloads are always allocated, not placed in a buffer
+  if (bufptr != NULL || zp->payload == NULL)
+    return false;
+  cdds_blob_sample_t *s = (cdds_blob_sample_t *)sample;
+  s->payload = (unsigned char *)ddsrt_realloc(s->payload, zp->size);
+  memcpy(s->payload, zp->payload, zp->size);
+  s->size = (uint32_t)zp->size;
+  s->key_only = (zp->sd.kind != SDK_DATA);
+  return true;
+}
+
+static bool cdds_serdata_untyped_to_sample(const struct ddsi_sertype *tpcmn, const struct ddsi_serdata *serdata_common, void *sample, void **bufptr, void *buflim)
+{
+  CY_DEBUG("Called <cdds_serdata_untyped_to_sample>\n");
+  (void)tpcmn;
+  (void)buflim;
+  if (bufptr != NULL)
+    return false;
+  // The untyped serdata only has the key
+  const struct cdds_ddsi_payload *zp = (const struct cdds_ddsi_payload *)serdata_common;
+  cdds_blob_sample_t *s = (cdds_blob_sample_t *)sample;
+  s->size = (uint32_t)(CDDS_ENCAPSULATION_HEADER_SIZE + zp->key_size);
+  s->payload = (unsigned char *)ddsrt_realloc(s->payload, s->size);
+  cdds_write_key_payload(s->payload, zp->key, zp->key_size);
+  s->key_only = true;
+  return true;
 }
 
 static const struct ddsi_serdata_ops cdds_serdata_ops = {
@@ -327,6 +629,9 @@ static const struct ddsi_serdata_ops cdds_serdata_ops = {
     .from_ser = cdds_serdata_from_ser,
     .from_ser_iov = cdds_serdata_from_ser_iov,
     .from_keyhash = cdds_serdata_from_keyhash,
+    .from_sample = cdds_serdata_from_sample,
+    .to_sample = cdds_serdata_to_sample,
+    .untyped_to_sample = cdds_serdata_untyped_to_sample,
     .to_ser = cdds_to_ser,
     .to_ser_ref = cdds_to_ser_ref,
     .to_ser_unref = cdds_to_ser_unref,
@@ -336,11 +641,95 @@ static const struct ddsi_serdata_ops cdds_serdata_ops = {
     .from_psmx = cdds_from_psmx
 };
 
+static struct cdds_sertype *cdds_sertype_new(char *type_name, const cdds_key_descriptor_t *keys, bool is_keyless)
+{
+  struct cdds_sertype *st = (struct cdds_sertype *)ddsrt_calloc(1, sizeof(struct cdds_sertype));
+  if (keys != NULL && keys->extractor != NULL)
+  {
+    st->extractor = keys->extractor;
+    st->extractor_arg = keys->extractor_arg;
+    st->extractor_arg_free = keys->extractor_arg_free;
+    st->max_key_size = keys->max_key_size;
+  }
+  else if (keys != NULL && keys->nfields > 0)
+  {
+    st->nfields = keys->nfields;
+    st->fields = (cdds_key_field_t *)ddsrt_malloc(keys->nfields * sizeof(cdds_key_field_t));
+    memcpy(st->fields, keys->fields, keys->nfields * sizeof(cdds_key_field_t));
+    st->max_key_size = (uint32_t)cdds_key_fields_size(st->nfields, st->fields);
+  }
+  uint32_t data_type_flags = (is_keyless ? DDSI_SERTYPE_FLAG_TOPICKIND_NO_KEY : 0);
+  ddsi_sertype_init_flags(&st->c, type_name, &cdds_sertype_ops, &cdds_serdata_ops, data_type_flags);
+  return st;
+}
+
 dds_entity_t cdds_create_blob_topic(dds_entity_t dp, char *topic_name, char *type_name, bool is_keyless)
 {
   CY_DEBUG("Called <cdds_create_blob_topic> \n");
-  struct ddsi_sertype *st = (struct ddsi_sertype *)malloc(sizeof(struct ddsi_sertype));
-  uint32_t data_type_flags = (is_keyless ? DDSI_SERTYPE_FLAG_TOPICKIND_NO_KEY : 0);
-  ddsi_sertype_init_flags(st, type_name, &cdds_sertype_ops, &cdds_serdata_ops, data_type_flags);
+  struct ddsi_sertype *st = &cdds_sertype_new(type_name, NULL, is_keyless)->c;
   return dds_create_topic_sertype(dp, topic_name, &st, NULL, NULL, NULL);
 }
+
+static bool cdds_key_descriptor_valid(const cdds_key_descriptor_t *keys)
+{
+  if (keys->extractor != NULL)
+    return true;
+  if (keys->nfields == 0 || keys->fields == NULL)
+    return false;
+  for (uint32_t i = 0; i < keys->nfields; i++)
+  {
+    const cdds_key_field_t *field = &keys->fields[i];
+    if ((field->size != 1 && field->size != 2 && field->size != 4 && field->size != 8) || field->count == 0)
+      return false;
+  }
+  return true;
+}
+
+dds_entity_t cdds_create_keyed_blob_topic(dds_entity_t dp, char *topic_name, char *type_name, const cdds_key_descriptor_t *keys)
+{
+  CY_DEBUG("Called <cdds_create_keyed_blob_topic> \n");
+  if (keys == NULL)
+    return DDS_RETCODE_BAD_PARAMETER;
+  return cdds_create_blob_topic_with_type_information(dp, topic_name, type_name, keys, NULL);
+}
+
+static unsigned char *copy_bytes(const unsigned char *data, uint32_t size)
+{
+  unsigned char *copy = (unsigned char *)ddsrt_malloc(size);
+  memcpy(copy, data, size);
+  return copy;
+}
+
+dds_entity_t cdds_create_blob_topic_with_type_information(dds_entity_t dp, char *topic_name, char *type_name, const cdds_key_descriptor_t *keys, const cdds_type_information_t *type_info)
+{
+  CY_DEBUG("Called <cdds_create_blob_topic_with_type_information> \n");
+  bool valid = keys == NULL || cdds_key_descriptor_valid(keys);
+  if (type_info != NULL)
+    valid = valid && type_info->type_information != NULL && type_info->type_information_size > 0 && (type_info->type_mapping != NULL || type_info->type_mapping_size == 0);
+#ifdef DDS_HAS_TYPELIB
+  const dds_return_t invalid = DDS_RETCODE_BAD_PARAMETER;
+#else
+  /* Without the type library, the type information could not be advertised */
+  const dds_return_t invalid = type_info != NULL ? DDS_RETCODE_UNSUPPORTED : DDS_RETCODE_BAD_PARAMETER;
+  valid = valid && type_info == NULL;
+#endif
+  if (!valid)
+  {
+    if (keys != NULL && keys->extractor_arg_free != NULL)
+      keys->extractor_arg_free(keys->extractor_arg);
+    return invalid;
+  }
+  struct cdds_sertype *st = cdds_sertype_new(type_name, keys, keys == NULL);
+  if (type_info != NULL)
+  {
+    st->type_information = copy_bytes(type_info->type_information, type_info->type_information_size);
+    st->type_information_size = type_info->type_information_size;
+    if (type_info->type_mapping_size > 0)
+    {
+      st->type_mapping = copy_bytes(type_info->type_mapping, type_info->type_mapping_size);
+      st->type_mapping_size = type_info->type_mapping_size;
+    }
+  }
+  struct ddsi_sertype *stcmn = &st->c;
+  return dds_create_topic_sertype(dp, topic_name, &stcmn, NULL, NULL, NULL);
+}
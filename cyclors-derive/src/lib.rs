@@ -0,0 +1,608 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+
+//! The derive macros of `cyclors::typed::TopicType` and `cyclors::cdr::Element`, re-exported
+//! by cyclors with its `derive` feature.
+//!
+//! ```ignore
+//! use cyclors::typed::TopicType;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, TopicType)]
+//! #[appendable]
+//! #[type_name = "sensors::Temperature"]
+//! struct Temperature {
+//!     #[key]
+//!     sensor_id: u32,
+//!     celsius: f32,
+//! }
+//! ```
+//!
+//! The bound of the string and sequence key fields is given by `#[key(bound = N)]`, for the
+//! keys of up to 16 bytes to be sent in the key hashes rather than as their MD5 hash. The key
+//! fields of other types than primitives and arrays of primitives (e.g. enums, aliases) are
+//! given their serialized size by `#[key(size = N)]`, aligned to the largest power of two
+//! dividing it unless set by `#[key(size = N, align = A)]`.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, Fields, GenericArgument, Index,
+    Lit, LitInt, LitStr, Member, Meta, PathArguments, Result, Type,
+};
+
+/// The max alignment of the primitives in XCDR2, in which the keys are serialized.
+const XCDR2_MAX_ALIGNMENT: usize = 4;
+
+#[proc_macro_derive(TopicType, attributes(key, r#final, appendable, mutable, type_name))]
+pub fn derive_topic_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    topic_type(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(Element)]
+pub fn derive_element(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    element(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Extensibility {
+    Final,
+    Appendable,
+    Mutable,
+}
+
+fn topic_type(input: &DeriveInput) -> Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "TopicType can't be derived for generic types",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new_spanned(
+                input,
+                "TopicType can only be derived for structs",
+            ))
+        }
+    };
+    let extensibility = extensibility(&input.attrs)?;
+    let ident = &input.ident;
+
+    let type_name = match type_name(&input.attrs)? {
+        Some(name) => quote!(::std::string::String::from(#name)),
+        None => {
+            let name = ident.to_string();
+            quote!(::cyclors::typed::__private::type_name(::std::module_path!(), #name))
+        }
+    };
+
+    let key = key_fields(fields)?;
+    let ids = key.iter().map(|field| field.id);
+    let representation = quote!(::cyclors::cdr::DataRepresentation::XCDR2);
+    let (serialize, deserialize) = match extensibility {
+        Extensibility::Final => (
+            quote!(::cyclors::cdr::to_vec(self, #representation)),
+            quote!(::cyclors::cdr::from_slice::<Self>(payload)),
+        ),
+        Extensibility::Appendable => (
+            quote!(::cyclors::cdr::to_vec(&::cyclors::cdr::Appendable(self), #representation)),
+            quote!(
+                ::cyclors::cdr::from_slice::<::cyclors::cdr::Appendable<Self>>(payload)
+                    .map(|sample| sample.0)
+            ),
+        ),
+        // The key members of mutable types must be understood
+        Extensibility::Mutable => (
+            quote!(::cyclors::cdr::to_vec_with_keys(
+                &::cyclors::cdr::Mutable(self),
+                #representation,
+                &[#(#ids),*],
+            )),
+            quote!(
+                ::cyclors::cdr::from_slice::<::cyclors::cdr::Mutable<Self>>(payload)
+                    .map(|sample| sample.0)
+            ),
+        ),
+    };
+
+    let key_impl = if key.is_empty() {
+        TokenStream2::new()
+    } else {
+        let members = key.iter().map(|field| &field.member);
+        let types: Vec<_> = key.iter().map(|field| field.ty).collect();
+        let indices: Vec<_> = (0..key.len()).map(Index::from).collect();
+        let len = key.len();
+        let max_key_size = match max_key_size(key.iter().map(|field| (field.ty, field.size))) {
+            Some(size) => quote!(::std::option::Option::Some(#size)),
+            None => quote!(::std::option::Option::None),
+        };
+        // The key fields are serialized as a final struct, in the order of the type (which is
+        // also the order of the member ids of mutable types)
+        let key_struct = quote! {
+            struct Key<'a>(#(&'a #types),*);
+
+            impl ::cyclors::typed::__private::serde::Serialize for Key<'_> {
+                fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+                where
+                    S: ::cyclors::typed::__private::serde::Serializer,
+                {
+                    use ::cyclors::typed::__private::serde::ser::SerializeTupleStruct;
+                    let mut key = serializer.serialize_tuple_struct("Key", #len)?;
+                    #(key.serialize_field(self.#indices)?;)*
+                    key.end()
+                }
+            }
+        };
+        quote! {
+            fn is_keyed() -> bool {
+                true
+            }
+
+            fn key(&self) -> ::std::vec::Vec<u8> {
+                #key_struct
+
+                ::cyclors::typed::key_to_vec(&Key(#(&self.#members),*))
+                    .expect("the key fields can be serialized")
+            }
+
+            fn max_key_size() -> ::std::option::Option<usize> {
+                #max_key_size
+            }
+
+            fn key_from_payload(
+                payload: &[u8],
+            ) -> ::std::result::Result<::std::vec::Vec<u8>, ::cyclors::cdr::CdrError> {
+                use ::cyclors::typed::__private::serde::de;
+                #key_struct
+
+                // The key samples hold the key fields as a final struct too
+                struct OwnedKey(#(#types),*);
+
+                struct KeyVisitor;
+
+                impl<'de> de::Visitor<'de> for KeyVisitor {
+                    type Value = OwnedKey;
+
+                    fn expecting(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                        f.write_str("the key fields")
+                    }
+
+                    fn visit_seq<A>(self, mut seq: A) -> ::std::result::Result<OwnedKey, A::Error>
+                    where
+                        A: de::SeqAccess<'de>,
+                    {
+                        ::std::result::Result::Ok(OwnedKey(#(
+                            seq.next_element()?
+                                .ok_or_else(|| de::Error::invalid_length(#indices, &self))?
+                        ),*))
+                    }
+                }
+
+                impl<'de> de::Deserialize<'de> for OwnedKey {
+                    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+                    where
+                        D: de::Deserializer<'de>,
+                    {
+                        deserializer.deserialize_tuple_struct("Key", #len, KeyVisitor)
+                    }
+                }
+
+                let key: OwnedKey = ::cyclors::cdr::from_slice(payload)?;
+                ::cyclors::typed::key_to_vec(&Key(#(&key.#indices),*))
+            }
+        }
+    };
+
+    Ok(quote! {
+        impl ::cyclors::typed::TopicType for #ident {
+            fn type_name() -> ::std::string::String {
+                #type_name
+            }
+
+            fn serialize(&self) -> ::std::result::Result<::std::vec::Vec<u8>, ::cyclors::cdr::CdrError> {
+                #serialize
+            }
+
+            fn deserialize(payload: &[u8]) -> ::std::result::Result<Self, ::cyclors::cdr::CdrError> {
+                #deserialize
+            }
+
+            #key_impl
+        }
+    })
+}
+
+fn element(input: &DeriveInput) -> Result<TokenStream2> {
+    let primitive = match &input.data {
+        Data::Enum(data) => {
+            let unit = data
+                .variants
+                .iter()
+                .all(|variant| matches!(variant.fields, Fields::Unit));
+            quote!(#unit)
+        }
+        // A newtype is serialized as its field
+        Data::Struct(data)
+            if matches!(data.fields, Fields::Unnamed(_)) && data.fields.len() == 1 =>
+        {
+            let ty = &data.fields.iter().next().unwrap().ty;
+            quote!(<#ty as ::cyclors::cdr::Element>::PRIMITIVE)
+        }
+        Data::Struct(_) => quote!(false),
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                input,
+                "Element can only be derived for structs and enums",
+            ))
+        }
+    };
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::cyclors::cdr::Element for #ident #ty_generics #where_clause {
+            const PRIMITIVE: bool = #primitive;
+        }
+    })
+}
+
+fn extensibility(attrs: &[Attribute]) -> Result<Extensibility> {
+    let mut extensibility = None;
+    for attr in attrs {
+        let value = if attr.path().is_ident("r#final") {
+            Extensibility::Final
+        } else if attr.path().is_ident("appendable") {
+            Extensibility::Appendable
+        } else if attr.path().is_ident("mutable") {
+            Extensibility::Mutable
+        } else {
+            continue;
+        };
+        attr.meta.require_path_only()?;
+        if extensibility.replace(value).is_some() {
+            return Err(Error::new_spanned(attr, "the extensibility is already set"));
+        }
+    }
+    Ok(extensibility.unwrap_or(Extensibility::Final))
+}
+
+fn type_name(attrs: &[Attribute]) -> Result<Option<LitStr>> {
+    let Some(attr) = attrs.iter().find(|attr| attr.path().is_ident("type_name")) else {
+        return Ok(None);
+    };
+    match &attr.meta.require_name_value()?.value {
+        Expr::Lit(expr) => match &expr.lit {
+            Lit::Str(name) => Ok(Some(name.clone())),
+            lit => Err(Error::new_spanned(lit, "expected a string")),
+        },
+        value => Err(Error::new_spanned(value, "expected a string")),
+    }
+}
+
+/// A key field, with the member to access it and its identifier in mutable types.
+struct KeyField<'a> {
+    member: Member,
+    id: u32,
+    ty: &'a Type,
+    size: KeySize,
+}
+
+/// The size of a key field, as given by its attribute.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum KeySize {
+    /// Told from the type, for primitives and arrays of primitives.
+    Type,
+    /// The bound of a string or sequence.
+    Bound(usize),
+    /// The size and alignment of a type of fixed size.
+    Fixed(usize, usize),
+}
+
+fn key_fields(fields: &Fields) -> Result<Vec<KeyField<'_>>> {
+    let mut key = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let Some(attr) = field.attrs.iter().find(|attr| attr.path().is_ident("key")) else {
+            continue;
+        };
+        let mut size = KeySize::Type;
+        if let Meta::List(_) = attr.meta {
+            let (mut bound, mut fixed, mut align) = (None, None, None);
+            attr.parse_nested_meta(|meta| {
+                let value = if meta.path.is_ident("bound") {
+                    &mut bound
+                } else if meta.path.is_ident("size") {
+                    &mut fixed
+                } else if meta.path.is_ident("align") {
+                    &mut align
+                } else {
+                    return Err(meta.error("expected `bound`, `size` or `align`"));
+                };
+                *value = Some(meta.value()?.parse::<LitInt>()?.base10_parse::<usize>()?);
+                Ok(())
+            })?;
+            let error = |message| Err(Error::new_spanned(attr, message));
+            size = match (bound, fixed, align) {
+                (Some(_), Some(_), _) => return error("the bound and the size are exclusive"),
+                (_, None, Some(_)) => return error("the alignment is only valid with a size"),
+                (Some(_), None, None) if bounded(&field.ty).is_none() => {
+                    return error("the bound is only valid for strings and sequences")
+                }
+                (Some(bound), None, None) => KeySize::Bound(bound),
+                (None, Some(0), _) => return error("the size must be positive"),
+                (None, Some(_), Some(align)) if ![1, 2, 4, 8].contains(&align) => {
+                    return error("the alignment must be 1, 2, 4 or 8")
+                }
+                (None, Some(fixed), align) => {
+                    // By default, the largest power of two dividing the size (e.g. 4 for enums)
+                    let align = align.unwrap_or(1 << fixed.trailing_zeros().min(3));
+                    KeySize::Fixed(fixed, align.min(XCDR2_MAX_ALIGNMENT))
+                }
+                (None, None, None) => KeySize::Type,
+            };
+        } else {
+            attr.meta.require_path_only()?;
+        }
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        };
+        key.push(KeyField {
+            member,
+            id: i as u32,
+            ty: &field.ty,
+            size,
+        });
+    }
+    Ok(key)
+}
+
+/// The max size of the serialized key, when made of primitives, arrays of primitives, bounded
+/// strings and sequences of primitives, and fields of a given size. The keys with other types
+/// are considered unbounded, so always hashed with MD5.
+fn max_key_size<'a>(fields: impl Iterator<Item = (&'a Type, KeySize)>) -> Option<usize> {
+    let mut size: usize = 0;
+    for (ty, key_size) in fields {
+        let (field_size, alignment) = match key_size {
+            KeySize::Type => fixed_size(ty)?,
+            KeySize::Bound(bound) => bounded_size(ty, bound)?,
+            KeySize::Fixed(field_size, alignment) => (field_size, alignment),
+        };
+        size = size.next_multiple_of(alignment) + field_size;
+    }
+    Some(size)
+}
+
+/// A string (`None`), or a sequence of elements of the given type.
+fn bounded(ty: &Type) -> Option<Option<&Type>> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    match (segment.ident.to_string().as_str(), &segment.arguments) {
+        ("String", PathArguments::None) => Some(None),
+        ("Vec", PathArguments::AngleBracketed(arguments)) => match arguments.args.first()? {
+            GenericArgument::Type(element) => Some(Some(element)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The max size and alignment of a bounded string or sequence.
+fn bounded_size(ty: &Type, bound: usize) -> Option<(usize, usize)> {
+    match bounded(ty)? {
+        // The length, then the characters and the terminating NUL
+        None => Some((4 + bound + 1, 4)),
+        // Sequences of arrays are preceded by a DHEADER
+        Some(element) => {
+            let (size, _) = fixed_size(element)?;
+            let dheader = if matches!(element, Type::Array(_)) {
+                4
+            } else {
+                0
+            };
+            Some((dheader + 4 + size * bound, 4))
+        }
+    }
+}
+
+/// The size and alignment of a primitive, or array of primitives.
+fn fixed_size(ty: &Type) -> Option<(usize, usize)> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            let segments: Vec<_> = path.path.segments.iter().collect();
+            let name = match segments[..] {
+                [name] => &name.ident,
+                // e.g. `std::primitive::u32`
+                [krate, module, name]
+                    if (krate.ident == "std" || krate.ident == "core")
+                        && module.ident == "primitive" =>
+                {
+                    &name.ident
+                }
+                _ => return None,
+            };
+            if segments.iter().any(|segment| !segment.arguments.is_none()) {
+                return None;
+            }
+            let size = match name.to_string().as_str() {
+                // An IDL char is a single byte
+                "bool" | "u8" | "i8" | "char" => 1,
+                "u16" | "i16" => 2,
+                "u32" | "i32" | "f32" => 4,
+                "u64" | "i64" | "f64" => 8,
+                _ => return None,
+            };
+            Some((size, size.min(XCDR2_MAX_ALIGNMENT)))
+        }
+        Type::Array(array) => {
+            let (size, alignment) = fixed_size(&array.elem)?;
+            let len = match &array.len {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Int(len) => len.base10_parse::<usize>().ok()?,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            Some((size * len, alignment))
+        }
+        Type::Paren(paren) => fixed_size(&paren.elem),
+        Type::Group(group) => fixed_size(&group.elem),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_max_key_size() {
+    let size = |types: &[&str]| {
+        let types: Vec<Type> = types.iter().map(|t| syn::parse_str(t).unwrap()).collect();
+        max_key_size(types.iter().map(|ty| (ty, KeySize::Type)))
+    };
+    assert_eq!(size(&["u32"]), Some(4));
+    assert_eq!(size(&["u8", "u64"]), Some(12));
+    assert_eq!(size(&["u8", "[u16; 3]", "bool"]), Some(9));
+    assert_eq!(size(&["[[u8; 4]; 4]", "i8"]), Some(17));
+    assert_eq!(size(&["char", "u16"]), Some(4));
+    assert_eq!(size(&["u32", "String"]), None);
+    assert_eq!(size(&["[u8; N]"]), None);
+    assert_eq!(
+        size(&["std::primitive::u8", "::core::primitive::u32"]),
+        Some(8)
+    );
+    assert_eq!(size(&["common::Id"]), None);
+
+    let given = |sizes: &[KeySize]| {
+        let ty: Type = syn::parse_str("Kind").unwrap();
+        max_key_size(sizes.iter().map(|size| (&ty, *size)))
+    };
+    assert_eq!(
+        given(&[KeySize::Fixed(1, 1), KeySize::Fixed(4, 4)]),
+        Some(8)
+    );
+    assert_eq!(given(&[KeySize::Type]), None);
+
+    let bounded = |types: &[(&str, Option<usize>)]| {
+        let types: Vec<(Type, KeySize)> = types
+            .iter()
+            .map(|(t, bound)| {
+                let size = bound.map_or(KeySize::Type, KeySize::Bound);
+                (syn::parse_str(t).unwrap(), size)
+            })
+            .collect();
+        max_key_size(types.iter().map(|(ty, size)| (ty, *size)))
+    };
+    assert_eq!(bounded(&[("String", Some(8))]), Some(13));
+    assert_eq!(bounded(&[("u8", None), ("String", Some(2))]), Some(11));
+    assert_eq!(bounded(&[("Vec<u16>", Some(3)), ("u8", None)]), Some(11));
+    assert_eq!(bounded(&[("Vec<[u8; 2]>", Some(2))]), Some(12));
+    assert_eq!(bounded(&[("Vec<String>", Some(2))]), None);
+}
+
+#[test]
+fn test_derive_errors() {
+    let error = |input: &str| {
+        let input: DeriveInput = syn::parse_str(input).unwrap();
+        topic_type(&input).unwrap_err().to_string()
+    };
+    assert_eq!(
+        error("enum E { A }"),
+        "TopicType can only be derived for structs"
+    );
+    assert_eq!(
+        error("struct S<T> { t: T }"),
+        "TopicType can't be derived for generic types"
+    );
+    assert_eq!(
+        error("#[appendable] #[mutable] struct S { a: u8 }"),
+        "the extensibility is already set"
+    );
+    assert_eq!(
+        error("#[r#final] #[appendable] struct S { a: u8 }"),
+        "the extensibility is already set"
+    );
+    assert_eq!(
+        error("#[type_name = 1] struct S { a: u8 }"),
+        "expected a string"
+    );
+    assert_eq!(
+        error("struct S { #[key(bound = 2)] a: u8 }"),
+        "the bound is only valid for strings and sequences"
+    );
+    assert_eq!(
+        error("struct S { #[key(len = 2)] a: String }"),
+        "expected `bound`, `size` or `align`"
+    );
+    assert_eq!(
+        error("struct S { #[key(bound = 2, size = 8)] a: String }"),
+        "the bound and the size are exclusive"
+    );
+    assert_eq!(
+        error("struct S { #[key(align = 2)] a: Kind }"),
+        "the alignment is only valid with a size"
+    );
+    assert_eq!(
+        error("struct S { #[key(size = 0)] a: Kind }"),
+        "the size must be positive"
+    );
+    assert_eq!(
+        error("struct S { #[key(size = 6, align = 3)] a: Kind }"),
+        "the alignment must be 1, 2, 4 or 8"
+    );
+}
+
+#[test]
+fn test_derive_extensibility() {
+    let derive = |input: &str| {
+        let input: DeriveInput = syn::parse_str(input).unwrap();
+        topic_type(&input).unwrap().to_string()
+    };
+    assert!(!derive("#[r#final] struct S { a: u8 }").contains("Appendable"));
+    assert!(derive("#[appendable] struct S { a: u8 }").contains(":: Appendable < Self >"));
+    assert!(derive("#[mutable] struct S { a: u8 }").contains(":: Mutable < Self >"));
+    let keyed = derive("struct S(#[key] u32, String);");
+    assert!(keyed.contains("fn is_keyed"));
+    assert!(keyed.contains(&quote!(&self.0).to_string()));
+    assert!(!derive("struct S { a: u8 }").contains("fn key"));
+    let bounded = derive("struct S { #[key(bound = 8)] name: String }");
+    assert!(bounded.contains(&quote!(::std::option::Option::Some(13usize)).to_string()));
+    let sized = derive("struct S { #[key(size = 1)] a: u8, #[key(size = 6)] b: Id }");
+    assert!(sized.contains(&quote!(::std::option::Option::Some(8usize)).to_string()));
+    let sized = derive("struct S { #[key(size = 1)] a: u8, #[key(size = 6, align = 4)] b: Id }");
+    assert!(sized.contains(&quote!(::std::option::Option::Some(10usize)).to_string()));
+    let mutable = derive("#[mutable] struct S { a: u8, #[key] b: u32, #[key] c: u16 }");
+    assert!(mutable.contains(&quote!(&[1u32, 2u32]).to_string()));
+}
+
+#[test]
+fn test_derive_element() {
+    let derive = |input: &str| {
+        let input: DeriveInput = syn::parse_str(input).unwrap();
+        element(&input).unwrap().to_string()
+    };
+    let primitive = |value: bool| format!("const PRIMITIVE : bool = {value}");
+    assert!(derive("enum Color { Red, Green }").contains(&primitive(true)));
+    assert!(derive("enum Shape { Point, Circle(f32) }").contains(&primitive(false)));
+    assert!(derive("struct S { a: u8 }").contains(&primitive(false)));
+    assert!(derive("struct Id(u32);").contains(
+        &quote!(const PRIMITIVE: bool = <u32 as ::cyclors::cdr::Element>::PRIMITIVE).to_string()
+    ));
+    assert!(derive("struct W<T>(T, u8);").contains(&primitive(false)));
+}
@@ -0,0 +1,991 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::parser::{
+    BinaryOp, ConstExpr, Definition, Enum, Extensibility, Label, Location, Member, Primitive,
+    ScopedName, Struct, TypeSpec, Typedef, Union,
+};
+use crate::SyntaxError;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// The lints of the generated items, whose names are the IDL ones.
+const ALLOW: &str =
+    "#[allow(non_camel_case_types, non_snake_case, non_upper_case_globals, clippy::all)]";
+
+/// The attribute of the sequence members, for their empty sequences to have a DHEADER if their
+/// elements are not primitive.
+const SEQUENCE: &str = "#[serde(with = \"::cyclors::cdr::sequence\")]";
+
+/// serde only implements its traits for arrays of up to 32 elements.
+const MAX_ARRAY_LEN: i128 = 32;
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "union", "unsafe", "unsized",
+    "use", "virtual", "where", "while", "yield",
+];
+
+/// Generates the Rust code of the IDL definitions.
+pub(crate) fn generate(
+    definitions: &[Definition],
+    topic_types: bool,
+) -> Result<String, SyntaxError> {
+    let mut generator = Generator {
+        symbols: HashMap::new(),
+        topic_types,
+        root: ModuleOut::default(),
+    };
+    generator.definitions(definitions, &mut Vec::new())?;
+    let mut out = String::from("// Generated by cyclors-idl, do not edit.\n");
+    generator.root.render(&mut out, 0);
+    Ok(out)
+}
+
+enum Symbol {
+    Module,
+    Struct {
+        extensibility: Extensibility,
+        /// The members, including the inherited ones, with the scope they are declared in.
+        members: Vec<(Member, Vec<String>)>,
+    },
+    Union,
+    Enum,
+    Enumerator {
+        /// The Rust path of the enumerator, in its enum.
+        path: Vec<String>,
+        value: u32,
+    },
+    Typedef {
+        ty: TypeSpec,
+        /// Whether the typedef is an array of `ty`.
+        array: bool,
+    },
+    Const(Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(i128),
+    Float(f64),
+    Char(char),
+    Str(String),
+    Bool(bool),
+    Enumerator { path: Vec<String>, value: u32 },
+}
+
+#[derive(Default)]
+struct ModuleOut {
+    items: Vec<Item>,
+    modules: HashMap<String, ModuleOut>,
+}
+
+enum Item {
+    Code(String),
+    Module(String),
+}
+
+impl ModuleOut {
+    fn render(&self, out: &mut String, depth: usize) {
+        let indent = "    ".repeat(depth);
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 || depth == 0 {
+                out.push('\n');
+            }
+            if depth == 0 {
+                out.push_str(ALLOW);
+                out.push('\n');
+            }
+            match item {
+                Item::Code(code) => {
+                    for line in code.lines() {
+                        let _ = writeln!(out, "{indent}{line}");
+                    }
+                }
+                Item::Module(name) => {
+                    let _ = writeln!(out, "{indent}pub mod {} {{", escape(name));
+                    self.modules[name].render(out, depth + 1);
+                    let _ = writeln!(out, "{indent}}}");
+                }
+            }
+        }
+    }
+}
+
+struct Generator {
+    /// The symbols, by their IDL scoped name.
+    symbols: HashMap<Vec<String>, Symbol>,
+    topic_types: bool,
+    root: ModuleOut,
+}
+
+fn error<T>(location: Location, message: impl Into<String>) -> Result<T, SyntaxError> {
+    Err(SyntaxError {
+        file: location.file,
+        line: location.line,
+        message: message.into(),
+    })
+}
+
+fn at(location: Location) -> impl Fn(String) -> SyntaxError {
+    move |message| SyntaxError {
+        file: location.file,
+        line: location.line,
+        message,
+    }
+}
+
+/// Escapes the IDL identifiers which are Rust keywords.
+fn escape(name: &str) -> String {
+    match name {
+        // These can't be raw identifiers
+        "self" | "Self" | "super" | "crate" | "_" => format!("{name}_"),
+        name if KEYWORDS.contains(&name) => format!("r#{name}"),
+        name => name.to_string(),
+    }
+}
+
+/// Converts the name of a union member to the name of its variant.
+fn camel_case(name: &str) -> String {
+    let mut camel = String::new();
+    let mut upper = true;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            camel.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    if camel.is_empty() {
+        camel.push('_');
+    }
+    escape(&camel)
+}
+
+fn scoped(scope: &[String], name: &str) -> Vec<String> {
+    let mut path = scope.to_vec();
+    path.push(name.to_string());
+    path
+}
+
+impl Generator {
+    fn module_mut(&mut self, scope: &[String]) -> &mut ModuleOut {
+        let mut module = &mut self.root;
+        for name in scope {
+            module = module.modules.get_mut(name).unwrap();
+        }
+        module
+    }
+
+    fn push(&mut self, scope: &[String], code: String) {
+        self.module_mut(scope).items.push(Item::Code(code));
+    }
+
+    fn declare(
+        &mut self,
+        scope: &[String],
+        name: &str,
+        symbol: Symbol,
+        location: Location,
+    ) -> Result<(), SyntaxError> {
+        let path = scoped(scope, name);
+        if self.symbols.contains_key(&path) {
+            return error(
+                location,
+                format!("`{}` is already defined", path.join("::")),
+            );
+        }
+        self.symbols.insert(path, symbol);
+        Ok(())
+    }
+
+    /// Looks up a scoped name from the given scope, then from its enclosing scopes.
+    fn resolve(&self, scope: &[String], name: &ScopedName) -> Result<Vec<String>, String> {
+        let depths = if name.absolute {
+            0..=0
+        } else {
+            0..=scope.len()
+        };
+        for depth in depths.rev() {
+            let mut path = scope[..depth].to_vec();
+            path.extend(name.parts.iter().cloned());
+            if self.symbols.contains_key(&path) {
+                return Ok(path);
+            }
+        }
+        Err(format!("unknown name `{name}`"))
+    }
+
+    /// The Rust path of an item, relative to the module of the given scope.
+    fn rust_path(&self, from: &[String], target: &[String]) -> String {
+        let common = from
+            .iter()
+            .zip(target)
+            .take_while(|(a, b)| a == b)
+            .count()
+            // The item itself is never a module
+            .min(target.len() - 1);
+        let mut path: Vec<String> = vec![String::from("super"); from.len() - common];
+        path.extend(target[common..].iter().map(|name| escape(name)));
+        path.join("::")
+    }
+
+    /// Resolves a type through its typedefs, with the scope it is declared in.
+    fn resolve_type(
+        &self,
+        ty: &TypeSpec,
+        scope: &[String],
+    ) -> Result<(TypeSpec, Vec<String>), String> {
+        let mut ty = ty.clone();
+        let mut scope = scope.to_vec();
+        while let TypeSpec::Named(name) = &ty {
+            let path = self.resolve(&scope, name)?;
+            match &self.symbols[&path] {
+                Symbol::Typedef { ty: aliased, array } if !array => {
+                    ty = aliased.clone();
+                    scope = path[..path.len() - 1].to_vec();
+                }
+                _ => break,
+            }
+        }
+        Ok((ty, scope))
+    }
+
+    fn definitions(
+        &mut self,
+        definitions: &[Definition],
+        scope: &mut Vec<String>,
+    ) -> Result<(), SyntaxError> {
+        for definition in definitions {
+            match definition {
+                Definition::Module(module) => {
+                    let path = scoped(scope, &module.name);
+                    match self.symbols.get(&path) {
+                        Some(Symbol::Module) => (),
+                        Some(_) => {
+                            return error(
+                                module.location,
+                                format!("`{}` is already defined", path.join("::")),
+                            )
+                        }
+                        None => {
+                            self.symbols.insert(path, Symbol::Module);
+                            let parent = self.module_mut(scope);
+                            parent.items.push(Item::Module(module.name.clone()));
+                            parent
+                                .modules
+                                .insert(module.name.clone(), ModuleOut::default());
+                        }
+                    }
+                    scope.push(module.name.clone());
+                    self.definitions(&module.definitions, scope)?;
+                    scope.pop();
+                }
+                Definition::Struct(s) => self.structure(s, scope)?,
+                Definition::Union(u) => self.union(u, scope)?,
+                Definition::Enum(e) => self.enumeration(e, scope)?,
+                Definition::Typedef(typedef) => self.typedef(typedef, scope)?,
+                Definition::Const(c) => {
+                    let value = self.eval(&c.value, scope).map_err(at(c.location))?;
+                    let (ty, literal) = self
+                        .const_value(&c.ty, value.clone(), scope)
+                        .map_err(at(c.location))?;
+                    self.declare(scope, &c.name, Symbol::Const(value), c.location)?;
+                    let code = format!("pub const {}: {ty} = {literal};", escape(&c.name));
+                    self.push(scope, code);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn structure(&mut self, s: &Struct, scope: &[String]) -> Result<(), SyntaxError> {
+        let mut members = Vec::new();
+        let mut extensibility = s.extensibility;
+        if let Some(base) = &s.base {
+            let path = self.resolve(scope, base).map_err(at(s.location))?;
+            match &self.symbols[&path] {
+                Symbol::Struct {
+                    extensibility: base_extensibility,
+                    members: base_members,
+                } => {
+                    members.extend(base_members.iter().cloned());
+                    extensibility = extensibility.or(Some(*base_extensibility));
+                }
+                _ => return error(s.location, format!("`{base}` is not a struct")),
+            }
+        }
+        let extensibility = extensibility.unwrap_or(Extensibility::Final);
+        members.extend(s.members.iter().map(|m| (m.clone(), scope.to_vec())));
+        // Declared before its members, which may refer to it in sequences
+        self.declare(
+            scope,
+            &s.name,
+            Symbol::Struct {
+                extensibility,
+                members: Vec::new(),
+            },
+            s.location,
+        )?;
+
+        let topic_type = self.topic_types && !s.nested;
+        let mut code = String::from(
+            "#[derive(Debug, Clone, PartialEq, ::serde::Serialize, ::serde::Deserialize",
+        );
+        if topic_type {
+            code.push_str(", ::cyclors::typed::TopicType");
+        }
+        code.push_str(")]\n");
+        if topic_type {
+            let _ = writeln!(
+                code,
+                "#[type_name = \"{}\"]",
+                scoped(scope, &s.name).join("::")
+            );
+            match extensibility {
+                Extensibility::Final => (),
+                Extensibility::Appendable => code.push_str("#[appendable]\n"),
+                Extensibility::Mutable => code.push_str("#[mutable]\n"),
+            }
+        }
+        let _ = writeln!(code, "pub struct {} {{", escape(&s.name));
+        for (i, (member, member_scope)) in members.iter().enumerate() {
+            if let Some(id) = &member.id {
+                // The serializer identifies the members by their position
+                let id = self.eval(id, member_scope).map_err(at(member.location))?;
+                if id != Value::Int(i as i128) {
+                    return error(member.location, "the member ids must be sequential from 0");
+                }
+            }
+            if member.key && member.optional {
+                return error(member.location, "key members can't be optional");
+            }
+            let mut ty = self
+                .member_type(member, member_scope, scope)
+                .map_err(at(member.location))?;
+            if member.key && topic_type {
+                // The derive only knows the primitive, string and sequence types by their
+                // name, not their aliases
+                let (attribute, resolved) = self
+                    .key_attribute(member, member_scope, scope)
+                    .map_err(at(member.location))?;
+                ty = resolved;
+                let _ = writeln!(code, "    {attribute}");
+            }
+            if self
+                .is_sequence(member, member_scope)
+                .map_err(at(member.location))?
+            {
+                let _ = writeln!(code, "    {SEQUENCE}");
+            }
+            let _ = writeln!(code, "    pub {}: {ty},", escape(&member.name));
+        }
+        code.push('}');
+        self.symbols.insert(
+            scoped(scope, &s.name),
+            Symbol::Struct {
+                extensibility,
+                members,
+            },
+        );
+        self.push(scope, code);
+        self.element(scope, &s.name, false);
+        Ok(())
+    }
+
+    fn union(&mut self, u: &Union, scope: &[String]) -> Result<(), SyntaxError> {
+        // The serializer writes the index of the variant as a 32 bits discriminator
+        let (discriminator, discriminator_scope) = self
+            .resolve_type(&u.discriminator, scope)
+            .map_err(at(u.location))?;
+        let valid = match &discriminator {
+            TypeSpec::Primitive(Primitive::Long | Primitive::ULong) => true,
+            TypeSpec::Named(name) => {
+                let path = self
+                    .resolve(&discriminator_scope, name)
+                    .map_err(at(u.location))?;
+                matches!(self.symbols[&path], Symbol::Enum)
+            }
+            _ => false,
+        };
+        if !valid {
+            return error(
+                u.location,
+                "the discriminator of a union must be a 32 bits integer or an enum",
+            );
+        }
+        self.declare(scope, &u.name, Symbol::Union, u.location)?;
+
+        let mut code = format!(
+            "#[derive(Debug, Clone, PartialEq, ::serde::Serialize, ::serde::Deserialize)]\npub enum {} {{\n",
+            escape(&u.name)
+        );
+        for (i, case) in u.cases.iter().enumerate() {
+            let member = &case.member;
+            let label = match case.labels.as_slice() {
+                [Label::Value(label)] => self.eval(label, scope).map_err(at(member.location))?,
+                _ => {
+                    return error(
+                        member.location,
+                        "each case of a union must have a single label",
+                    )
+                }
+            };
+            let label = match label {
+                Value::Int(v) => v,
+                Value::Enumerator { value, .. } => value as i128,
+                _ => return error(member.location, "the labels of a union must be integers"),
+            };
+            if label != i as i128 {
+                return error(
+                    member.location,
+                    "the labels of a union must be sequential from 0",
+                );
+            }
+            if member.optional {
+                return error(member.location, "union members can't be optional");
+            }
+            let mut ty = self
+                .member_type(member, scope, scope)
+                .map_err(at(member.location))?;
+            if self
+                .is_sequence(member, scope)
+                .map_err(at(member.location))?
+            {
+                ty = format!("{SEQUENCE} {ty}");
+            }
+            let _ = writeln!(code, "    {}({ty}),", camel_case(&member.name));
+        }
+        code.push('}');
+        self.push(scope, code);
+        self.element(scope, &u.name, false);
+        Ok(())
+    }
+
+    fn enumeration(&mut self, e: &Enum, scope: &[String]) -> Result<(), SyntaxError> {
+        self.declare(scope, &e.name, Symbol::Enum, e.location)?;
+        let mut code = format!(
+            "#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, ::serde::Serialize, ::serde::Deserialize)]\npub enum {} {{\n",
+            escape(&e.name)
+        );
+        for (i, enumerator) in e.enumerators.iter().enumerate() {
+            if let Some(value) = &enumerator.value {
+                // The serializer writes the index of the variant
+                if self.eval(value, scope).map_err(at(e.location))? != Value::Int(i as i128) {
+                    return error(
+                        e.location,
+                        "the values of an enum must be sequential from 0",
+                    );
+                }
+            }
+            if i == 0 {
+                code.push_str("    #[default]\n");
+            }
+            let _ = writeln!(code, "    {},", escape(&enumerator.name));
+            // Enumerators are in the scope of their enum
+            let symbol = Symbol::Enumerator {
+                path: scoped(&scoped(scope, &e.name), &enumerator.name),
+                value: i as u32,
+            };
+            self.declare(scope, &enumerator.name, symbol, e.location)?;
+        }
+        code.push('}');
+        self.push(scope, code);
+        // Enums are primitive, their sequences have no DHEADER
+        self.element(scope, &e.name, true);
+        Ok(())
+    }
+
+    /// Implements `Element` for a generated type, for `sequence` to tell whether its empty
+    /// sequences start with a DHEADER.
+    fn element(&mut self, scope: &[String], name: &str, primitive: bool) {
+        let code = format!(
+            "impl ::cyclors::cdr::Element for {} {{\n    const PRIMITIVE: bool = {primitive};\n}}",
+            escape(name)
+        );
+        self.push(scope, code);
+    }
+
+    fn typedef(&mut self, typedef: &Typedef, scope: &[String]) -> Result<(), SyntaxError> {
+        if !typedef.dimensions.is_empty() {
+            self.check_element(&typedef.ty, scope)
+                .map_err(at(typedef.location))?;
+        }
+        let ty = self
+            .rust_type(&typedef.ty, scope, scope)
+            .and_then(|ty| self.array_type(ty, &typedef.dimensions, scope))
+            .map_err(at(typedef.location))?;
+        let symbol = Symbol::Typedef {
+            ty: typedef.ty.clone(),
+            array: !typedef.dimensions.is_empty(),
+        };
+        self.declare(scope, &typedef.name, symbol, typedef.location)?;
+        let code = format!("pub type {} = {ty};", escape(&typedef.name));
+        self.push(scope, code);
+        Ok(())
+    }
+
+    /// The Rust type of a member declared in `scope`, for the module of `from`.
+    fn member_type(
+        &self,
+        member: &Member,
+        scope: &[String],
+        from: &[String],
+    ) -> Result<String, String> {
+        if !member.dimensions.is_empty() {
+            self.check_element(&member.ty, scope)?;
+        }
+        let ty = self.rust_type(&member.ty, scope, from)?;
+        let ty = self.array_type(ty, &member.dimensions, scope)?;
+        Ok(match member.optional {
+            true => format!("Option<{ty}>"),
+            false => ty,
+        })
+    }
+
+    /// Whether a member is a sequence, possibly optional, serialized with `sequence`.
+    fn is_sequence(&self, member: &Member, scope: &[String]) -> Result<bool, String> {
+        if !member.dimensions.is_empty() {
+            return Ok(false);
+        }
+        let (resolved, _) = self.resolve_type(&member.ty, scope)?;
+        Ok(matches!(resolved, TypeSpec::Sequence(..)))
+    }
+
+    /// Checks that the elements of a sequence or array are not unions, which the serializer
+    /// doesn't support in collections.
+    fn check_element(&self, ty: &TypeSpec, scope: &[String]) -> Result<(), String> {
+        if let (TypeSpec::Named(name), element_scope) = self.resolve_type(ty, scope)? {
+            let path = self.resolve(&element_scope, &name)?;
+            if let Symbol::Union = self.symbols[&path] {
+                return Err(String::from(
+                    "sequences and arrays of unions are not supported",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The `#[key]` attribute of a key member, with the bound of the bounded strings and
+    /// sequences and the size of the enums, and the Rust type of the member resolved through
+    /// its typedefs, for the module of `from`.
+    fn key_attribute(
+        &self,
+        member: &Member,
+        scope: &[String],
+        from: &[String],
+    ) -> Result<(String, String), String> {
+        let (resolved, resolved_scope) = self.resolve_type(&member.ty, scope)?;
+        let ty = self.rust_type(&resolved, &resolved_scope, from)?;
+        let ty = self.array_type(ty, &member.dimensions, scope)?;
+        let attribute = match &resolved {
+            TypeSpec::String(Some(bound)) | TypeSpec::Sequence(_, Some(bound))
+                if member.dimensions.is_empty() =>
+            {
+                match self.eval(bound, &resolved_scope)? {
+                    Value::Int(bound) if bound > 0 => format!("#[key(bound = {bound})]"),
+                    _ => return Err(String::from("invalid bound")),
+                }
+            }
+            TypeSpec::Named(name)
+                if matches!(
+                    self.symbols[&self.resolve(&resolved_scope, name)?],
+                    Symbol::Enum
+                ) =>
+            {
+                // The enums are serialized as 32 bits integers
+                let mut len = 1;
+                for dimension in &member.dimensions {
+                    if let Value::Int(dimension) = self.eval(dimension, scope)? {
+                        len *= dimension;
+                    }
+                }
+                format!("#[key(size = {})]", 4 * len)
+            }
+            _ => String::from("#[key]"),
+        };
+        Ok((attribute, ty))
+    }
+
+    fn array_type(
+        &self,
+        mut ty: String,
+        dimensions: &[ConstExpr],
+        scope: &[String],
+    ) -> Result<String, String> {
+        for dimension in dimensions.iter().rev() {
+            match self.eval(dimension, scope)? {
+                Value::Int(len) if (1..=MAX_ARRAY_LEN).contains(&len) => {
+                    ty = format!("[{ty}; {len}]");
+                }
+                Value::Int(len) if len > MAX_ARRAY_LEN => {
+                    return Err(format!(
+                        "arrays of more than {MAX_ARRAY_LEN} elements are not supported"
+                    ))
+                }
+                _ => return Err(String::from("invalid array dimension")),
+            }
+        }
+        Ok(ty)
+    }
+
+    /// The Rust type of a type spec resolved in `scope`, for the module of `from`.
+    fn rust_type(
+        &self,
+        ty: &TypeSpec,
+        scope: &[String],
+        from: &[String],
+    ) -> Result<String, String> {
+        let ty = match ty {
+            TypeSpec::Primitive(primitive) => match primitive {
+                Primitive::Boolean => "bool",
+                Primitive::UInt8 => "u8",
+                Primitive::Char => "char",
+                Primitive::Int8 => "i8",
+                Primitive::Short => "i16",
+                Primitive::UShort => "u16",
+                Primitive::Long => "i32",
+                Primitive::ULong => "u32",
+                Primitive::LongLong => "i64",
+                Primitive::ULongLong => "u64",
+                Primitive::Float => "f32",
+                Primitive::Double => "f64",
+                Primitive::WChar => return Err(String::from("`wchar` is not supported")),
+                Primitive::LongDouble => {
+                    return Err(String::from("`long double` is not supported"))
+                }
+            }
+            .to_string(),
+            // The bounds are not checked
+            TypeSpec::String(_) => String::from("String"),
+            TypeSpec::WString(_) => return Err(String::from("`wstring` is not supported")),
+            TypeSpec::Fixed => return Err(String::from("`fixed` is not supported")),
+            TypeSpec::Sequence(element, _) => {
+                self.check_element(element, scope)?;
+                format!("Vec<{}>", self.rust_type(element, scope, from)?)
+            }
+            TypeSpec::Map(key, value, _) => format!(
+                "::std::collections::BTreeMap<{}, {}>",
+                self.rust_type(key, scope, from)?,
+                self.rust_type(value, scope, from)?
+            ),
+            TypeSpec::Named(name) => {
+                let path = self.resolve(scope, name)?;
+                let rust_path = self.rust_path(from, &path);
+                match &self.symbols[&path] {
+                    // The extensibility of the nested structs is set by their wrapper
+                    Symbol::Struct {
+                        extensibility: Extensibility::Appendable,
+                        ..
+                    } => format!("::cyclors::cdr::Appendable<{rust_path}>"),
+                    Symbol::Struct {
+                        extensibility: Extensibility::Mutable,
+                        ..
+                    } => format!("::cyclors::cdr::Mutable<{rust_path}>"),
+                    Symbol::Struct { .. }
+                    | Symbol::Union
+                    | Symbol::Enum
+                    | Symbol::Typedef { .. } => rust_path,
+                    _ => return Err(format!("`{name}` is not a type")),
+                }
+            }
+        };
+        Ok(ty)
+    }
+
+    /// The Rust type and value of a constant.
+    fn const_value(
+        &self,
+        ty: &TypeSpec,
+        value: Value,
+        scope: &[String],
+    ) -> Result<(String, String), String> {
+        let (resolved, _) = self.resolve_type(ty, scope)?;
+        let rust_type = match resolved {
+            TypeSpec::String(_) => String::from("&str"),
+            _ => self.rust_type(ty, scope, scope)?,
+        };
+        let mismatch = || format!("invalid value for a constant of type `{rust_type}`");
+        let literal = match (&resolved, value) {
+            (TypeSpec::Primitive(primitive), Value::Int(v)) => {
+                let range = match primitive {
+                    Primitive::UInt8 => 0..=u8::MAX as i128,
+                    Primitive::Int8 => i8::MIN as i128..=i8::MAX as i128,
+                    Primitive::Short => i16::MIN as i128..=i16::MAX as i128,
+                    Primitive::UShort => 0..=u16::MAX as i128,
+                    Primitive::Long => i32::MIN as i128..=i32::MAX as i128,
+                    Primitive::ULong => 0..=u32::MAX as i128,
+                    Primitive::LongLong => i64::MIN as i128..=i64::MAX as i128,
+                    Primitive::ULongLong => 0..=u64::MAX as i128,
+                    Primitive::Float | Primitive::Double => {
+                        return Ok((rust_type, format!("{:?}", v as f64)))
+                    }
+                    _ => return Err(mismatch()),
+                };
+                if !range.contains(&v) {
+                    return Err(format!("`{v}` is out of the range of `{rust_type}`"));
+                }
+                v.to_string()
+            }
+            (TypeSpec::Primitive(Primitive::Float | Primitive::Double), Value::Float(v)) => {
+                format!("{v:?}")
+            }
+            (TypeSpec::Primitive(Primitive::Boolean), Value::Bool(v)) => v.to_string(),
+            (TypeSpec::Primitive(Primitive::Char), Value::Char(c)) if c.is_ascii() => {
+                format!("{c:?}")
+            }
+            (TypeSpec::String(_), Value::Str(s)) => format!("{s:?}"),
+            (TypeSpec::Named(_), Value::Enumerator { path, .. }) => self.rust_path(scope, &path),
+            _ => return Err(mismatch()),
+        };
+        Ok((rust_type, literal))
+    }
+
+    fn eval(&self, expr: &ConstExpr, scope: &[String]) -> Result<Value, String> {
+        let value = match expr {
+            ConstExpr::Int(v) => Value::Int(*v as i128),
+            ConstExpr::Float(v) => {
+                Value::Float(v.parse().map_err(|_| format!("invalid float `{v}`"))?)
+            }
+            ConstExpr::Char(c) => Value::Char(*c),
+            ConstExpr::Str(s) => Value::Str(s.clone()),
+            ConstExpr::Bool(v) => Value::Bool(*v),
+            ConstExpr::Name(name) => {
+                let path = self.resolve(scope, name)?;
+                match &self.symbols[&path] {
+                    Symbol::Const(value) => value.clone(),
+                    Symbol::Enumerator { path, value } => Value::Enumerator {
+                        path: path.clone(),
+                        value: *value,
+                    },
+                    _ => return Err(format!("`{name}` is not a constant")),
+                }
+            }
+            ConstExpr::Unary(op, expr) => match (op, self.eval(expr, scope)?) {
+                ('-', Value::Int(v)) => Value::Int(-v),
+                ('-', Value::Float(v)) => Value::Float(-v),
+                ('+', v @ (Value::Int(_) | Value::Float(_))) => v,
+                ('~', Value::Int(v)) => Value::Int(!v),
+                _ => return Err(format!("invalid operand of `{op}`")),
+            },
+            ConstExpr::Binary(op, lhs, rhs) => {
+                binary(*op, self.eval(lhs, scope)?, self.eval(rhs, scope)?)?
+            }
+        };
+        Ok(value)
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Value, rhs: Value) -> Result<Value, String> {
+    let overflow = || String::from("overflow in a constant expression");
+    let value = match (lhs, rhs) {
+        (Value::Int(lhs), Value::Int(rhs)) => Value::Int(match op {
+            BinaryOp::Or => lhs | rhs,
+            BinaryOp::Xor => lhs ^ rhs,
+            BinaryOp::And => lhs & rhs,
+            BinaryOp::Shl => u32::try_from(rhs)
+                .ok()
+                .filter(|rhs| *rhs < 64)
+                .map(|rhs| lhs << rhs)
+                .ok_or_else(overflow)?,
+            BinaryOp::Shr => u32::try_from(rhs)
+                .ok()
+                .filter(|rhs| *rhs < 64)
+                .map(|rhs| lhs >> rhs)
+                .ok_or_else(overflow)?,
+            BinaryOp::Add => lhs.checked_add(rhs).ok_or_else(overflow)?,
+            BinaryOp::Sub => lhs.checked_sub(rhs).ok_or_else(overflow)?,
+            BinaryOp::Mul => lhs.checked_mul(rhs).ok_or_else(overflow)?,
+            BinaryOp::Div => lhs
+                .checked_div(rhs)
+                .ok_or_else(|| String::from("division by zero"))?,
+            BinaryOp::Mod => lhs
+                .checked_rem(rhs)
+                .ok_or_else(|| String::from("division by zero"))?,
+        }),
+        (lhs @ (Value::Int(_) | Value::Float(_)), rhs @ (Value::Int(_) | Value::Float(_))) => {
+            let float = |v| match v {
+                Value::Int(v) => v as f64,
+                Value::Float(v) => v,
+                _ => unreachable!(),
+            };
+            let (lhs, rhs) = (float(lhs), float(rhs));
+            Value::Float(match op {
+                BinaryOp::Add => lhs + rhs,
+                BinaryOp::Sub => lhs - rhs,
+                BinaryOp::Mul => lhs * rhs,
+                BinaryOp::Div => lhs / rhs,
+                _ => return Err(String::from("invalid operation on floats")),
+            })
+        }
+        _ => return Err(String::from("invalid operands in a constant expression")),
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+fn generate_str(source: &str) -> Result<String, SyntaxError> {
+    let definitions = crate::parser::parse(crate::lexer::tokenize(source, 0)?)?;
+    generate(&definitions, true)
+}
+
+#[test]
+fn test_generate() {
+    let code = generate_str(
+        r#"
+        module a {
+            const unsigned short N = 1 + 2;
+            enum Kind { ONE, TWO };
+            module b {
+                @appendable @nested struct Point { long x; };
+                typedef Point Points[N];
+            };
+            struct S : b::Point {
+                @key Kind kind;
+                b::Points points;
+                sequence<b::Point> more;
+                @optional string type;
+            };
+            union U switch (Kind) { case ONE: long one; case TWO: string long_name; };
+        };
+        module a { const Kind K = a::TWO; };
+        "#,
+    )
+    .unwrap();
+    assert!(code.contains("pub mod a {\n    pub const N: u16 = 3;\n"));
+    assert!(code.contains("        #[default]\n        ONE,\n        TWO,\n"));
+    assert!(code.contains("pub type Points = [::cyclors::cdr::Appendable<Point>; 3];"));
+    assert!(code.contains("        pub struct Point {\n            pub x: i32,\n        }"));
+    assert!(code.contains(
+        "    #[type_name = \"a::S\"]\n    #[appendable]\n    pub struct S {\n        pub x: i32,\n        #[key(size = 4)]\n        pub kind: Kind,\n        pub points: b::Points,\n        #[serde(with = \"::cyclors::cdr::sequence\")]\n        pub more: Vec<::cyclors::cdr::Appendable<b::Point>>,\n        pub r#type: Option<String>,\n    }"
+    ));
+    assert!(code.contains("        One(i32),\n        LongName(String),\n"));
+    assert!(code.contains(
+        "    impl ::cyclors::cdr::Element for Kind {\n        const PRIMITIVE: bool = true;\n    }"
+    ));
+    assert!(code.contains(
+        "    impl ::cyclors::cdr::Element for U {\n        const PRIMITIVE: bool = false;\n    }"
+    ));
+    assert!(code.contains("pub const K: Kind = Kind::TWO;"));
+    // The reopened module is merged, and the nested struct is not a topic type
+    assert_eq!(code.matches("pub mod a").count(), 1);
+    assert_eq!(code.matches("TopicType").count(), 1);
+
+    // The key members have their type resolved through its typedefs, with the bound of the
+    // bounded ones and the size of the enums
+    let code = generate_str(
+        "module m { typedef string<8> Name; typedef long Id; enum E { X }; }; const long N = 4;\n\
+         struct K { @key m::Name name; @key sequence<octet, N> ids; @key string any;\n\
+         @key m::Id id; @key m::E e[2]; };",
+    )
+    .unwrap();
+    assert!(code.contains(
+        "    #[key(bound = 8)]\n    pub name: String,\n    #[key(bound = 4)]\n    #[serde(with = \"::cyclors::cdr::sequence\")]\n    pub ids: Vec<u8>,\n    #[key]\n    pub any: String,\n    #[key]\n    pub id: i32,\n    #[key(size = 8)]\n    pub e: [m::E; 2],\n"
+    ));
+
+    // Including the sequences of typedefs and in unions
+    let code = generate_str(
+        "typedef sequence<long> Longs; struct S { Longs a; @optional sequence<string> b; };\n\
+         union U switch (long) { case 0: Longs a; };",
+    )
+    .unwrap();
+    assert_eq!(
+        code.matches("#[serde(with = \"::cyclors::cdr::sequence\")]")
+            .count(),
+        3
+    );
+    assert!(code.contains("    A(#[serde(with = \"::cyclors::cdr::sequence\")] Longs),\n"));
+
+    let code = generate_str("module m { struct T { long t; }; }; struct S { m::T t; };").unwrap();
+    assert!(code.contains("pub t: m::T,"));
+    let code = generate_str("module m { struct S { ::T t; }; }; struct T { long t; };");
+    assert_eq!(code.unwrap_err().message, "unknown name `::T`");
+}
+
+#[test]
+fn test_generate_errors() {
+    let error = |source| generate_str(source).unwrap_err();
+    assert_eq!(error("struct S {};\nstruct S {};").line, 2);
+    assert_eq!(
+        error("struct S {};\nstruct S {};").message,
+        "`S` is already defined"
+    );
+    assert_eq!(
+        error("enum E { A, @value(2) B };").message,
+        "the values of an enum must be sequential from 0"
+    );
+    assert_eq!(
+        error("union U switch (short) { case 0: long a; };").message,
+        "the discriminator of a union must be a 32 bits integer or an enum"
+    );
+    assert_eq!(
+        error("union U switch (long) { case 1: long a; };").message,
+        "the labels of a union must be sequential from 0"
+    );
+    assert_eq!(
+        error("union U switch (long) { case 0: long a; default: long b; };").message,
+        "each case of a union must have a single label"
+    );
+    assert_eq!(
+        error("@mutable struct S { @id(1) long a; };").message,
+        "the member ids must be sequential from 0"
+    );
+    assert_eq!(
+        error("struct S { @key @optional long a; };").message,
+        "key members can't be optional"
+    );
+    assert_eq!(
+        error("struct S { long a[33]; };").message,
+        "arrays of more than 32 elements are not supported"
+    );
+    assert_eq!(
+        error("struct S { wstring a; };").message,
+        "`wstring` is not supported"
+    );
+    assert_eq!(
+        error("const octet C = 256;").message,
+        "`256` is out of the range of `u8`"
+    );
+    assert_eq!(
+        error("const long C = 1 / (2 - 2);").message,
+        "division by zero"
+    );
+    assert_eq!(
+        error("const long C = 1; struct S { C c; };").message,
+        "`C` is not a type"
+    );
+    // Including through typedefs
+    let union = "union U switch (long) { case 0: long a; };\n";
+    for source in [
+        "struct S { sequence<U> u; };",
+        "struct S { U u[2]; };",
+        "typedef U V; struct S { sequence<V, 2> v; };",
+        "typedef U Us[2];",
+    ] {
+        assert_eq!(
+            generate_str(&format!("{union}{source}"))
+                .unwrap_err()
+                .message,
+            "sequences and arrays of unions are not supported"
+        );
+    }
+    assert_eq!(
+        error("struct S { @key string<0> a; };").message,
+        "invalid bound"
+    );
+}
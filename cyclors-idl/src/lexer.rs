@@ -0,0 +1,326 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::SyntaxError;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    Ident(String),
+    Int(u64),
+    Float(String),
+    Char(char),
+    Str(String),
+    Punct(char),
+    /// `::`
+    Scope,
+    /// A preprocessor directive, without the `#`.
+    Directive(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "`{ident}`"),
+            Token::Int(v) => write!(f, "`{v}`"),
+            Token::Float(v) => write!(f, "`{v}`"),
+            Token::Char(c) => write!(f, "`{c:?}`"),
+            Token::Str(s) => write!(f, "`{s:?}`"),
+            Token::Punct(c) => write!(f, "`{c}`"),
+            Token::Scope => write!(f, "`::`"),
+            Token::Directive(d) => write!(f, "`#{d}`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Spanned {
+    pub token: Token,
+    pub file: usize,
+    pub line: usize,
+}
+
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    file: usize,
+    line: usize,
+    /// Whether only whitespaces were read since the beginning of the line.
+    line_start: bool,
+}
+
+/// Splits the source of the `file`-th IDL file into tokens.
+pub(crate) fn tokenize(source: &str, file: usize) -> Result<Vec<Spanned>, SyntaxError> {
+    let mut lexer = Lexer {
+        chars: source.chars().peekable(),
+        file,
+        line: 1,
+        line_start: true,
+    };
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.next_token()? {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+impl Lexer<'_> {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, SyntaxError> {
+        Err(SyntaxError {
+            file: self.file,
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = true;
+        } else if !c.is_whitespace() {
+            self.line_start = false;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.chars.peek() == Some(&c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespaces_and_comments(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('/') => {
+                    let mut lookahead = self.chars.clone();
+                    lookahead.next();
+                    match lookahead.next() {
+                        Some('/') => {
+                            while !matches!(self.chars.peek(), None | Some('\n')) {
+                                self.chars.next();
+                            }
+                        }
+                        Some('*') => {
+                            let line = self.line;
+                            self.chars.next();
+                            self.chars.next();
+                            loop {
+                                match self.chars.next() {
+                                    Some('*') if self.chars.peek() == Some(&'/') => {
+                                        self.chars.next();
+                                        break;
+                                    }
+                                    Some('\n') => self.line += 1,
+                                    Some(_) => (),
+                                    None => {
+                                        return Err(SyntaxError {
+                                            file: self.file,
+                                            line,
+                                            message: String::from("unterminated comment"),
+                                        })
+                                    }
+                                }
+                            }
+                        }
+                        _ => return Ok(()),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Spanned>, SyntaxError> {
+        self.skip_whitespaces_and_comments()?;
+        let line = self.line;
+        let line_start = self.line_start;
+        let Some(c) = self.bump() else {
+            return Ok(None);
+        };
+        let token = match c {
+            '#' if line_start => {
+                let mut directive = String::new();
+                while !matches!(self.chars.peek(), None | Some('\n')) {
+                    directive.extend(self.chars.next());
+                }
+                Token::Directive(directive.trim().to_string())
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = String::from(c);
+                while let Some(&c) = self.chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    ident.push(c);
+                    self.bump();
+                }
+                Token::Ident(ident)
+            }
+            '0'..='9' => self.number(c)?,
+            '.' if self.chars.peek().is_some_and(char::is_ascii_digit) => self.number(c)?,
+            '\'' => {
+                let c = self.escaped_char()?;
+                if !self.eat('\'') {
+                    return self.error("unterminated character literal");
+                }
+                Token::Char(c)
+            }
+            '"' => {
+                let mut s = String::new();
+                while !self.eat('"') {
+                    if self.chars.peek().is_none() {
+                        return self.error("unterminated string literal");
+                    }
+                    s.push(self.escaped_char()?);
+                }
+                Token::Str(s)
+            }
+            ':' if self.eat(':') => Token::Scope,
+            '{' | '}' | '(' | ')' | '[' | ']' | '<' | '>' | ';' | ':' | ',' | '=' | '@' | '+'
+            | '-' | '*' | '/' | '%' | '|' | '&' | '^' | '~' => Token::Punct(c),
+            c => return self.error(format!("unexpected character {c:?}")),
+        };
+        Ok(Some(Spanned {
+            token,
+            file: self.file,
+            line,
+        }))
+    }
+
+    fn number(&mut self, first: char) -> Result<Token, SyntaxError> {
+        let mut literal = String::from(first);
+        while let Some(&c) = self.chars.peek() {
+            let exponent_sign =
+                (c == '+' || c == '-') && literal.ends_with(['e', 'E']) && !is_hex(&literal);
+            if !(c.is_ascii_alphanumeric() || c == '.' || exponent_sign) {
+                break;
+            }
+            literal.push(c);
+            self.bump();
+        }
+        let int = if is_hex(&literal) {
+            u64::from_str_radix(&literal[2..], 16).ok()
+        } else if literal.contains(['.', 'e', 'E']) {
+            // Fixed-point literals end with d or D, and are read as floats
+            let float = literal.trim_end_matches(['d', 'D']);
+            if float.parse::<f64>().is_err() {
+                return self.error(format!("invalid number `{literal}`"));
+            }
+            return Ok(Token::Float(float.to_string()));
+        } else if literal.len() > 1 && literal.starts_with('0') {
+            u64::from_str_radix(&literal[1..], 8).ok()
+        } else {
+            literal.parse().ok()
+        };
+        match int {
+            Some(v) => Ok(Token::Int(v)),
+            None => self.error(format!("invalid number `{literal}`")),
+        }
+    }
+
+    fn escaped_char(&mut self) -> Result<char, SyntaxError> {
+        let c = match self.bump() {
+            Some('\\') => match self.bump() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('0') => '\0',
+                Some(c @ ('\\' | '\'' | '"' | '?')) => c,
+                _ => return self.error("unsupported escape sequence"),
+            },
+            Some('\n') | None => return self.error("unterminated literal"),
+            Some(c) => c,
+        };
+        Ok(c)
+    }
+}
+
+fn is_hex(literal: &str) -> bool {
+    literal.starts_with("0x") || literal.starts_with("0X")
+}
+
+#[test]
+fn test_tokenize() {
+    let tokens = |source| {
+        tokenize(source, 0)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.token)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        tokens("module m { const long N = 0x10 + 010; };"),
+        vec![
+            Token::Ident(String::from("module")),
+            Token::Ident(String::from("m")),
+            Token::Punct('{'),
+            Token::Ident(String::from("const")),
+            Token::Ident(String::from("long")),
+            Token::Ident(String::from("N")),
+            Token::Punct('='),
+            Token::Int(16),
+            Token::Punct('+'),
+            Token::Int(8),
+            Token::Punct(';'),
+            Token::Punct('}'),
+            Token::Punct(';'),
+        ]
+    );
+    assert_eq!(
+        tokens("a::b 1.5e-3 2.0d 'x' '\\n' \"s\\\"\" >>"),
+        vec![
+            Token::Ident(String::from("a")),
+            Token::Scope,
+            Token::Ident(String::from("b")),
+            Token::Float(String::from("1.5e-3")),
+            Token::Float(String::from("2.0")),
+            Token::Char('x'),
+            Token::Char('\n'),
+            Token::Str(String::from("s\"")),
+            Token::Punct('>'),
+            Token::Punct('>'),
+        ]
+    );
+
+    let spanned = tokenize(
+        "// comment\n  #include \"a.idl\"\n/* multi\nline */ x # y",
+        0,
+    )
+    .unwrap_err();
+    assert_eq!(spanned.line, 4);
+    let spanned = tokenize("// comment\n  #include \"a.idl\"\n/* multi\nline */ x", 1).unwrap();
+    assert_eq!(
+        spanned,
+        vec![
+            Spanned {
+                token: Token::Directive(String::from("include \"a.idl\"")),
+                file: 1,
+                line: 2,
+            },
+            Spanned {
+                token: Token::Ident(String::from("x")),
+                file: 1,
+                line: 4,
+            },
+        ]
+    );
+    assert_eq!(tokenize("/* never closed", 0).unwrap_err().line, 1);
+}
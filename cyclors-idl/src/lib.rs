@@ -0,0 +1,333 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+
+//! Generates Rust types from OMG IDL files, to use as the typed topics of cyclors. The types are
+//! serialized with the `cdr` module of cyclors, and implement `cyclors::typed::TopicType`
+//! (which needs the `derive` feature of cyclors, and serde as a dependency of the crate).
+//!
+//! In `build.rs`:
+//! ```no_run
+//! let out = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+//! cyclors_idl::Builder::new()
+//!     .file("idl/sensors.idl")
+//!     .include_dir("idl/common")
+//!     .compile(out.join("sensors.rs"))
+//!     .unwrap();
+//! ```
+//!
+//! Then in the crate:
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/sensors.rs"));
+//! ```
+//!
+//! The IDL modules are generated as Rust modules, and:
+//! - the structs as structs, flattening their base struct. Their extensibility is set by
+//!   `@final`, `@appendable` or `@mutable`, `@key` or `#pragma keylist` set their key, and
+//!   `@optional` members are `Option`s. The structs annotated with `@nested` don't implement
+//!   `TopicType`. The key members have their type resolved through its typedefs, for the
+//!   derive to tell the max size of the keys. The member ids are their positions, so
+//!   `@autoid(HASH)` and `@hashid` are rejected;
+//! - the enums as enums, whose values must be sequential from 0, serialized as 32 bits integers
+//!   (`@bit_bound` is rejected);
+//! - the unions as enums with a variant per case, whose discriminators must be 32 bits
+//!   integers or enums, and whose cases must have a single label, sequential from 0. They can't
+//!   be the elements of sequences or arrays;
+//! - the sequences as `Vec`s serialized with `cyclors::cdr::sequence`, the strings as
+//!   `String`s (their bounds are not checked, but set the max size of the keys), the maps as
+//!   `BTreeMap`s and the arrays as arrays;
+//! - the typedefs as type aliases and the constants as constants.
+//!
+//! The structs, enums and unions implement `cyclors::cdr::Element`.
+//!
+//! `#include` directives are followed, and the other preprocessor directives are ignored.
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+mod codegen;
+mod lexer;
+mod parser;
+
+use lexer::{Spanned, Token};
+
+/// An error in the `file`-th IDL file.
+#[derive(Debug)]
+pub(crate) struct SyntaxError {
+    pub file: usize,
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, std::io::Error),
+    Idl {
+        file: Option<PathBuf>,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "{}: {e}", path.display()),
+            Error::Idl {
+                file: Some(file),
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", file.display()),
+            Error::Idl {
+                file: None,
+                line,
+                message,
+            } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, e) => Some(e),
+            Error::Idl { .. } => None,
+        }
+    }
+}
+
+/// Generates the Rust code of an IDL specification, without includes.
+pub fn generate(source: &str) -> Result<String, Error> {
+    let error = |e: SyntaxError| Error::Idl {
+        file: None,
+        line: e.line,
+        message: e.message,
+    };
+    let tokens = lexer::tokenize(source, 0).map_err(error)?;
+    if let Some(include) = tokens.iter().find(|t| is_include(&t.token).is_some()) {
+        return Err(Error::Idl {
+            file: None,
+            line: include.line,
+            message: String::from("includes need the files of a Builder"),
+        });
+    }
+    let definitions = parser::parse(tokens).map_err(error)?;
+    codegen::generate(&definitions, true).map_err(error)
+}
+
+/// Generates the Rust code of IDL files.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    files: Vec<PathBuf>,
+    include_dirs: Vec<PathBuf>,
+    topic_types: bool,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Builder {
+            files: Vec::new(),
+            include_dirs: Vec::new(),
+            topic_types: true,
+        }
+    }
+
+    /// Adds an IDL file to generate the types of.
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.files.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Adds a directory to search the included files in.
+    pub fn include_dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.include_dirs.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Whether the structs (without `@nested`) derive `cyclors::typed::TopicType`, true by
+    /// default.
+    pub fn topic_types(mut self, topic_types: bool) -> Self {
+        self.topic_types = topic_types;
+        self
+    }
+
+    /// Generates the Rust code of the files, including the included ones.
+    pub fn generate(&self) -> Result<String, Error> {
+        self.generate_with_sources().map(|(code, _)| code)
+    }
+
+    /// Generates the Rust code of the files into `out`, and tells cargo to run the build
+    /// script again when they (or the files they include) change.
+    pub fn compile(&self, out: impl AsRef<Path>) -> Result<(), Error> {
+        let (code, sources) = self.generate_with_sources()?;
+        for source in sources {
+            println!("cargo:rerun-if-changed={}", source.display());
+        }
+        let out = out.as_ref();
+        std::fs::write(out, code).map_err(|e| Error::Io(out.to_path_buf(), e))
+    }
+
+    /// The generated code, with the paths of the files it is generated from.
+    fn generate_with_sources(&self) -> Result<(String, Vec<PathBuf>), Error> {
+        let mut sources = Vec::new();
+        let mut tokens = Vec::new();
+        for file in &self.files {
+            self.load(file, &mut sources, &mut tokens)?;
+        }
+        let sources: Vec<PathBuf> = sources.into_iter().map(|(path, _)| path).collect();
+        let error = |e: SyntaxError| Error::Idl {
+            file: sources.get(e.file).cloned(),
+            line: e.line,
+            message: e.message,
+        };
+        let code = parser::parse(tokens)
+            .and_then(|definitions| codegen::generate(&definitions, self.topic_types))
+            .map_err(error)?;
+        Ok((code, sources))
+    }
+
+    /// Appends the tokens of a file to `tokens`, with the ones of the files it includes
+    /// instead of its `#include` directives. Each file is only included once: `sources` are
+    /// the paths of the loaded files, with their canonical paths.
+    fn load(
+        &self,
+        path: &Path,
+        sources: &mut Vec<(PathBuf, PathBuf)>,
+        tokens: &mut Vec<Spanned>,
+    ) -> Result<(), Error> {
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| Error::Io(path.to_path_buf(), e))?;
+        if sources.iter().any(|(_, source)| *source == canonical) {
+            return Ok(());
+        }
+        let source = std::fs::read_to_string(path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+        let file = sources.len();
+        sources.push((path.to_path_buf(), canonical));
+        let error = |line, message| Error::Idl {
+            file: Some(path.to_path_buf()),
+            line,
+            message,
+        };
+        let file_tokens = lexer::tokenize(&source, file).map_err(|e| error(e.line, e.message))?;
+        for token in file_tokens {
+            let Some(include) = is_include(&token.token) else {
+                tokens.push(token);
+                continue;
+            };
+            let (name, local) = match include.as_bytes() {
+                [b'"', .., b'"'] => (&include[1..include.len() - 1], true),
+                [b'<', .., b'>'] => (&include[1..include.len() - 1], false),
+                _ => return Err(error(token.line, String::from("invalid include"))),
+            };
+            let local_dir = path.parent().filter(|_| local);
+            let included = local_dir
+                .into_iter()
+                .chain(self.include_dirs.iter().map(PathBuf::as_path))
+                .map(|dir| dir.join(name))
+                .find(|path| path.is_file())
+                .ok_or_else(|| error(token.line, format!("cannot find `{name}`")))?;
+            self.load(&included, sources, tokens)?;
+        }
+        Ok(())
+    }
+}
+
+/// The file of an `#include` directive.
+fn is_include(token: &Token) -> Option<&str> {
+    match token {
+        Token::Directive(directive) => directive.strip_prefix("include").map(str::trim),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_builder() {
+    let dir = std::env::temp_dir().join(format!("cyclors-idl-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("include")).unwrap();
+    std::fs::write(
+        dir.join("include/common.idl"),
+        "#ifndef COMMON\n#define COMMON\nmodule common { typedef long Id; };\n#endif\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("a.idl"),
+        "#include <common.idl>\nstruct A { @key common::Id id; };\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("b.idl"),
+        "#include \"include/common.idl\"\n#include \"a.idl\"\n@nested struct B { A a; };\n",
+    )
+    .unwrap();
+
+    // Each file is only included once
+    let builder = Builder::new()
+        .file(dir.join("a.idl"))
+        .file(dir.join("b.idl"))
+        .include_dir(dir.join("include"));
+    let code = builder.generate().unwrap();
+    assert_eq!(code.matches("pub type Id = i32;").count(), 1);
+    assert!(code.contains("    #[key]\n    pub id: i32,"));
+    let out = dir.join("out.rs");
+    builder.compile(&out).unwrap();
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), code);
+    let code = builder.topic_types(false).generate().unwrap();
+    assert!(!code.contains("TopicType") && !code.contains("#[key]"));
+
+    std::fs::write(dir.join("c.idl"), "#include <a.idl>\n").unwrap();
+    let error = Builder::new()
+        .file(dir.join("c.idl"))
+        .generate()
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!("{}:1: cannot find `a.idl`", dir.join("c.idl").display())
+    );
+    std::fs::write(
+        dir.join("d.idl"),
+        "#include \"a.idl\"\nstruct D { B b; };\n",
+    )
+    .unwrap();
+    let error = Builder::new()
+        .file(dir.join("d.idl"))
+        .include_dir(dir.join("include"));
+    assert_eq!(
+        error.generate().unwrap_err().to_string(),
+        format!("{}:2: unknown name `B`", dir.join("d.idl").display())
+    );
+    assert!(matches!(
+        Builder::new().file(dir.join("none.idl")).generate(),
+        Err(Error::Io(..))
+    ));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_generate() {
+    let code = generate("struct S { long a; };").unwrap();
+    assert!(code.contains("pub struct S {\n    pub a: i32,\n}"));
+    assert_eq!(
+        generate("#include \"a.idl\"").unwrap_err().to_string(),
+        "line 1: includes need the files of a Builder"
+    );
+    assert_eq!(
+        generate("struct S {\n long a\n};").unwrap_err().to_string(),
+        "line 3: expected `;`, found `}`"
+    );
+}
@@ -0,0 +1,1084 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::lexer::{Spanned, Token};
+use crate::SyntaxError;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub(crate) struct Location {
+    pub file: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Definition {
+    Module(Module),
+    Struct(Struct),
+    Union(Union),
+    Enum(Enum),
+    Typedef(Typedef),
+    Const(Const),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Module {
+    pub name: String,
+    pub definitions: Vec<Definition>,
+    pub location: Location,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Extensibility {
+    Final,
+    Appendable,
+    Mutable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Struct {
+    pub name: String,
+    pub base: Option<ScopedName>,
+    pub members: Vec<Member>,
+    pub extensibility: Option<Extensibility>,
+    /// Whether the struct is only used in other types (`@nested`), so not a topic type.
+    pub nested: bool,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Member {
+    pub name: String,
+    pub ty: TypeSpec,
+    /// The dimensions of the member if it is an array.
+    pub dimensions: Vec<ConstExpr>,
+    pub key: bool,
+    pub optional: bool,
+    pub id: Option<ConstExpr>,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Union {
+    pub name: String,
+    pub discriminator: TypeSpec,
+    pub cases: Vec<Case>,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Case {
+    pub labels: Vec<Label>,
+    pub member: Member,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Label {
+    Value(ConstExpr),
+    Default,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Enum {
+    pub name: String,
+    pub enumerators: Vec<Enumerator>,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Enumerator {
+    pub name: String,
+    /// The value set with `@value`.
+    pub value: Option<ConstExpr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Typedef {
+    pub name: String,
+    pub ty: TypeSpec,
+    pub dimensions: Vec<ConstExpr>,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Const {
+    pub name: String,
+    pub ty: TypeSpec,
+    pub value: ConstExpr,
+    pub location: Location,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Primitive {
+    Boolean,
+    Char,
+    WChar,
+    Int8,
+    UInt8,
+    Short,
+    UShort,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+    Float,
+    Double,
+    LongDouble,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TypeSpec {
+    Primitive(Primitive),
+    /// A string, with its bound if bounded.
+    String(Option<ConstExpr>),
+    WString(Option<ConstExpr>),
+    Sequence(Box<TypeSpec>, Option<ConstExpr>),
+    Map(Box<TypeSpec>, Box<TypeSpec>, Option<ConstExpr>),
+    Fixed,
+    Named(ScopedName),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ScopedName {
+    /// Whether the name starts with `::`.
+    pub absolute: bool,
+    pub parts: Vec<String>,
+}
+
+impl std::fmt::Display for ScopedName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.absolute {
+            write!(f, "::")?;
+        }
+        write!(f, "{}", self.parts.join("::"))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum BinaryOp {
+    Or,
+    Xor,
+    And,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ConstExpr {
+    Int(u64),
+    Float(String),
+    Char(char),
+    Str(String),
+    Bool(bool),
+    Name(ScopedName),
+    /// `-`, `+` or `~`.
+    Unary(char, Box<ConstExpr>),
+    Binary(BinaryOp, Box<ConstExpr>, Box<ConstExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Annotation {
+    name: String,
+    params: Vec<Token>,
+    location: Location,
+}
+
+/// A `#pragma keylist`, setting the key of a struct declared in the same scope.
+struct KeyList {
+    ty: ScopedName,
+    members: Vec<String>,
+    location: Location,
+}
+
+/// Parses the tokens of an IDL specification (after preprocessing).
+pub(crate) fn parse(tokens: Vec<Spanned>) -> Result<Vec<Definition>, SyntaxError> {
+    let mut parser = Parser { tokens, pos: 0 };
+    let definitions = parser.definitions(false)?;
+    Ok(definitions)
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    pos: usize,
+}
+
+impl Parser {
+    fn location(&self) -> Location {
+        match self.tokens.get(self.pos).or(self.tokens.last()) {
+            Some(token) => Location {
+                file: token.file,
+                line: token.line,
+            },
+            None => Location::default(),
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, SyntaxError> {
+        let location = self.location();
+        Err(SyntaxError {
+            file: location.file,
+            line: location.line,
+            message: message.into(),
+        })
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, SyntaxError> {
+        match self.peek() {
+            Some(token) => self.error(format!("expected {expected}, found {token}")),
+            None => self.error(format!("expected {expected}, found the end of the file")),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.token)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + n).map(|t| &t.token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos)?.token.clone();
+        self.pos += 1;
+        Some(token)
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<(), SyntaxError> {
+        if self.eat_punct(c) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{c}`"))
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(ident)) if ident == keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), SyntaxError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{keyword}`"))
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, SyntaxError> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => {
+                let ident = ident.clone();
+                self.pos += 1;
+                Ok(ident)
+            }
+            _ => self.unexpected("an identifier"),
+        }
+    }
+
+    fn scoped_name(&mut self) -> Result<ScopedName, SyntaxError> {
+        let absolute = self.peek() == Some(&Token::Scope);
+        if absolute {
+            self.pos += 1;
+        }
+        let mut parts = vec![self.identifier()?];
+        while self.peek() == Some(&Token::Scope) {
+            self.pos += 1;
+            parts.push(self.identifier()?);
+        }
+        Ok(ScopedName { absolute, parts })
+    }
+
+    /// Parses definitions until the end of the tokens, or until `}` if `nested`.
+    fn definitions(&mut self, nested: bool) -> Result<Vec<Definition>, SyntaxError> {
+        let mut definitions = Vec::new();
+        let mut keylists = Vec::new();
+        loop {
+            match self.peek() {
+                None if nested => return self.unexpected("`}`"),
+                None => break,
+                Some(Token::Punct('}')) if nested => break,
+                Some(Token::Directive(directive)) => {
+                    let location = self.location();
+                    let directive = directive.clone();
+                    self.pos += 1;
+                    if let Some(keylist) = keylist(&directive, location) {
+                        keylists.push(keylist);
+                    }
+                }
+                Some(_) => self.definition(&mut definitions)?,
+            }
+        }
+        for keylist in keylists {
+            apply_keylist(&mut definitions, &keylist)?;
+        }
+        Ok(definitions)
+    }
+
+    fn definition(&mut self, definitions: &mut Vec<Definition>) -> Result<(), SyntaxError> {
+        let annotations = self.annotations()?;
+        let location = self.location();
+        let keyword = self.identifier()?;
+        match keyword.as_str() {
+            "module" => {
+                let name = self.identifier()?;
+                self.expect_punct('{')?;
+                let module = Module {
+                    name,
+                    definitions: self.definitions(true)?,
+                    location,
+                };
+                self.expect_punct('}')?;
+                definitions.push(Definition::Module(module));
+            }
+            "struct" => {
+                let name = self.identifier()?;
+                // A forward declaration
+                if self.peek() == Some(&Token::Punct(';')) {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                let s = self.struct_body(name, &annotations, location)?;
+                definitions.push(Definition::Struct(s));
+            }
+            "union" => {
+                let name = self.identifier()?;
+                if self.peek() == Some(&Token::Punct(';')) {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                definitions.push(Definition::Union(self.union_body(name, location)?));
+            }
+            "enum" => {
+                let name = self.identifier()?;
+                self.expect_punct('{')?;
+                let mut enumerators = Vec::new();
+                loop {
+                    let annotations = self.annotations()?;
+                    let value = match find_annotation(&annotations, "value") {
+                        Some(value) => Some(annotation_expr(value)?),
+                        None => None,
+                    };
+                    let name = self.identifier()?;
+                    enumerators.push(Enumerator { name, value });
+                    if !self.eat_punct(',') {
+                        break;
+                    }
+                }
+                self.expect_punct('}')?;
+                definitions.push(Definition::Enum(Enum {
+                    name,
+                    enumerators,
+                    location,
+                }));
+            }
+            "typedef" => {
+                let ty = self.type_spec()?;
+                loop {
+                    let location = self.location();
+                    let (name, dimensions) = self.declarator()?;
+                    definitions.push(Definition::Typedef(Typedef {
+                        name,
+                        ty: ty.clone(),
+                        dimensions,
+                        location,
+                    }));
+                    if !self.eat_punct(',') {
+                        break;
+                    }
+                }
+            }
+            "const" => {
+                let ty = self.type_spec()?;
+                let name = self.identifier()?;
+                self.expect_punct('=')?;
+                let value = self.const_expr()?;
+                definitions.push(Definition::Const(Const {
+                    name,
+                    ty,
+                    value,
+                    location,
+                }));
+            }
+            "interface" | "valuetype" | "exception" | "native" | "bitmask" | "bitset"
+            | "abstract" | "local" | "custom" | "eventtype" | "component" | "home" => {
+                self.pos -= 1;
+                return self.error(format!("`{keyword}` declarations are not supported"));
+            }
+            _ => {
+                self.pos -= 1;
+                return self.unexpected("a definition");
+            }
+        }
+        self.expect_punct(';')
+    }
+
+    fn struct_body(
+        &mut self,
+        name: String,
+        annotations: &[Annotation],
+        location: Location,
+    ) -> Result<Struct, SyntaxError> {
+        let base = if self.eat_punct(':') {
+            Some(self.scoped_name()?)
+        } else {
+            None
+        };
+        self.expect_punct('{')?;
+        let mut members = Vec::new();
+        while !self.eat_punct('}') {
+            self.members(&mut members)?;
+        }
+        Ok(Struct {
+            name,
+            base,
+            members,
+            extensibility: extensibility(annotations)?,
+            nested: find_annotation(annotations, "nested").is_some_and(
+                |a| !matches!(a.params.as_slice(), [Token::Ident(value)] if value == "FALSE"),
+            ),
+            location,
+        })
+    }
+
+    /// Parses the declaration of one or more members of the same type.
+    fn members(&mut self, members: &mut Vec<Member>) -> Result<(), SyntaxError> {
+        let annotations = self.annotations()?;
+        let ty = self.type_spec()?;
+        loop {
+            let location = self.location();
+            let (name, dimensions) = self.declarator()?;
+            members.push(member(
+                name,
+                ty.clone(),
+                dimensions,
+                &annotations,
+                location,
+            )?);
+            if !self.eat_punct(',') {
+                break;
+            }
+        }
+        self.expect_punct(';')
+    }
+
+    fn union_body(&mut self, name: String, location: Location) -> Result<Union, SyntaxError> {
+        self.expect_keyword("switch")?;
+        self.expect_punct('(')?;
+        self.annotations()?;
+        let discriminator = self.type_spec()?;
+        self.expect_punct(')')?;
+        self.expect_punct('{')?;
+        let mut cases = Vec::new();
+        while !self.eat_punct('}') {
+            let mut labels = Vec::new();
+            loop {
+                if self.eat_keyword("case") {
+                    labels.push(Label::Value(self.const_expr()?));
+                } else if self.eat_keyword("default") {
+                    labels.push(Label::Default);
+                } else if labels.is_empty() {
+                    return self.unexpected("`case` or `default`");
+                } else {
+                    break;
+                }
+                self.expect_punct(':')?;
+            }
+            let annotations = self.annotations()?;
+            let ty = self.type_spec()?;
+            let location = self.location();
+            let (name, dimensions) = self.declarator()?;
+            let member = member(name, ty, dimensions, &annotations, location)?;
+            self.expect_punct(';')?;
+            cases.push(Case { labels, member });
+        }
+        Ok(Union {
+            name,
+            discriminator,
+            cases,
+            location,
+        })
+    }
+
+    fn declarator(&mut self) -> Result<(String, Vec<ConstExpr>), SyntaxError> {
+        let name = self.identifier()?;
+        let mut dimensions = Vec::new();
+        while self.eat_punct('[') {
+            dimensions.push(self.const_expr()?);
+            self.expect_punct(']')?;
+        }
+        Ok((name, dimensions))
+    }
+
+    fn annotations(&mut self) -> Result<Vec<Annotation>, SyntaxError> {
+        let mut annotations = Vec::new();
+        while self.peek() == Some(&Token::Punct('@')) {
+            let location = self.location();
+            self.pos += 1;
+            let name = self.scoped_name()?.parts.join("::");
+            let mut params = Vec::new();
+            if self.eat_punct('(') {
+                let mut depth = 0;
+                loop {
+                    match self.next() {
+                        Some(Token::Punct(')')) if depth == 0 => break,
+                        Some(token) => {
+                            match token {
+                                Token::Punct('(') => depth += 1,
+                                Token::Punct(')') => depth -= 1,
+                                _ => (),
+                            }
+                            params.push(token);
+                        }
+                        None => return self.unexpected("`)`"),
+                    }
+                }
+            }
+            // The member ids are their positions (`@autoid` defaults to HASH), and the enums
+            // are serialized as 32 bits integers
+            let unsupported = match (name.as_str(), params.as_slice()) {
+                ("autoid", [Token::Ident(kind)]) if kind == "SEQUENTIAL" => None,
+                ("autoid", _) => Some(String::from("`@autoid(HASH)`")),
+                ("hashid" | "bit_bound", _) => Some(format!("`@{name}`")),
+                _ => None,
+            };
+            if let Some(annotation) = unsupported {
+                return Err(SyntaxError {
+                    file: location.file,
+                    line: location.line,
+                    message: format!("{annotation} is not supported"),
+                });
+            }
+            annotations.push(Annotation {
+                name,
+                params,
+                location,
+            });
+        }
+        Ok(annotations)
+    }
+
+    fn type_spec(&mut self) -> Result<TypeSpec, SyntaxError> {
+        let Some(Token::Ident(keyword)) = self.peek().cloned() else {
+            if self.peek() == Some(&Token::Scope) {
+                return Ok(TypeSpec::Named(self.scoped_name()?));
+            }
+            return self.unexpected("a type");
+        };
+        self.pos += 1;
+        let primitive = match keyword.as_str() {
+            "boolean" => Primitive::Boolean,
+            "octet" | "uint8" => Primitive::UInt8,
+            "char" => Primitive::Char,
+            "wchar" => Primitive::WChar,
+            "int8" => Primitive::Int8,
+            "short" | "int16" => Primitive::Short,
+            "int32" => Primitive::Long,
+            "int64" => Primitive::LongLong,
+            "uint16" => Primitive::UShort,
+            "uint32" => Primitive::ULong,
+            "uint64" => Primitive::ULongLong,
+            "float" => Primitive::Float,
+            "double" => Primitive::Double,
+            "long" if self.eat_keyword("long") => Primitive::LongLong,
+            "long" if self.eat_keyword("double") => Primitive::LongDouble,
+            "long" => Primitive::Long,
+            "unsigned" if self.eat_keyword("short") => Primitive::UShort,
+            "unsigned" => {
+                self.expect_keyword("long")?;
+                if self.eat_keyword("long") {
+                    Primitive::ULongLong
+                } else {
+                    Primitive::ULong
+                }
+            }
+            "string" | "wstring" => {
+                let bound = if self.eat_punct('<') {
+                    let bound = self.const_expr()?;
+                    self.expect_punct('>')?;
+                    Some(bound)
+                } else {
+                    None
+                };
+                return Ok(if keyword == "string" {
+                    TypeSpec::String(bound)
+                } else {
+                    TypeSpec::WString(bound)
+                });
+            }
+            "sequence" => {
+                self.expect_punct('<')?;
+                let element = self.type_spec()?;
+                let bound = if self.eat_punct(',') {
+                    Some(self.const_expr()?)
+                } else {
+                    None
+                };
+                self.expect_punct('>')?;
+                return Ok(TypeSpec::Sequence(Box::new(element), bound));
+            }
+            "map" => {
+                self.expect_punct('<')?;
+                let key = self.type_spec()?;
+                self.expect_punct(',')?;
+                let value = self.type_spec()?;
+                let bound = if self.eat_punct(',') {
+                    Some(self.const_expr()?)
+                } else {
+                    None
+                };
+                self.expect_punct('>')?;
+                return Ok(TypeSpec::Map(Box::new(key), Box::new(value), bound));
+            }
+            "fixed" => {
+                if self.eat_punct('<') {
+                    self.const_expr()?;
+                    self.expect_punct(',')?;
+                    self.const_expr()?;
+                    self.expect_punct('>')?;
+                }
+                return Ok(TypeSpec::Fixed);
+            }
+            _ => {
+                self.pos -= 1;
+                return Ok(TypeSpec::Named(self.scoped_name()?));
+            }
+        };
+        Ok(TypeSpec::Primitive(primitive))
+    }
+
+    fn const_expr(&mut self) -> Result<ConstExpr, SyntaxError> {
+        self.binary_expr(0)
+    }
+
+    /// Parses the binary expressions of the operators of at least the given precedence.
+    fn binary_expr(&mut self, precedence: usize) -> Result<ConstExpr, SyntaxError> {
+        const PRECEDENCES: [&[(&str, BinaryOp)]; 5] = [
+            &[("|", BinaryOp::Or)],
+            &[("^", BinaryOp::Xor)],
+            &[("&", BinaryOp::And)],
+            &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+        ];
+        if precedence == PRECEDENCES.len() {
+            return self.mul_expr();
+        }
+        let mut lhs = self.binary_expr(precedence + 1)?;
+        'operators: loop {
+            for (op, binary_op) in PRECEDENCES[precedence] {
+                if self.eat_operator(op) {
+                    let rhs = self.binary_expr(precedence + 1)?;
+                    lhs = ConstExpr::Binary(*binary_op, Box::new(lhs), Box::new(rhs));
+                    continue 'operators;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn mul_expr(&mut self) -> Result<ConstExpr, SyntaxError> {
+        let mut lhs = self.unary_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Punct('*')) => BinaryOp::Mul,
+                Some(Token::Punct('/')) => BinaryOp::Div,
+                Some(Token::Punct('%')) => BinaryOp::Mod,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = self.unary_expr()?;
+            lhs = ConstExpr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    /// Eats an operator of one or two punctuation characters (the shifts).
+    fn eat_operator(&mut self, op: &str) -> bool {
+        let matches = op
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_nth(i) == Some(&Token::Punct(c)));
+        if matches {
+            self.pos += op.len();
+        }
+        matches
+    }
+
+    fn unary_expr(&mut self) -> Result<ConstExpr, SyntaxError> {
+        match self.peek() {
+            Some(Token::Punct(op @ ('-' | '+' | '~'))) => {
+                let op = *op;
+                self.pos += 1;
+                Ok(ConstExpr::Unary(op, Box::new(self.unary_expr()?)))
+            }
+            _ => self.primary_expr(),
+        }
+    }
+
+    fn primary_expr(&mut self) -> Result<ConstExpr, SyntaxError> {
+        let expr = match self.peek().cloned() {
+            Some(Token::Int(v)) => ConstExpr::Int(v),
+            Some(Token::Float(v)) => ConstExpr::Float(v),
+            Some(Token::Char(c)) => ConstExpr::Char(c),
+            Some(Token::Str(mut s)) => {
+                // Adjacent strings are concatenated
+                self.pos += 1;
+                while let Some(Token::Str(next)) = self.peek() {
+                    s.push_str(next);
+                    self.pos += 1;
+                }
+                return Ok(ConstExpr::Str(s));
+            }
+            Some(Token::Ident(ident)) if ident == "TRUE" => ConstExpr::Bool(true),
+            Some(Token::Ident(ident)) if ident == "FALSE" => ConstExpr::Bool(false),
+            Some(Token::Ident(_) | Token::Scope) => {
+                return Ok(ConstExpr::Name(self.scoped_name()?))
+            }
+            Some(Token::Punct('(')) => {
+                self.pos += 1;
+                let expr = self.const_expr()?;
+                self.expect_punct(')')?;
+                return Ok(expr);
+            }
+            _ => return self.unexpected("a constant expression"),
+        };
+        self.pos += 1;
+        Ok(expr)
+    }
+}
+
+fn find_annotation<'a>(annotations: &'a [Annotation], name: &str) -> Option<&'a Annotation> {
+    annotations.iter().find(|a| a.name == name)
+}
+
+/// Parses the single parameter of an annotation as a constant expression.
+fn annotation_expr(annotation: &Annotation) -> Result<ConstExpr, SyntaxError> {
+    let tokens = annotation
+        .params
+        .iter()
+        .map(|token| Spanned {
+            token: token.clone(),
+            file: annotation.location.file,
+            line: annotation.location.line,
+        })
+        .collect();
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.const_expr()?;
+    if parser.peek().is_some() {
+        return parser.unexpected(&format!("the end of @{}", annotation.name));
+    }
+    Ok(expr)
+}
+
+/// Whether a boolean annotation like `@key` or `@key(TRUE)` is set.
+fn is_set(annotations: &[Annotation], name: &str) -> Result<bool, SyntaxError> {
+    match find_annotation(annotations, name) {
+        None => Ok(false),
+        Some(annotation) if annotation.params.is_empty() => Ok(true),
+        Some(annotation) => match annotation_expr(annotation)? {
+            ConstExpr::Bool(value) => Ok(value),
+            _ => Err(SyntaxError {
+                file: annotation.location.file,
+                line: annotation.location.line,
+                message: format!("expected TRUE or FALSE in @{}", annotation.name),
+            }),
+        },
+    }
+}
+
+fn extensibility(annotations: &[Annotation]) -> Result<Option<Extensibility>, SyntaxError> {
+    let mut extensibility = None;
+    for annotation in annotations {
+        let value = match annotation.name.as_str() {
+            "final" => Extensibility::Final,
+            "appendable" => Extensibility::Appendable,
+            "mutable" => Extensibility::Mutable,
+            "extensibility" => match annotation.params.as_slice() {
+                [Token::Ident(kind)] if kind == "FINAL" => Extensibility::Final,
+                [Token::Ident(kind)] if kind == "APPENDABLE" => Extensibility::Appendable,
+                [Token::Ident(kind)] if kind == "MUTABLE" => Extensibility::Mutable,
+                _ => {
+                    return Err(SyntaxError {
+                        file: annotation.location.file,
+                        line: annotation.location.line,
+                        message: String::from(
+                            "expected FINAL, APPENDABLE or MUTABLE in @extensibility",
+                        ),
+                    })
+                }
+            },
+            _ => continue,
+        };
+        if extensibility.replace(value).is_some() {
+            return Err(SyntaxError {
+                file: annotation.location.file,
+                line: annotation.location.line,
+                message: String::from("the extensibility is already set"),
+            });
+        }
+    }
+    Ok(extensibility)
+}
+
+fn member(
+    name: String,
+    ty: TypeSpec,
+    dimensions: Vec<ConstExpr>,
+    annotations: &[Annotation],
+    location: Location,
+) -> Result<Member, SyntaxError> {
+    let id = match find_annotation(annotations, "id") {
+        Some(id) => Some(annotation_expr(id)?),
+        None => None,
+    };
+    Ok(Member {
+        name,
+        ty,
+        dimensions,
+        key: is_set(annotations, "key")?,
+        optional: is_set(annotations, "optional")?,
+        id,
+        location,
+    })
+}
+
+/// Parses `pragma keylist <type> <member>*`, the other directives are ignored.
+fn keylist(directive: &str, location: Location) -> Option<KeyList> {
+    let mut words = directive.split_whitespace();
+    if words.next() != Some("pragma") || words.next() != Some("keylist") {
+        return None;
+    }
+    let ty = words.next()?;
+    let ty = ScopedName {
+        absolute: ty.starts_with("::"),
+        parts: ty
+            .trim_start_matches("::")
+            .split("::")
+            .map(String::from)
+            .collect(),
+    };
+    Some(KeyList {
+        ty,
+        members: words.map(|w| w.trim_matches(',').to_string()).collect(),
+        location,
+    })
+}
+
+fn apply_keylist(definitions: &mut [Definition], keylist: &KeyList) -> Result<(), SyntaxError> {
+    let error = |message: String| SyntaxError {
+        file: keylist.location.file,
+        line: keylist.location.line,
+        message,
+    };
+    let s = find_struct(definitions, &keylist.ty.parts)
+        .ok_or_else(|| error(format!("unknown struct `{}` in keylist", keylist.ty)))?;
+    for key in &keylist.members {
+        let member = s
+            .members
+            .iter_mut()
+            .find(|m| &m.name == key)
+            .ok_or_else(|| error(format!("unknown member `{key}` in keylist")))?;
+        member.key = true;
+    }
+    Ok(())
+}
+
+/// Finds a struct by its name relative to the definitions, in any of the reopened modules.
+fn find_struct<'a>(definitions: &'a mut [Definition], path: &[String]) -> Option<&'a mut Struct> {
+    definitions.iter_mut().find_map(|d| match (d, path) {
+        (Definition::Struct(s), [name]) if &s.name == name => Some(s),
+        (Definition::Module(m), [module, path @ ..]) if &m.name == module => {
+            find_struct(&mut m.definitions, path)
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+fn parse_str(source: &str) -> Result<Vec<Definition>, SyntaxError> {
+    parse(crate::lexer::tokenize(source, 0)?)
+}
+
+#[test]
+fn test_parse_struct() {
+    let definitions = parse_str(
+        r#"
+        module sensors {
+            @appendable
+            struct Reading {
+                @key unsigned long id;
+                @optional double value;
+                long long a, b[2][3];
+                sequence<string<8>, 10> tags;
+            };
+            struct Fwd;
+        };
+        "#,
+    )
+    .unwrap();
+    let Definition::Module(module) = &definitions[0] else {
+        panic!("expected a module");
+    };
+    assert_eq!(module.name, "sensors");
+    assert_eq!(module.definitions.len(), 1);
+    let Definition::Struct(s) = &module.definitions[0] else {
+        panic!("expected a struct");
+    };
+    assert_eq!(s.extensibility, Some(Extensibility::Appendable));
+    assert!(!s.nested);
+    let members: Vec<_> = s.members.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(members, vec!["id", "value", "a", "b", "tags"]);
+    assert_eq!(s.members[0].ty, TypeSpec::Primitive(Primitive::ULong));
+    assert!(s.members[0].key && !s.members[0].optional);
+    assert!(!s.members[1].key && s.members[1].optional);
+    assert_eq!(s.members[2].ty, TypeSpec::Primitive(Primitive::LongLong));
+    assert_eq!(
+        s.members[3].dimensions,
+        vec![ConstExpr::Int(2), ConstExpr::Int(3)]
+    );
+    assert_eq!(
+        s.members[4].ty,
+        TypeSpec::Sequence(
+            Box::new(TypeSpec::String(Some(ConstExpr::Int(8)))),
+            Some(ConstExpr::Int(10))
+        )
+    );
+    assert_eq!(s.members[4].location.line, 8);
+}
+
+#[test]
+fn test_parse_definitions() {
+    let definitions = parse_str(
+        r#"
+        const long N = (1 << 4) * 2 + -1;
+        enum Color { RED, @value(5) GREEN };
+        typedef sequence<sequence<long>> Matrix, Pair[2];
+        union U switch (Color) {
+            case RED: case GREEN: long a;
+            default: ::Matrix m;
+        };
+        @extensibility(MUTABLE) @nested
+        struct S : Base { map<string, long> m; };
+        #pragma keylist S m
+        "#,
+    )
+    .unwrap();
+    assert_eq!(definitions.len(), 6);
+    let Definition::Const(n) = &definitions[0] else {
+        panic!("expected a const");
+    };
+    let one = Box::new(ConstExpr::Int(1));
+    assert_eq!(
+        n.value,
+        ConstExpr::Binary(
+            BinaryOp::Add,
+            Box::new(ConstExpr::Binary(
+                BinaryOp::Mul,
+                Box::new(ConstExpr::Binary(
+                    BinaryOp::Shl,
+                    one.clone(),
+                    Box::new(ConstExpr::Int(4))
+                )),
+                Box::new(ConstExpr::Int(2))
+            )),
+            Box::new(ConstExpr::Unary('-', one))
+        )
+    );
+    let Definition::Enum(e) = &definitions[1] else {
+        panic!("expected an enum");
+    };
+    assert_eq!(e.enumerators[1].value, Some(ConstExpr::Int(5)));
+    let Definition::Typedef(pair) = &definitions[3] else {
+        panic!("expected a typedef");
+    };
+    assert_eq!(pair.name, "Pair");
+    assert_eq!(pair.dimensions, vec![ConstExpr::Int(2)]);
+    let Definition::Union(u) = &definitions[4] else {
+        panic!("expected a union");
+    };
+    assert_eq!(u.cases[0].labels.len(), 2);
+    assert_eq!(u.cases[1].labels, vec![Label::Default]);
+    assert_eq!(
+        u.cases[1].member.ty,
+        TypeSpec::Named(ScopedName {
+            absolute: true,
+            parts: vec![String::from("Matrix")]
+        })
+    );
+    let Definition::Struct(s) = &definitions[5] else {
+        panic!("expected a struct");
+    };
+    assert_eq!(s.extensibility, Some(Extensibility::Mutable));
+    assert!(s.nested);
+    assert_eq!(s.base.as_ref().unwrap().parts, vec![String::from("Base")]);
+    assert!(s.members[0].key);
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = |source| parse_str(source).unwrap_err();
+    assert_eq!(
+        error("struct S {\n long a\n};").message,
+        "expected `;`, found `}`"
+    );
+    assert_eq!(error("struct S {\n long a\n};").line, 3);
+    assert_eq!(
+        error("interface I {};").message,
+        "`interface` declarations are not supported"
+    );
+    assert_eq!(
+        error("@final @mutable struct S { long a; };").message,
+        "the extensibility is already set"
+    );
+    assert_eq!(
+        error("module m { struct S { long a; };").message,
+        "expected `}`, found the end of the file"
+    );
+    assert_eq!(
+        error("struct S { long a; };\n#pragma keylist S b").message,
+        "unknown member `b` in keylist"
+    );
+    assert_eq!(
+        error("@autoid(HASH) struct S { long a; };").message,
+        "`@autoid(HASH)` is not supported"
+    );
+    assert_eq!(
+        error("@autoid struct S { long a; };").message,
+        "`@autoid(HASH)` is not supported"
+    );
+    assert_eq!(error("struct S {\n @hashid long a; };").line, 2);
+    assert_eq!(
+        error("struct S { @hashid(\"a\") long a; };").message,
+        "`@hashid` is not supported"
+    );
+    assert_eq!(
+        error("@bit_bound(8) enum E { A };").message,
+        "`@bit_bound` is not supported"
+    );
+    assert!(parse_str("@autoid(SEQUENTIAL) struct S { long a; };").is_ok());
+}
@@ -0,0 +1,829 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::cdr::{EncapsulationHeader, ENCAPSULATION_HEADER_SIZE};
+use crate::entity::{
+    CdrSample, Entity, Participant, Reader, ReaderParent, Sealed, SerdataRef, Topic, Writer,
+    WriterParent,
+};
+use crate::error::check;
+use crate::listener::Listener;
+use crate::qos::Qos;
+use crate::sample_info::SampleInfo;
+use crate::*;
+use std::{
+    ffi::CString,
+    io::IoSlice,
+    os::raw::{c_char, c_uchar, c_void},
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+/// A key field of a blob topic type: `count` consecutive primitives of `size` bytes (1, 2,
+/// 4 or 8), at `offset` from the end of the encapsulation header of the data samples.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct KeyField {
+    pub offset: u32,
+    pub size: u32,
+    pub count: u32,
+}
+
+impl KeyField {
+    pub fn new(offset: u32, size: u32) -> Self {
+        KeyField {
+            offset,
+            size,
+            count: 1,
+        }
+    }
+
+    pub fn array(offset: u32, size: u32, count: u32) -> Self {
+        KeyField {
+            offset,
+            size,
+            count,
+        }
+    }
+}
+
+/// Extracts the big-endian XCDR2 serialized key from a payload (with its encapsulation
+/// header). The `bool` tells if the payload only contains the key fields, as in the samples
+/// notifying a dispose or an unregister. Returns `None` if the payload is invalid.
+pub type KeyExtractor = Box<dyn Fn(&[u8], bool) -> Option<Vec<u8>> + Send + Sync>;
+
+/// How the instances of a blob topic are identified.
+///
+/// The key must be the one of the type the samples were serialized with, for the key hashes
+/// to match the ones of the original writers.
+pub enum BlobKey {
+    /// All the samples are of the same instance.
+    Keyless,
+    /// The key is made of fields at fixed offsets in the data samples, in the order of the key.
+    Fields(Vec<KeyField>),
+    /// The key is extracted by a function, e.g. for types with variable size fields before
+    /// the key fields. `max_size` is the max size of the serialized key, if bounded.
+    Extractor {
+        extract: KeyExtractor,
+        max_size: Option<u32>,
+    },
+}
+
+impl std::fmt::Debug for BlobKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlobKey::Keyless => write!(f, "Keyless"),
+            BlobKey::Fields(fields) => f.debug_tuple("Fields").field(fields).finish(),
+            BlobKey::Extractor { max_size, .. } => f
+                .debug_struct("Extractor")
+                .field("max_size", max_size)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// The XTypes TypeInformation and TypeMapping of a type, serialized as in discovery.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct TypeInformation {
+    pub type_information: Vec<u8>,
+    /// The type objects of the type and of its dependencies, served to the peers resolving
+    /// the type to check its assignability.
+    pub type_mapping: Option<Vec<u8>>,
+}
+
+unsafe extern "C" fn extract_key(
+    arg: *mut c_void,
+    payload: *const c_uchar,
+    size: usize,
+    key_only: bool,
+    key: *mut c_uchar,
+    key_capacity: usize,
+) -> usize {
+    let extract = &*(arg as *const KeyExtractor);
+    let payload = std::slice::from_raw_parts(payload, size);
+    // Unwinding into C is undefined behavior, a panic is reported as an invalid payload
+    match catch_unwind(AssertUnwindSafe(|| extract(payload, key_only))) {
+        Ok(Some(k)) => {
+            if k.len() <= key_capacity {
+                std::ptr::copy_nonoverlapping(k.as_ptr(), key, k.len());
+            }
+            k.len()
+        }
+        _ => 0,
+    }
+}
+
+unsafe extern "C" fn free_key_extractor(arg: *mut c_void) {
+    drop(Box::from_raw(arg as *mut KeyExtractor));
+}
+
+/// A topic whose samples are written and read in their serialized representation (the CDR
+/// encapsulation header followed by the serialized sample), whatever their type.
+#[derive(Debug, Clone)]
+pub struct BlobTopic {
+    topic: Topic,
+}
+
+impl BlobTopic {
+    pub fn new(
+        participant: &Participant,
+        topic_name: &str,
+        type_name: &str,
+        key: BlobKey,
+    ) -> Result<Self> {
+        let topic = create_topic(participant, topic_name, type_name, key, None)?;
+        Ok(BlobTopic { topic })
+    }
+
+    /// Creates a blob topic advertising the type information of its type, e.g. as discovered
+    /// from the original writers, so that it can match the peers requiring type assignability.
+    ///
+    /// This needs Cyclone DDS to be built with its type library, otherwise it fails with
+    /// [`Error::Unsupported`].
+    pub fn with_type_information(
+        participant: &Participant,
+        topic_name: &str,
+        type_name: &str,
+        key: BlobKey,
+        type_information: &TypeInformation,
+    ) -> Result<Self> {
+        let topic = create_topic(
+            participant,
+            topic_name,
+            type_name,
+            key,
+            Some(type_information),
+        )?;
+        Ok(BlobTopic { topic })
+    }
+
+    pub fn topic(&self) -> &Topic {
+        &self.topic
+    }
+
+    /// Filters the samples delivered to the readers of this topic on their payloads: only
+    /// those for which `filter` returns true are kept.
+    ///
+    /// Cyclone DDS doesn't synchronize the replacement of a filter with the filtering, so the
+    /// samples being filtered meanwhile may still be filtered by the previous one. The filters
+    /// replaced or cleared are only dropped with the topic, as they may still be in use: set the
+    /// filter once, before creating readers and writers, rather than replacing it repeatedly.
+    pub fn set_filter<F>(&self, filter: F) -> Result<()>
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        let filter: Box<TopicFilter> = Box::new(Box::new(filter));
+        let arg = &*filter as *const TopicFilter as *mut c_void;
+        let handle = self.topic.handle().ok_or(Error::BadParameter)?;
+        let mut attachment = handle.attachment.lock().unwrap();
+        check(unsafe {
+            dds_set_topic_filter_and_arg(self.topic.entity(), Some(call_topic_filter), arg)
+        })?;
+        let filters = attachment.get_or_insert_with(|| Box::<Vec<Box<TopicFilter>>>::default());
+        // Only the topic sets its attachment
+        filters
+            .downcast_mut::<Vec<Box<TopicFilter>>>()
+            .unwrap()
+            .push(filter);
+        Ok(())
+    }
+
+    /// Removes the filter of the topic. It is only dropped with the topic, as it may still be
+    /// in use.
+    pub fn clear_filter(&self) -> Result<()> {
+        let handle = self.topic.handle().ok_or(Error::BadParameter)?;
+        let _attachment = handle.attachment.lock().unwrap();
+        check(unsafe {
+            dds_set_topic_filter_and_arg(self.topic.entity(), None, std::ptr::null_mut())
+        })?;
+        Ok(())
+    }
+}
+
+type TopicFilter = Box<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+unsafe extern "C" fn call_topic_filter(sample: *const c_void, arg: *mut c_void) -> bool {
+    let filter = &*(arg as *const TopicFilter);
+    let sample = &*(sample as *const cdds_blob_sample_t);
+    let payload = if sample.payload.is_null() {
+        &[]
+    } else {
+        std::slice::from_raw_parts(sample.payload, sample.size as usize)
+    };
+    // Unwinding into C is undefined behavior, a panic rejects the sample
+    catch_unwind(AssertUnwindSafe(|| filter(payload))).unwrap_or(false)
+}
+
+/// The sample of a blob topic borrowing `data`, only valid to be read by Cyclone DDS.
+fn blob_sample(data: &[u8]) -> Result<cdds_blob_sample_t> {
+    Ok(cdds_blob_sample_t {
+        payload: data.as_ptr() as *mut c_uchar,
+        size: u32::try_from(data.len()).map_err(|_| Error::BadParameter)?,
+        key_only: false,
+    })
+}
+
+/// A writer of serialized samples on a [`BlobTopic`].
+#[derive(Debug, Clone)]
+pub struct BlobWriter {
+    writer: Writer,
+}
+
+impl BlobWriter {
+    pub fn new<P: WriterParent>(
+        parent: &P,
+        topic: &BlobTopic,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        let writer = Writer::new(parent, &topic.topic, qos, listener)?;
+        Ok(BlobWriter { writer })
+    }
+
+    pub fn writer(&self) -> &Writer {
+        &self.writer
+    }
+
+    /// Writes a sample, which must start with a CDR encapsulation header.
+    pub fn write(&self, data: &[u8]) -> Result<()> {
+        self.write_vectored(&[IoSlice::new(data)])
+    }
+
+    /// Writes a sample made of the concatenation of `data`, e.g. an encapsulation header and
+    /// a payload received separately.
+    ///
+    /// Fails with [`Error::BadParameter`] if the sample doesn't start with a valid
+    /// encapsulation header, or if its key can't be extracted.
+    pub fn write_vectored(&self, data: &[IoSlice<'_>]) -> Result<()> {
+        let size: usize = data.iter().map(|d| d.len()).sum();
+        let mut header = [0u8; ENCAPSULATION_HEADER_SIZE];
+        let mut len = 0;
+        for d in data {
+            let n = d.len().min(ENCAPSULATION_HEADER_SIZE - len);
+            header[len..len + n].copy_from_slice(&d[..n]);
+            len += n;
+        }
+        let header = EncapsulationHeader::parse(&header[..len]).map_err(|_| Error::BadParameter)?;
+        if header.padding() as usize > size - ENCAPSULATION_HEADER_SIZE {
+            return Err(Error::BadParameter);
+        }
+        let iov: Vec<ddsrt_iovec_t> = data
+            .iter()
+            .map(|d| ddsrt_iovec_t {
+                iov_base: d.as_ptr() as *mut c_void,
+                iov_len: d.len() as _,
+            })
+            .collect();
+        unsafe {
+            let mut sertype: *const ddsi_sertype = std::ptr::null();
+            check(dds_get_entity_sertype(self.writer.entity(), &mut sertype))?;
+            // The payload is copied into the serdata
+            let serdata = ddsi_serdata_from_ser_iov(
+                sertype,
+                ddsi_serdata_kind_SDK_DATA,
+                iov.len() as _,
+                iov.as_ptr(),
+                size,
+            );
+            if serdata.is_null() {
+                return Err(Error::BadParameter);
+            }
+            // Consumes the reference on serdata, even on failure
+            check(dds_writecdr(self.writer.entity(), serdata))?;
+        }
+        Ok(())
+    }
+
+    /// Disposes the instance of the sample `data`.
+    pub fn dispose(&self, data: &[u8]) -> Result<()> {
+        let sample = blob_sample(data)?;
+        check(unsafe {
+            dds_dispose(
+                self.writer.entity(),
+                &sample as *const cdds_blob_sample_t as *const c_void,
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Unregisters the writer from the instance of the sample `data`.
+    pub fn unregister(&self, data: &[u8]) -> Result<()> {
+        let sample = blob_sample(data)?;
+        check(unsafe {
+            dds_unregister_instance(
+                self.writer.entity(),
+                &sample as *const cdds_blob_sample_t as *const c_void,
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn dispose_instance(&self, handle: dds_instance_handle_t) -> Result<()> {
+        check(unsafe { dds_dispose_ih(self.writer.entity(), handle) })?;
+        Ok(())
+    }
+
+    pub fn unregister_instance(&self, handle: dds_instance_handle_t) -> Result<()> {
+        check(unsafe { dds_unregister_instance_ih(self.writer.entity(), handle) })?;
+        Ok(())
+    }
+
+    /// Returns the handle of the instance of the sample `data`, if known by the writer.
+    pub fn lookup_instance(&self, data: &[u8]) -> Result<Option<dds_instance_handle_t>> {
+        let sample = blob_sample(data)?;
+        let handle = unsafe {
+            dds_lookup_instance(
+                self.writer.entity(),
+                &sample as *const cdds_blob_sample_t as *const c_void,
+            )
+        };
+        Ok((handle != 0).then_some(handle))
+    }
+}
+
+/// A reader of serialized samples on a [`BlobTopic`].
+#[derive(Debug, Clone)]
+pub struct BlobReader {
+    reader: Reader,
+}
+
+impl BlobReader {
+    pub fn new<P: ReaderParent>(
+        parent: &P,
+        topic: &BlobTopic,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        let reader = Reader::new(parent, &topic.topic, qos, listener)?;
+        Ok(BlobReader { reader })
+    }
+
+    pub fn reader(&self) -> &Reader {
+        &self.reader
+    }
+
+    /// Takes up to `max_samples` samples, copying their payloads.
+    pub fn take(&self, max_samples: usize) -> Result<Vec<CdrSample>> {
+        self.reader.take_cdr(max_samples)
+    }
+
+    /// Takes up to `max_samples` samples, without copying their payloads.
+    pub fn take_borrowed(&self, max_samples: usize) -> Result<Vec<(SerdataRef, SampleInfo)>> {
+        self.reader.take_serdata(max_samples)
+    }
+}
+
+fn create_topic(
+    participant: &Participant,
+    topic_name: &str,
+    type_name: &str,
+    key: BlobKey,
+    type_information: Option<&TypeInformation>,
+) -> Result<Topic> {
+    let topic_name = CString::new(topic_name).map_err(|_| Error::BadParameter)?;
+    let type_name = CString::new(type_name).map_err(|_| Error::BadParameter)?;
+    let fields: Vec<cdds_key_field_t>;
+    let keys = match key {
+        BlobKey::Keyless => None,
+        BlobKey::Fields(key_fields) => {
+            fields = key_fields
+                .iter()
+                .map(|f| cdds_key_field_t {
+                    offset: f.offset,
+                    size: f.size,
+                    count: f.count,
+                })
+                .collect();
+            Some(cdds_key_descriptor_t {
+                nfields: fields.len() as u32,
+                fields: fields.as_ptr(),
+                extractor: None,
+                extractor_arg: std::ptr::null_mut(),
+                extractor_arg_free: None,
+                max_key_size: 0,
+            })
+        }
+        BlobKey::Extractor { extract, max_size } => Some(cdds_key_descriptor_t {
+            nfields: 0,
+            fields: std::ptr::null(),
+            extractor: Some(extract_key),
+            // Freed by free_key_extractor with the topic type
+            extractor_arg: Box::into_raw(Box::new(extract)) as *mut c_void,
+            extractor_arg_free: Some(free_key_extractor),
+            max_key_size: max_size.unwrap_or(0),
+        }),
+    };
+    let type_info = type_information.map(|t| {
+        let type_mapping = t.type_mapping.as_deref().unwrap_or_default();
+        cdds_type_information_t {
+            type_information: t.type_information.as_ptr(),
+            type_information_size: t.type_information.len() as u32,
+            type_mapping: type_mapping.as_ptr(),
+            type_mapping_size: type_mapping.len() as u32,
+        }
+    });
+    // The key fields and the type information are copied
+    let topic = unsafe {
+        cdds_create_blob_topic_with_type_information(
+            participant.entity(),
+            topic_name.as_ptr() as *mut c_char,
+            type_name.as_ptr() as *mut c_char,
+            keys.as_ref().map_or(std::ptr::null(), |k| k as *const _),
+            type_info
+                .as_ref()
+                .map_or(std::ptr::null(), |t| t as *const _),
+        )
+    };
+    check(topic)?;
+    unsafe { Topic::from_raw(participant, topic) }
+}
+
+/// Writes key 1 in little and big-endian, then key 2, on a topic with a `u32` key, and
+/// returns the instance handles of the samples in that order.
+#[cfg(test)]
+fn instance_handles_for_tests(
+    participant: &Participant,
+    topic: &BlobTopic,
+) -> Vec<dds_instance_handle_t> {
+    let writer = BlobWriter::new(participant, topic, None, None).unwrap();
+    let qos = Qos::builder().keep_all().build();
+    let reader = BlobReader::new(participant, topic, Some(&qos), None).unwrap();
+    writer.write(&[0, 1, 0, 0, 1, 0, 0, 0, 0]).unwrap();
+    writer.write(&[0, 0, 0, 0, 0, 0, 0, 1, 1]).unwrap();
+    writer.write(&[0, 1, 0, 0, 2, 0, 0, 0, 2]).unwrap();
+    let mut samples: Vec<_> = reader
+        .take_borrowed(10)
+        .unwrap()
+        .into_iter()
+        .map(|(data, info)| (data[8], info.instance_handle))
+        .collect();
+    samples.sort();
+    samples.into_iter().map(|(_, handle)| handle).collect()
+}
+
+#[test]
+fn test_blob_write_and_take() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = BlobTopic::new(
+        &participant,
+        "test_blob",
+        "cyclors::test::Blob",
+        BlobKey::Keyless,
+    )
+    .unwrap();
+    let writer = BlobWriter::new(&participant, &topic, None, None).unwrap();
+    let qos = Qos::builder().keep_all().build();
+    let reader = BlobReader::new(&participant, &topic, Some(&qos), None).unwrap();
+
+    writer.write(&[0, 1, 0, 0, 1, 2]).unwrap();
+    writer
+        .write_vectored(&[IoSlice::new(&[0, 1, 0, 0]), IoSlice::new(&[3, 4])])
+        .unwrap();
+    assert_eq!(writer.write(&[0, 1]), Err(Error::BadParameter));
+    assert_eq!(writer.write(&[0, 4, 0, 0, 1]), Err(Error::BadParameter));
+
+    let samples = reader.take(10).unwrap();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0].data, vec![0, 1, 0, 0, 1, 2]);
+    assert_eq!(samples[1].data, vec![0, 1, 0, 0, 3, 4]);
+    assert!(samples.iter().all(|s| s.info.valid_data));
+    assert!(reader.take_borrowed(10).unwrap().is_empty());
+}
+
+#[test]
+fn test_keyed_blob_topic() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = BlobTopic::new(
+        &participant,
+        "test_keyed_blob",
+        "cyclors::test::Keyed",
+        BlobKey::Fields(vec![KeyField::new(0, 4)]),
+    )
+    .unwrap();
+    let handles = instance_handles_for_tests(&participant, &topic);
+    assert_eq!(handles.len(), 3);
+    assert_eq!(handles[0], handles[1]);
+    assert_ne!(handles[0], handles[2]);
+
+    // A sample too short for its key is rejected
+    let writer = BlobWriter::new(&participant, &topic, None, None).unwrap();
+    assert_eq!(writer.write(&[0, 1, 0, 0, 1]), Err(Error::BadParameter));
+
+    assert_eq!(
+        BlobTopic::new(
+            &participant,
+            "test_keyed_blob_invalid",
+            "cyclors::test::Invalid",
+            BlobKey::Fields(vec![KeyField::new(0, 3)]),
+        )
+        .unwrap_err(),
+        Error::BadParameter
+    );
+}
+
+#[test]
+fn test_blob_key_extractor() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let extract: KeyExtractor = Box::new(|payload, _| {
+        let key = payload.get(4..8)?;
+        // Little-endian encapsulation
+        Some(if payload[1] & 1 == 1 {
+            key.iter().rev().copied().collect()
+        } else {
+            key.to_vec()
+        })
+    });
+    let topic = BlobTopic::new(
+        &participant,
+        "test_blob_extractor",
+        "cyclors::test::Extracted",
+        BlobKey::Extractor {
+            extract,
+            max_size: Some(4),
+        },
+    )
+    .unwrap();
+    let handles = instance_handles_for_tests(&participant, &topic);
+    assert_eq!(handles.len(), 3);
+    assert_eq!(handles[0], handles[1]);
+    assert_ne!(handles[0], handles[2]);
+}
+
+#[test]
+fn test_blob_topic_with_type_information() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let result = BlobTopic::with_type_information(
+        &participant,
+        "test_blob_type_information",
+        "cyclors::test::Typed",
+        BlobKey::Keyless,
+        &TypeInformation::default(),
+    );
+    assert_eq!(result.unwrap_err(), Error::BadParameter);
+}
+
+/// The type information and type mapping of a type known to the domain of `participant`, as
+/// serialized by Cyclone DDS.
+#[cfg(test)]
+unsafe fn serialize_type_information_for_tests(
+    participant: &Participant,
+    type_info: *const dds_typeinfo_t,
+) -> TypeInformation {
+    let mut descriptor = std::ptr::null_mut();
+    let ret = dds_create_topic_descriptor(
+        dds_find_scope_DDS_FIND_SCOPE_LOCAL_DOMAIN,
+        participant.entity(),
+        type_info,
+        0,
+        &mut descriptor,
+    );
+    assert_eq!(ret, 0);
+    let bytes = |meta: &dds_type_meta_ser| match meta.sz {
+        0 => Vec::new(),
+        sz => std::slice::from_raw_parts(meta.data, sz as usize).to_vec(),
+    };
+    let type_information = TypeInformation {
+        type_information: bytes(&(*descriptor).type_information),
+        type_mapping: Some(bytes(&(*descriptor).type_mapping)),
+    };
+    dds_delete_topic_descriptor(descriptor);
+    type_information
+}
+
+#[test]
+fn test_blob_topic_with_serialized_type_information() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    // A struct Typed { long x; }, whose type information is serialized by Cyclone DDS
+    let type_information = unsafe {
+        let name = CString::new("cyclors::test::Typed").unwrap();
+        let mut descriptor: dds_dynamic_type_descriptor_t = std::mem::zeroed();
+        descriptor.kind = dds_dynamic_type_kind_DDS_DYNAMIC_STRUCTURE;
+        descriptor.name = name.as_ptr();
+        let mut dynamic_type = dds_dynamic_type_create(participant.entity(), descriptor);
+        let member_name = CString::new("x").unwrap();
+        let mut member: dds_dynamic_member_descriptor_t = std::mem::zeroed();
+        member.name = member_name.as_ptr();
+        member.type_.kind = dds_dynamic_type_spec_kind_DDS_DYNAMIC_TYPE_SPEC_KIND_PRIMITIVE;
+        member.type_.type_.primitive = dds_dynamic_type_kind_DDS_DYNAMIC_INT32;
+        assert_eq!(dds_dynamic_type_add_member(&mut dynamic_type, member), 0);
+        let mut type_info = std::ptr::null_mut();
+        assert_eq!(
+            dds_dynamic_type_register(&mut dynamic_type, &mut type_info),
+            0
+        );
+        let type_information = serialize_type_information_for_tests(&participant, type_info);
+        dds_free_typeinfo(type_info);
+        dds_dynamic_type_unref(&mut dynamic_type);
+        type_information
+    };
+
+    let topic = BlobTopic::with_type_information(
+        &participant,
+        "test_blob_serialized_type_information",
+        "cyclors::test::Typed",
+        BlobKey::Keyless,
+        &type_information,
+    )
+    .unwrap();
+    // The topic advertises the type information
+    let mut type_info = std::ptr::null_mut();
+    assert_eq!(
+        unsafe { dds_get_typeinfo(topic.topic().entity(), &mut type_info) },
+        0
+    );
+    assert!(!type_info.is_null());
+    let advertised = unsafe { serialize_type_information_for_tests(&participant, type_info) };
+    unsafe { dds_free_typeinfo(type_info) };
+    assert_eq!(advertised, type_information);
+}
+
+#[test]
+fn test_blob_take_borrowed() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = BlobTopic::new(
+        &participant,
+        "test_blob_borrowed",
+        "cyclors::test::Blob",
+        BlobKey::Keyless,
+    )
+    .unwrap();
+    let writer = BlobWriter::new(&participant, &topic, None, None).unwrap();
+    let reader = BlobReader::new(&participant, &topic, None, None).unwrap();
+    writer.write(&[0, 1, 0, 0, 5, 6, 7]).unwrap();
+
+    let mut samples = reader.take_borrowed(1).unwrap();
+    let (data, info) = samples.pop().unwrap();
+    assert!(info.valid_data);
+    // The payload outlives the reader, and can be forwarded as is
+    drop(reader);
+    let forwarded = std::thread::spawn(move || {
+        let other = BlobWriter::new(&participant, &topic, None, None).unwrap();
+        other.write(&data).unwrap();
+        data.to_vec()
+    })
+    .join()
+    .unwrap();
+    assert_eq!(forwarded, vec![0, 1, 0, 0, 5, 6, 7]);
+}
+
+#[test]
+fn test_blob_dispose() {
+    use crate::sample_info::InstanceState;
+
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = BlobTopic::new(
+        &participant,
+        "test_blob_dispose",
+        "cyclors::test::Keyed",
+        BlobKey::Fields(vec![KeyField::new(0, 4)]),
+    )
+    .unwrap();
+    let writer = BlobWriter::new(&participant, &topic, None, None).unwrap();
+    let reader = BlobReader::new(&participant, &topic, None, None).unwrap();
+    let (first, second) = ([0, 1, 0, 0, 1, 0, 0, 0], [0, 1, 0, 0, 2, 0, 0, 0]);
+    writer.write(&first).unwrap();
+    writer.write(&second).unwrap();
+    let samples = reader.take(10).unwrap();
+    assert_eq!(samples.len(), 2);
+
+    // By sample, and by instance handle
+    let first_handle = writer.lookup_instance(&first).unwrap().unwrap();
+    writer.dispose(&first).unwrap();
+    let second_handle = writer.lookup_instance(&second).unwrap().unwrap();
+    writer.dispose_instance(second_handle).unwrap();
+    assert!(writer
+        .lookup_instance(&[0, 1, 0, 0, 3, 0, 0, 0])
+        .unwrap()
+        .is_none());
+
+    let samples = reader.take(10).unwrap();
+    assert_eq!(samples.len(), 2);
+    assert!(samples
+        .iter()
+        .all(|s| !s.info.valid_data && s.info.instance_state == InstanceState::NOT_ALIVE_DISPOSED));
+    let mut handles: Vec<_> = samples.iter().map(|s| s.info.instance_handle).collect();
+    handles.sort();
+    let mut expected = vec![first_handle, second_handle];
+    expected.sort();
+    assert_eq!(handles, expected);
+}
+
+#[test]
+fn test_blob_topic_filter() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = BlobTopic::new(
+        &participant,
+        "test_blob_filter",
+        "cyclors::test::Blob",
+        BlobKey::Keyless,
+    )
+    .unwrap();
+    topic
+        .set_filter(|payload| payload.get(4) == Some(&1))
+        .unwrap();
+    let writer = BlobWriter::new(&participant, &topic, None, None).unwrap();
+    let qos = Qos::builder().keep_all().build();
+    let reader = BlobReader::new(&participant, &topic, Some(&qos), None).unwrap();
+
+    for i in 0..3 {
+        writer.write(&[0, 1, 0, 0, i]).unwrap();
+    }
+    let samples = reader.take(10).unwrap();
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].data, vec![0, 1, 0, 0, 1]);
+
+    topic.clear_filter().unwrap();
+    writer.write(&[0, 1, 0, 0, 2]).unwrap();
+    assert_eq!(reader.take(10).unwrap().len(), 1);
+}
+
+#[test]
+fn test_blob_reader_topic_filter() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let other = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let (name, type_name) = ("test_blob_reader_filter", "cyclors::test::Blob");
+    let topic = BlobTopic::new(&participant, name, type_name, BlobKey::Keyless).unwrap();
+    let filtered = BlobTopic::new(&other, name, type_name, BlobKey::Keyless).unwrap();
+    // Only the topic of the reader has a filter, applied on reception
+    filtered
+        .set_filter(|payload| payload.get(4) == Some(&1))
+        .unwrap();
+    let qos = Qos::builder().keep_all().build();
+    let reader = BlobReader::new(&other, &filtered, Some(&qos), None).unwrap();
+    let writer = BlobWriter::new(&participant, &topic, None, None).unwrap();
+
+    for i in 0..3 {
+        writer.write(&[0, 1, 0, 0, i]).unwrap();
+    }
+    let samples = reader.take(10).unwrap();
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].data, vec![0, 1, 0, 0, 1]);
+
+    // The replaced filter is kept until the topic is dropped
+    filtered
+        .set_filter(|payload| payload.get(4) == Some(&2))
+        .unwrap();
+    for i in 0..3 {
+        writer.write(&[0, 1, 0, 0, i]).unwrap();
+    }
+    let samples = reader.take(10).unwrap();
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].data, vec![0, 1, 0, 0, 2]);
+}
+
+#[test]
+fn test_blob_unregister_instance() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = BlobTopic::new(
+        &participant,
+        "test_blob_unregister",
+        "cyclors::test::Keyed",
+        BlobKey::Fields(vec![KeyField::new(0, 4)]),
+    )
+    .unwrap();
+    let writer = BlobWriter::new(&participant, &topic, None, None).unwrap();
+    let qos = Qos::builder().keep_all().build();
+    let reader = BlobReader::new(&participant, &topic, Some(&qos), None).unwrap();
+    let data = [0, 1, 0, 0, 1, 0, 0, 0];
+    writer.write(&data).unwrap();
+    assert_eq!(reader.take(10).unwrap().len(), 1);
+
+    // The key sample of the instance is allocated by the type, then freed
+    let handle = writer.lookup_instance(&data).unwrap().unwrap();
+    writer.unregister_instance(handle).unwrap();
+    let samples = reader.take(10).unwrap();
+    assert_eq!(samples.len(), 1);
+    assert!(!samples[0].info.valid_data);
+    assert_eq!(samples[0].info.instance_handle, handle);
+    assert!(writer.lookup_instance(&data).unwrap().is_none());
+}
+
+#[test]
+fn test_blob_loan_sample() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = BlobTopic::new(
+        &participant,
+        "test_blob_loan",
+        "cyclors::test::Blob",
+        BlobKey::Keyless,
+    )
+    .unwrap();
+    let writer = BlobWriter::new(&participant, &topic, None, None).unwrap();
+    let entity = writer.writer().entity();
+
+    // Without shared memory, the loaned sample is allocated by the type, zeroed
+    let mut sample = std::ptr::null_mut();
+    assert_eq!(unsafe { dds_loan_sample(entity, &mut sample) }, 0);
+    let blob = unsafe { &*(sample as *const cdds_blob_sample_t) };
+    assert!(blob.payload.is_null());
+    assert_eq!(blob.size, 0);
+    assert_eq!(unsafe { dds_return_loan(entity, &mut sample, 1) }, 0);
+}
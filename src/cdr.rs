@@ -0,0 +1,602 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+
+mod de;
+mod ser;
+pub use self::de::from_slice;
+pub use self::ser::{to_vec, to_vec_with_endianness, to_vec_with_keys};
+
+/// Derives [`Element`]: enums without data are primitive, as are the newtype structs of
+/// primitive types, and other structs and enums are not.
+#[cfg(feature = "derive")]
+pub use cyclors_derive::Element;
+
+/// The size of the encapsulation header preceding the serialized samples.
+pub const ENCAPSULATION_HEADER_SIZE: usize = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    Big,
+    Little,
+}
+
+impl Endianness {
+    /// The endianness of the host, the most efficient to serialize to.
+    pub const NATIVE: Endianness = if cfg!(target_endian = "little") {
+        Endianness::Little
+    } else {
+        Endianness::Big
+    };
+}
+
+/// The versions of the extended CDR representation of XTypes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum DataRepresentation {
+    XCDR1,
+    XCDR2,
+}
+
+/// The representation of a serialized sample, as identified by its encapsulation header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+pub enum Encapsulation {
+    CDR_BE = 0x0000,
+    CDR_LE = 0x0001,
+    PL_CDR_BE = 0x0002,
+    PL_CDR_LE = 0x0003,
+    CDR2_BE = 0x0006,
+    CDR2_LE = 0x0007,
+    D_CDR2_BE = 0x0008,
+    D_CDR2_LE = 0x0009,
+    PL_CDR2_BE = 0x000a,
+    PL_CDR2_LE = 0x000b,
+}
+
+impl Encapsulation {
+    pub fn from_identifier(identifier: u16) -> Option<Self> {
+        match identifier {
+            0x0000 => Some(Encapsulation::CDR_BE),
+            0x0001 => Some(Encapsulation::CDR_LE),
+            0x0002 => Some(Encapsulation::PL_CDR_BE),
+            0x0003 => Some(Encapsulation::PL_CDR_LE),
+            0x0006 => Some(Encapsulation::CDR2_BE),
+            0x0007 => Some(Encapsulation::CDR2_LE),
+            0x0008 => Some(Encapsulation::D_CDR2_BE),
+            0x0009 => Some(Encapsulation::D_CDR2_LE),
+            0x000a => Some(Encapsulation::PL_CDR2_BE),
+            0x000b => Some(Encapsulation::PL_CDR2_LE),
+            _ => None,
+        }
+    }
+
+    /// The identifier in the encapsulation header (serialized in big-endian).
+    pub fn identifier(self) -> u16 {
+        self as u16
+    }
+
+    pub fn endianness(self) -> Endianness {
+        // The little-endian variant of each representation is the odd identifier
+        if self.identifier() & 1 == 1 {
+            Endianness::Little
+        } else {
+            Endianness::Big
+        }
+    }
+
+    /// Returns the same representation, in the given endianness. This only changes the
+    /// identifier: the serialized sample must be re-encoded too, see [`to_endianness`].
+    pub fn with_endianness(self, endianness: Endianness) -> Self {
+        let identifier = match endianness {
+            Endianness::Big => self.identifier() & !1,
+            Endianness::Little => self.identifier() | 1,
+        };
+        // Both variants of each representation exist
+        Encapsulation::from_identifier(identifier).unwrap_or(self)
+    }
+
+    /// Returns true for the XCDR version 2 representations.
+    pub fn is_xcdr2(self) -> bool {
+        self.identifier() >= Encapsulation::CDR2_BE.identifier()
+    }
+
+    /// Returns true for the parameter list representations, used by mutable types.
+    pub fn is_parameter_list(self) -> bool {
+        matches!(
+            self,
+            Encapsulation::PL_CDR_BE
+                | Encapsulation::PL_CDR_LE
+                | Encapsulation::PL_CDR2_BE
+                | Encapsulation::PL_CDR2_LE
+        )
+    }
+
+    /// The maximum alignment of the primitive types in this representation.
+    pub fn max_alignment(self) -> usize {
+        if self.is_xcdr2() {
+            4
+        } else {
+            8
+        }
+    }
+}
+
+impl fmt::Display for Encapsulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Why a payload is not a valid serialized sample.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CdrError {
+    /// The payload is shorter than what it should contain.
+    TooShort {
+        expected: usize,
+        actual: usize,
+    },
+    UnknownEncapsulation(u16),
+    /// The padding declared in the options of the header is longer than the body.
+    InvalidPadding(u16),
+    /// A feature of the type or of the representation not supported by `to_vec` and
+    /// `from_slice`.
+    Unsupported(&'static str),
+    InvalidBool(u8),
+    InvalidString,
+    /// A member which is not optional is missing in a mutable or appendable type.
+    MissingMember,
+    /// An error of the `Serialize` or `Deserialize` implementation of the type.
+    Message(String),
+}
+
+impl fmt::Display for CdrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CdrError::TooShort { expected, actual } => {
+                write!(f, "payload too short: {actual} bytes instead of {expected}")
+            }
+            CdrError::UnknownEncapsulation(identifier) => {
+                write!(f, "unknown encapsulation identifier: {identifier:#06x}")
+            }
+            CdrError::InvalidPadding(padding) => {
+                write!(f, "padding of {padding} bytes longer than the payload")
+            }
+            CdrError::Unsupported(what) => write!(f, "unsupported: {what}"),
+            CdrError::InvalidBool(v) => write!(f, "invalid boolean: {v}"),
+            CdrError::InvalidString => write!(f, "string not NUL terminated or not UTF-8"),
+            CdrError::MissingMember => write!(f, "missing member"),
+            CdrError::Message(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for CdrError {}
+
+/// The header preceding the serialized samples of the payloads.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EncapsulationHeader {
+    pub encapsulation: Encapsulation,
+    /// The options, whose 2 lowest bits are the number of padding bytes at the end of the
+    /// payload in XCDR2.
+    pub options: u16,
+}
+
+impl EncapsulationHeader {
+    pub fn new(encapsulation: Encapsulation) -> Self {
+        EncapsulationHeader {
+            encapsulation,
+            options: 0,
+        }
+    }
+
+    pub fn parse(payload: &[u8]) -> Result<Self, CdrError> {
+        let header = payload
+            .get(..ENCAPSULATION_HEADER_SIZE)
+            .ok_or(CdrError::TooShort {
+                expected: ENCAPSULATION_HEADER_SIZE,
+                actual: payload.len(),
+            })?;
+        let identifier = u16::from_be_bytes([header[0], header[1]]);
+        Ok(EncapsulationHeader {
+            encapsulation: Encapsulation::from_identifier(identifier)
+                .ok_or(CdrError::UnknownEncapsulation(identifier))?,
+            options: u16::from_be_bytes([header[2], header[3]]),
+        })
+    }
+
+    pub fn to_bytes(self) -> [u8; ENCAPSULATION_HEADER_SIZE] {
+        let [i0, i1] = self.encapsulation.identifier().to_be_bytes();
+        let [o0, o1] = self.options.to_be_bytes();
+        [i0, i1, o0, o1]
+    }
+
+    /// The number of padding bytes at the end of the payload.
+    pub fn padding(self) -> u16 {
+        self.options & 0x3
+    }
+}
+
+/// Splits a payload into its encapsulation header and the serialized sample, without the
+/// final padding.
+pub fn split(payload: &[u8]) -> Result<(EncapsulationHeader, &[u8]), CdrError> {
+    let header = validate(payload)?;
+    let end = payload.len() - header.padding() as usize;
+    Ok((header, &payload[ENCAPSULATION_HEADER_SIZE..end]))
+}
+
+/// Checks that a payload starts with a known encapsulation header consistent with its size,
+/// e.g. before writing it on a blob topic.
+pub fn validate(payload: &[u8]) -> Result<EncapsulationHeader, CdrError> {
+    let header = EncapsulationHeader::parse(payload)?;
+    if header.padding() as usize > payload.len() - ENCAPSULATION_HEADER_SIZE {
+        return Err(CdrError::InvalidPadding(header.padding()));
+    }
+    Ok(header)
+}
+
+/// Re-encodes a payload in the given endianness, keeping its representation. As CDR is not
+/// self-describing, this needs the type `T` of the serialized sample, the payload being
+/// deserialized then serialized again.
+pub fn to_endianness<T>(payload: &[u8], endianness: Endianness) -> Result<Vec<u8>, CdrError>
+where
+    T: Serialize + serde::de::DeserializeOwned,
+{
+    let header = validate(payload)?;
+    if header.encapsulation.endianness() == endianness {
+        return Ok(payload.to_vec());
+    }
+    let value: T = from_slice(payload)?;
+    let representation = if header.encapsulation.is_xcdr2() {
+        DataRepresentation::XCDR2
+    } else {
+        DataRepresentation::XCDR1
+    };
+    to_vec_with_endianness(&value, representation, endianness)
+}
+
+// The names of the newtype structs the wrappers serialize as, to set the extensibility of
+// the wrapped type in `to_vec` and `from_slice`
+const APPENDABLE: &str = "$cyclors::cdr::Appendable";
+const MUTABLE: &str = "$cyclors::cdr::Mutable";
+
+// The names of the newtype structs `sequence` serializes sequences as, to tell `to_vec` the
+// kind of their elements
+const SEQUENCE: &str = "$cyclors::cdr::Sequence";
+const PRIMITIVE_SEQUENCE: &str = "$cyclors::cdr::PrimitiveSequence";
+
+// The parameter identifiers of the extended header and of the end of the parameter lists of
+// XCDR1, which mutable types and optional members are serialized as
+const PID_EXTENDED: u16 = 0x3f01;
+const PID_LIST_END: u16 = 0x3f02;
+
+// The must-understand flags of the parameter identifiers of XCDR1 and of the EMHEADERs of
+// XCDR2, set on the key members of mutable types
+const PID_MUST_UNDERSTAND: u16 = 0x4000;
+const EMHEADER_MUST_UNDERSTAND: u32 = 1 << 31;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Extensibility {
+    Final,
+    Appendable,
+    Mutable,
+}
+
+macro_rules! extensibility_wrapper {
+    ($(#[$meta:meta])* $wrapper:ident, $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+        pub struct $wrapper<T>(pub T);
+
+        impl<T: Serialize> Serialize for $wrapper<T> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct($name, &self.0)
+            }
+        }
+
+        impl<'de, T: Deserialize<'de>> Deserialize<'de> for $wrapper<T> {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer
+                    .deserialize_newtype_struct($name, WrapperVisitor(PhantomData))
+                    .map($wrapper)
+            }
+        }
+    };
+}
+
+extensibility_wrapper!(
+    /// Serializes the wrapped struct as an appendable type: in XCDR2, its members are preceded
+    /// by their size, so that readers of an older version of the type skip the members added
+    /// at its end.
+    Appendable,
+    APPENDABLE
+);
+
+extensibility_wrapper!(
+    /// Serializes the wrapped struct as a mutable type, with each member identified by its
+    /// position in the struct. Absent optional members are omitted. In XCDR1, it is a
+    /// parameter list (`PL_CDR`).
+    Mutable,
+    MUTABLE
+);
+
+/// The types of the elements of sequences, telling whether they are primitive in XCDR2: the
+/// empty sequences of non-primitive types start with a DHEADER, which can't be decided from
+/// their missing elements. The primitive types are the integers, floats, booleans, chars and
+/// enums, and the newtypes of those.
+///
+/// It is implemented by `#[derive(Element)]` with the `derive` feature, and by the code
+/// generated from IDL.
+pub trait Element: Serialize {
+    const PRIMITIVE: bool;
+
+    /// Serializes the value as an element of a sequence, as `sequence` does for sequences.
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize(serializer)
+    }
+}
+
+macro_rules! impl_element {
+    ($primitive:literal, $($ty:ty),*) => {
+        $(impl Element for $ty {
+            const PRIMITIVE: bool = $primitive;
+        })*
+    };
+}
+
+impl_element!(true, bool, i8, i16, i32, i64, u8, u16, u32, u64, f32, f64, char);
+impl_element!(false, String, str);
+
+impl<T: Element + ?Sized> Element for &T {
+    const PRIMITIVE: bool = T::PRIMITIVE;
+
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize_element(serializer)
+    }
+}
+
+impl<T: Element + ?Sized> Element for Box<T> {
+    const PRIMITIVE: bool = T::PRIMITIVE;
+
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize_element(serializer)
+    }
+}
+
+impl<T: Element> Element for Vec<T> {
+    const PRIMITIVE: bool = false;
+
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        sequence::serialize(self, serializer)
+    }
+}
+
+impl<T: Element> Element for [T] {
+    const PRIMITIVE: bool = false;
+
+    fn serialize_element<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        sequence::serialize(self, serializer)
+    }
+}
+
+// Arrays are not primitive as elements of sequences
+impl<T: Serialize, const N: usize> Element for [T; N]
+where
+    [T; N]: Serialize,
+{
+    const PRIMITIVE: bool = false;
+}
+
+impl<T: Serialize> Element for Option<T> {
+    const PRIMITIVE: bool = false;
+}
+
+impl<K: Serialize, V: Serialize> Element for std::collections::BTreeMap<K, V> {
+    const PRIMITIVE: bool = false;
+}
+
+impl<T: Serialize> Element for Appendable<T> {
+    const PRIMITIVE: bool = false;
+}
+
+impl<T: Serialize> Element for Mutable<T> {
+    const PRIMITIVE: bool = false;
+}
+
+/// Serializes the sequences with the kind of their elements, for the members of type `Vec<T>`
+/// or `Option<Vec<T>>` with `#[serde(with = "cyclors::cdr::sequence")]`, so that their empty
+/// sequences start with a DHEADER in XCDR2 if `T` is not primitive. Without it, the empty
+/// sequences are serialized as those of primitive types.
+pub mod sequence {
+    use super::{Element, PRIMITIVE_SEQUENCE, SEQUENCE};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The types of the members serialized by `sequence`.
+    pub trait Sequence {
+        fn serialize_sequence<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+    }
+
+    impl<T: Element> Sequence for [T] {
+        fn serialize_sequence<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let name = if T::PRIMITIVE {
+                PRIMITIVE_SEQUENCE
+            } else {
+                SEQUENCE
+            };
+            serializer.serialize_newtype_struct(name, &Elements(self))
+        }
+    }
+
+    impl<T: Element> Sequence for Vec<T> {
+        fn serialize_sequence<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.as_slice().serialize_sequence(serializer)
+        }
+    }
+
+    impl<V: Sequence> Sequence for Option<V> {
+        fn serialize_sequence<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Some(value) => serializer.serialize_some(&AsSequence(value)),
+                None => serializer.serialize_none(),
+            }
+        }
+    }
+
+    pub fn serialize<V, S>(value: &V, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        V: Sequence + ?Sized,
+        S: Serializer,
+    {
+        value.serialize_sequence(serializer)
+    }
+
+    pub fn deserialize<'de, V, D>(deserializer: D) -> Result<V, D::Error>
+    where
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        V::deserialize(deserializer)
+    }
+
+    struct AsSequence<'a, V: ?Sized>(&'a V);
+
+    impl<V: Sequence + ?Sized> Serialize for AsSequence<'_, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize_sequence(serializer)
+        }
+    }
+
+    // The elements, which may be sequences themselves
+    struct Elements<'a, T>(&'a [T]);
+
+    impl<T: Element> Serialize for Elements<'_, T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.iter().map(AsElement))
+        }
+    }
+
+    struct AsElement<'a, T>(&'a T);
+
+    impl<T: Element> Serialize for AsElement<'_, T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize_element(serializer)
+        }
+    }
+}
+
+struct WrapperVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for WrapperVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a struct")
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+        T::deserialize(deserializer)
+    }
+}
+
+#[test]
+fn test_encapsulation() {
+    for identifier in 0..0x10 {
+        if let Some(encapsulation) = Encapsulation::from_identifier(identifier) {
+            assert_eq!(encapsulation.identifier(), identifier);
+            let other = encapsulation.with_endianness(Endianness::Little);
+            assert_eq!(other.endianness(), Endianness::Little);
+            assert_eq!(
+                other.with_endianness(Endianness::Big).endianness(),
+                Endianness::Big
+            );
+            assert_eq!(other.is_xcdr2(), encapsulation.is_xcdr2());
+        }
+    }
+    assert!(Encapsulation::from_identifier(0x0004).is_none());
+    assert!(Encapsulation::D_CDR2_LE.is_xcdr2());
+    assert!(!Encapsulation::PL_CDR_BE.is_xcdr2());
+    assert!(Encapsulation::PL_CDR2_BE.is_parameter_list());
+    assert_eq!(Encapsulation::CDR_LE.max_alignment(), 8);
+}
+
+#[test]
+fn test_split_payload() {
+    let (header, body) = split(&[0x00, 0x07, 0x00, 0x02, 1, 2, 0, 0]).unwrap();
+    assert_eq!(header.encapsulation, Encapsulation::CDR2_LE);
+    assert_eq!(header.padding(), 2);
+    assert_eq!(body, &[1, 2]);
+    assert_eq!(header.to_bytes(), [0x00, 0x07, 0x00, 0x02]);
+    assert_eq!(EncapsulationHeader::parse(&header.to_bytes()), Ok(header));
+
+    assert_eq!(
+        validate(&[0x00, 0x01]),
+        Err(CdrError::TooShort {
+            expected: 4,
+            actual: 2
+        })
+    );
+    assert_eq!(
+        validate(&[0x00, 0x04, 0x00, 0x00]),
+        Err(CdrError::UnknownEncapsulation(0x0004))
+    );
+    assert_eq!(
+        validate(&[0x00, 0x01, 0x00, 0x03, 1]),
+        Err(CdrError::InvalidPadding(3))
+    );
+}
+
+#[test]
+fn test_to_endianness() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct S {
+        a: u16,
+        b: Vec<u32>,
+        c: String,
+    }
+    let value = S {
+        a: 1,
+        b: vec![2, 3],
+        c: String::from("c"),
+    };
+    for representation in [DataRepresentation::XCDR1, DataRepresentation::XCDR2] {
+        let little = to_vec_with_endianness(&value, representation, Endianness::Little).unwrap();
+        let big = to_vec_with_endianness(&value, representation, Endianness::Big).unwrap();
+        assert_ne!(
+            little[ENCAPSULATION_HEADER_SIZE..],
+            big[ENCAPSULATION_HEADER_SIZE..]
+        );
+        assert_eq!(
+            to_endianness::<S>(&little, Endianness::Big),
+            Ok(big.clone())
+        );
+        assert_eq!(
+            to_endianness::<S>(&big, Endianness::Little),
+            Ok(little.clone())
+        );
+        assert_eq!(to_endianness::<S>(&big, Endianness::Big), Ok(big));
+    }
+    // Appendable, as given by the type
+    let value = Appendable(value);
+    let little =
+        to_vec_with_endianness(&value, DataRepresentation::XCDR2, Endianness::Little).unwrap();
+    let big = to_endianness::<Appendable<S>>(&little, Endianness::Big).unwrap();
+    assert_eq!(
+        EncapsulationHeader::parse(&big).unwrap().encapsulation,
+        Encapsulation::D_CDR2_BE
+    );
+    assert_eq!(from_slice::<Appendable<S>>(&big), Ok(value));
+}
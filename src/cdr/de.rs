@@ -0,0 +1,1002 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use super::*;
+use serde::de::{self, DeserializeSeed, IntoDeserializer};
+
+/// Deserializes a payload with its encapsulation header, in XCDR1 or XCDR2 depending on the
+/// header.
+///
+/// As CDR is not self-describing, in XCDR2 the DHEADER of collections is expected depending on
+/// the type of their first element: the collections of enums with data (unions) can't be
+/// deserialized, and are not serialized by `to_vec` either.
+pub fn from_slice<'de, T: Deserialize<'de>>(payload: &'de [u8]) -> Result<T, CdrError> {
+    let (header, body) = split(payload)?;
+    let encapsulation = header.encapsulation;
+    let mut de = Deserializer {
+        input: body,
+        pos: 0,
+        origin: 0,
+        big_endian: encapsulation.endianness() == Endianness::Big,
+        max_alignment: encapsulation.max_alignment(),
+        pending: None,
+        mutable_member: false,
+        in_array: false,
+        collection: None,
+        collection_len: None,
+        empty_collection: false,
+    };
+    T::deserialize(&mut de)
+}
+
+/// The header of a collection of XCDR2, not yet read as its type is unknown.
+#[derive(Clone, Copy)]
+enum CollectionHeader {
+    /// The first 32 bits of a sequence: its length, or its DHEADER followed by its length.
+    Sequence(u32),
+    /// An array, preceded by a DHEADER or not.
+    Array,
+}
+
+struct Deserializer<'de> {
+    input: &'de [u8],
+    pos: usize,
+    /// The position the alignment is relative to: the start of the body, or of the value of
+    /// the parameter being deserialized in XCDR1.
+    origin: usize,
+    big_endian: bool,
+    max_alignment: usize,
+    /// The extensibility of the next struct, set by the `Appendable` and `Mutable` wrappers.
+    pending: Option<Extensibility>,
+    /// The value being deserialized is a member of a mutable struct.
+    mutable_member: bool,
+    /// The value being deserialized is an element of an array.
+    in_array: bool,
+    /// The header of the collection whose first element is being deserialized.
+    collection: Option<CollectionHeader>,
+    /// The length of the sequence, once its header is resolved.
+    collection_len: Option<u32>,
+    /// The sequence turned out to be an empty collection of non-primitive elements.
+    empty_collection: bool,
+}
+
+impl<'de> Deserializer<'de> {
+    /// Called first by the deserialization of each value, except the transparent ones, with
+    /// whether it is a non-primitive type.
+    fn value(&mut self, complex: bool) -> Result<(), CdrError> {
+        self.mutable_member = false;
+        self.in_array = false;
+        match self.collection.take() {
+            Some(CollectionHeader::Sequence(_)) if complex => {
+                let len = self.read_u32()?;
+                if len == 0 {
+                    self.empty_collection = true;
+                    return Err(CdrError::MissingMember);
+                }
+                self.collection_len = Some(len);
+            }
+            Some(CollectionHeader::Sequence(first)) => self.collection_len = Some(first),
+            Some(CollectionHeader::Array) if complex => {
+                self.read_u32()?;
+            }
+            Some(CollectionHeader::Array) | None => (),
+        }
+        Ok(())
+    }
+
+    fn align(&mut self, size: usize) {
+        let alignment = size.min(self.max_alignment);
+        let offset = self.pos - self.origin;
+        self.pos += (alignment - offset % alignment) % alignment;
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'de [u8], CdrError> {
+        let end = self.pos + len;
+        let bytes = self.input.get(self.pos..end).ok_or(CdrError::TooShort {
+            expected: end + ENCAPSULATION_HEADER_SIZE,
+            actual: self.input.len() + ENCAPSULATION_HEADER_SIZE,
+        })?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read<const N: usize>(&mut self) -> Result<[u8; N], CdrError> {
+        self.align(N);
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(self.take(N)?);
+        if self.big_endian == cfg!(target_endian = "little") {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, CdrError> {
+        self.read().map(u32::from_ne_bytes)
+    }
+
+    /// Reads the header of a parameter of XCDR1, returning its identifier and the end of its
+    /// value, or `None` at the end of a parameter list.
+    fn read_parameter(&mut self) -> Result<Option<(u32, usize)>, CdrError> {
+        self.align(4);
+        // Without the flags of the parameter identifier
+        let pid = u16::from_ne_bytes(self.read()?) & 0x3fff;
+        let size = u16::from_ne_bytes(self.read()?);
+        let (id, size) = match pid {
+            PID_LIST_END => return Ok(None),
+            PID_EXTENDED => {
+                let id = self.read_u32()? & 0x0fff_ffff;
+                let size = self.read_u32()?;
+                (id, size as usize)
+            }
+            _ => (u32::from(pid), size as usize),
+        };
+        let end = self.pos + size;
+        if end > self.input.len() {
+            return Err(CdrError::TooShort {
+                expected: end + ENCAPSULATION_HEADER_SIZE,
+                actual: self.input.len() + ENCAPSULATION_HEADER_SIZE,
+            });
+        }
+        Ok(Some((id, end)))
+    }
+
+    /// Reads the parameter list of a mutable struct of XCDR1, returning the identifiers and
+    /// positions of its members.
+    fn read_parameters(&mut self) -> Result<Vec<(u32, usize)>, CdrError> {
+        let mut members = Vec::new();
+        while let Some((id, end)) = self.read_parameter()? {
+            members.push((id, self.pos));
+            self.pos = end;
+        }
+        Ok(members)
+    }
+
+    /// Reads a DHEADER, returning the end of the delimited value.
+    fn read_dheader(&mut self) -> Result<usize, CdrError> {
+        let size = self.read_u32()? as usize;
+        let end = self.pos + size;
+        if end > self.input.len() {
+            return Err(CdrError::TooShort {
+                expected: end + ENCAPSULATION_HEADER_SIZE,
+                actual: self.input.len() + ENCAPSULATION_HEADER_SIZE,
+            });
+        }
+        Ok(end)
+    }
+
+    /// Reads the EMHEADERs of the members of a mutable struct, returning their identifiers and
+    /// positions.
+    fn read_members(&mut self, end: usize) -> Result<Vec<(u32, usize)>, CdrError> {
+        let mut members = Vec::new();
+        loop {
+            // The members may be followed by the padding of the last one
+            self.align(4);
+            if self.pos >= end {
+                break;
+            }
+            let emheader = self.read_u32()?;
+            let id = emheader & 0x0fff_ffff;
+            let size = match (emheader >> 28) & 0x7 {
+                lc @ 0..=3 => 1 << lc,
+                4 => self.read_u32()? as usize,
+                // The NEXTINT is also the beginning of the member
+                lc => {
+                    let nextint = self.read_u32()? as usize;
+                    self.pos -= 4;
+                    4 + nextint * [1, 4, 8][lc as usize - 5]
+                }
+            };
+            members.push((id, self.pos));
+            self.take(size)?;
+        }
+        Ok(members)
+    }
+
+    fn deserialize_members<V: de::Visitor<'de>>(
+        &mut self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CdrError> {
+        let extensibility = self.pending.take().unwrap_or(Extensibility::Final);
+        self.value(true)?;
+        match extensibility {
+            Extensibility::Appendable if self.max_alignment == 4 => {
+                let end = self.read_dheader()?;
+                let value = visitor.visit_seq(StructAccess {
+                    de: self,
+                    remaining: len,
+                    end: Some(end),
+                })?;
+                // Skipping the members unknown to this version of the type
+                self.pos = end;
+                Ok(value)
+            }
+            Extensibility::Final | Extensibility::Appendable => visitor.visit_seq(StructAccess {
+                de: self,
+                remaining: len,
+                end: None,
+            }),
+            Extensibility::Mutable if self.max_alignment == 4 => {
+                let end = self.read_dheader()?;
+                let members = self.read_members(end)?;
+                let value = visitor.visit_seq(MutableAccess {
+                    de: self,
+                    members,
+                    member_id: 0,
+                    remaining: len,
+                })?;
+                self.pos = end;
+                Ok(value)
+            }
+            Extensibility::Mutable => {
+                let members = self.read_parameters()?;
+                let end = self.pos;
+                let value = visitor.visit_seq(MutableAccess {
+                    de: self,
+                    members,
+                    member_id: 0,
+                    remaining: len,
+                })?;
+                self.pos = end;
+                Ok(value)
+            }
+        }
+    }
+}
+
+/// Deserializes the members of structs, and of the variants of unions.
+struct StructAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    remaining: usize,
+    /// The end of the members of an appendable struct, after which they are missing.
+    end: Option<usize>,
+}
+
+impl<'de> de::SeqAccess<'de> for StructAccess<'_, 'de> {
+    type Error = CdrError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, CdrError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        match self.end {
+            Some(end) if self.de.pos >= end => seed.deserialize(MissingMember).map(Some),
+            _ => seed.deserialize(&mut *self.de).map(Some),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Deserializes the members of a mutable struct, looked up by their position in the type.
+struct MutableAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    members: Vec<(u32, usize)>,
+    member_id: u32,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for MutableAccess<'_, 'de> {
+    type Error = CdrError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, CdrError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let member_id = self.member_id;
+        self.member_id += 1;
+        match self.members.iter().find(|(id, _)| *id == member_id) {
+            Some(&(_, pos)) => {
+                self.de.pos = pos;
+                // The alignment of the parameters of XCDR1 is relative to their start
+                let origin = std::mem::replace(&mut self.de.origin, pos);
+                self.de.mutable_member = true;
+                let value = seed.deserialize(&mut *self.de);
+                self.de.mutable_member = false;
+                self.de.origin = origin;
+                value.map(Some)
+            }
+            None => seed.deserialize(MissingMember).map(Some),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Deserializes the elements of sequences and arrays.
+struct CollectionAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    /// The header, until resolved by the deserialization of the first element.
+    header: Option<CollectionHeader>,
+    remaining: usize,
+    array: bool,
+}
+
+impl<'de> de::SeqAccess<'de> for CollectionAccess<'_, 'de> {
+    type Error = CdrError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, CdrError> {
+        let Some(header) = self.header.take() else {
+            if self.remaining == 0 {
+                return Ok(None);
+            }
+            self.remaining -= 1;
+            self.de.in_array = self.array;
+            return seed.deserialize(&mut *self.de).map(Some);
+        };
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        // The length of the enclosing sequence, if this collection is in its first element
+        let enclosing_len = self.de.collection_len.take();
+        self.de.collection = Some(header);
+        self.de.in_array = self.array;
+        let value = seed.deserialize(&mut *self.de);
+        self.de.collection = None;
+        let len = std::mem::replace(&mut self.de.collection_len, enclosing_len);
+        if let CollectionHeader::Sequence(first) = header {
+            self.remaining = len.unwrap_or(first) as usize;
+        }
+        match value {
+            Ok(value) => {
+                self.remaining -= 1;
+                Ok(Some(value))
+            }
+            Err(_) if std::mem::take(&mut self.de.empty_collection) => {
+                self.remaining = 0;
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Deserializes the key and value pairs of maps.
+struct MapAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    remaining: usize,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'_, 'de> {
+    type Error = CdrError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, CdrError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, CdrError> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Deserializes the members missing from appendable and mutable structs, which must be optional.
+struct MissingMember;
+
+impl<'de> de::Deserializer<'de> for MissingMember {
+    type Error = CdrError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, CdrError> {
+        Err(CdrError::MissingMember)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        visitor.visit_none()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl de::Error for CdrError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CdrError::Message(msg.to_string())
+    }
+}
+
+macro_rules! deserialize_primitive {
+    ($($method:ident: $type:ty => $visit:ident,)*) => {
+        $(
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+                self.value(false)?;
+                visitor.$visit(<$type>::from_ne_bytes(self.read()?))
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = CdrError;
+
+    deserialize_primitive! {
+        deserialize_i8: i8 => visit_i8,
+        deserialize_i16: i16 => visit_i16,
+        deserialize_i32: i32 => visit_i32,
+        deserialize_i64: i64 => visit_i64,
+        deserialize_u8: u8 => visit_u8,
+        deserialize_u16: u16 => visit_u16,
+        deserialize_u32: u32 => visit_u32,
+        deserialize_u64: u64 => visit_u64,
+        deserialize_f32: f32 => visit_f32,
+        deserialize_f64: f64 => visit_f64,
+    }
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, CdrError> {
+        Err(CdrError::Unsupported("deserializing without the type"))
+    }
+
+    fn deserialize_bool<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        self.value(false)?;
+        match self.read::<1>()? {
+            [0] => visitor.visit_bool(false),
+            [1] => visitor.visit_bool(true),
+            [v] => Err(CdrError::InvalidBool(v)),
+        }
+    }
+
+    fn deserialize_char<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        self.value(false)?;
+        let [v] = self.read::<1>()?;
+        visitor.visit_char(char::from(v))
+    }
+
+    fn deserialize_str<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        self.value(true)?;
+        let len = self.read_u32()? as usize;
+        let bytes = match self.take(len)? {
+            [] => &[],
+            [bytes @ .., 0] => bytes,
+            _ => return Err(CdrError::InvalidString),
+        };
+        let s = std::str::from_utf8(bytes).map_err(|_| CdrError::InvalidString)?;
+        visitor.visit_borrowed_str(s)
+    }
+
+    fn deserialize_string<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        self.value(true)?;
+        let len = self.read_u32()? as usize;
+        visitor.visit_borrowed_bytes(self.take(len)?)
+    }
+
+    fn deserialize_byte_buf<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        // Present members of mutable types have no flag
+        if std::mem::take(&mut self.mutable_member) {
+            return visitor.visit_some(self);
+        }
+        self.value(true)?;
+        if self.max_alignment != 4 {
+            // A parameter in XCDR1, empty if absent
+            let (_, end) = self
+                .read_parameter()?
+                .ok_or(CdrError::Message(String::from(
+                    "unexpected end of parameter list",
+                )))?;
+            if self.pos == end {
+                return visitor.visit_none();
+            }
+            let origin = std::mem::replace(&mut self.origin, self.pos);
+            let value = visitor.visit_some(&mut *self);
+            self.origin = origin;
+            self.pos = end;
+            return value;
+        }
+        match self.read::<1>()? {
+            [0] => visitor.visit_none(),
+            [1] => visitor.visit_some(self),
+            [v] => Err(CdrError::InvalidBool(v)),
+        }
+    }
+
+    fn deserialize_unit<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        self.value(false)?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, CdrError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, CdrError> {
+        match name {
+            APPENDABLE => self.pending = Some(Extensibility::Appendable),
+            MUTABLE => self.pending = Some(Extensibility::Mutable),
+            _ => (),
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        self.value(true)?;
+        let first = self.read_u32()?;
+        let (header, remaining) = match self.max_alignment {
+            4 => (Some(CollectionHeader::Sequence(first)), first as usize),
+            _ => (None, first as usize),
+        };
+        visitor.visit_seq(CollectionAccess {
+            de: self,
+            header,
+            remaining,
+            array: false,
+        })
+    }
+
+    fn deserialize_tuple<V: de::Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CdrError> {
+        let header = if std::mem::take(&mut self.in_array) {
+            // The DHEADER of a multidimensional array precedes its first dimension only, and
+            // may still be pending
+            None
+        } else {
+            self.value(true)?;
+            (self.max_alignment == 4).then_some(CollectionHeader::Array)
+        };
+        visitor.visit_seq(CollectionAccess {
+            de: self,
+            header,
+            remaining: len,
+            array: true,
+        })
+    }
+
+    fn deserialize_tuple_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CdrError> {
+        self.deserialize_members(len, visitor)
+    }
+
+    fn deserialize_map<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        self.value(true)?;
+        let end = match self.max_alignment {
+            4 => Some(self.read_dheader()?),
+            _ => None,
+        };
+        let remaining = self.read_u32()? as usize;
+        let value = visitor.visit_map(MapAccess {
+            de: &mut *self,
+            remaining,
+        })?;
+        if let Some(end) = end {
+            self.pos = end;
+        }
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CdrError> {
+        self.deserialize_members(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CdrError> {
+        // Only enums without data are primitive, but it is unknown before the variant
+        self.value(false)?;
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CdrError> {
+        self.deserialize_u32(visitor)
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, CdrError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_i128<V: de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, CdrError> {
+        Err(CdrError::Unsupported("128 bits integers"))
+    }
+
+    fn deserialize_u128<V: de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, CdrError> {
+        Err(CdrError::Unsupported("128 bits integers"))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Deserializer<'de> {
+    type Error = CdrError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), CdrError> {
+        // The value of enums, or the discriminator of unions
+        let index = self.read_u32()?;
+        let variant = seed.deserialize(index.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Deserializer<'de> {
+    type Error = CdrError;
+
+    fn unit_variant(self) -> Result<(), CdrError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, CdrError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CdrError> {
+        self.deserialize_members(len, visitor)
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CdrError> {
+        self.deserialize_members(fields.len(), visitor)
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Shape {
+    Point,
+    Circle(f64),
+    Rectangle { width: u32, height: u32 },
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Sample {
+    id: i32,
+    name: String,
+    flag: bool,
+    values: Vec<f64>,
+    names: Vec<String>,
+    empty: Vec<u8>,
+    matrix: [[u16; 2]; 2],
+    pair: (u8, i64),
+    shape: Shape,
+    kind: Shape,
+    map: std::collections::BTreeMap<u16, String>,
+    nested: Appendable<Nested>,
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Nested {
+    a: u8,
+    b: Option<u64>,
+    c: Option<String>,
+}
+
+#[cfg(test)]
+impl Element for Nested {
+    const PRIMITIVE: bool = false;
+}
+
+#[test]
+fn test_deserialize_roundtrip() {
+    let sample = Sample {
+        id: -3,
+        name: String::from("sample"),
+        flag: true,
+        values: vec![1.5, -2.0],
+        names: vec![String::from("a"), String::new()],
+        empty: Vec::new(),
+        matrix: [[1, 2], [3, 4]],
+        pair: (5, -6),
+        shape: Shape::Rectangle {
+            width: 7,
+            height: 8,
+        },
+        kind: Shape::Point,
+        map: [(9, String::from("nine"))].into_iter().collect(),
+        nested: Appendable(Nested {
+            a: 10,
+            b: Some(11),
+            c: None,
+        }),
+    };
+    for endianness in [Endianness::Big, Endianness::Little] {
+        let payload =
+            to_vec_with_endianness(&sample, DataRepresentation::XCDR2, endianness).unwrap();
+        assert_eq!(from_slice::<Sample>(&payload).as_ref(), Ok(&sample));
+    }
+
+    // Including absent last members, after which there is no padding
+    for c in [Some(String::from("c")), None] {
+        let nested = Mutable(Nested { a: 1, b: None, c });
+        let payload = to_vec(&nested, DataRepresentation::XCDR2).unwrap();
+        assert_eq!(
+            from_slice::<Mutable<Nested>>(&payload).as_ref(),
+            Ok(&nested)
+        );
+        // Also in a member of a mutable type
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Outer {
+            nested: Mutable<Nested>,
+            d: u8,
+        }
+        let outer = Mutable(Outer { nested, d: 2 });
+        let payload = to_vec(&outer, DataRepresentation::XCDR2).unwrap();
+        assert_eq!(from_slice::<Mutable<Outer>>(&payload), Ok(outer));
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Final {
+        a: u8,
+        b: u64,
+        c: [String; 2],
+        d: Shape,
+    }
+    let f = Final {
+        a: 1,
+        b: 2,
+        c: [String::from("x"), String::from("y")],
+        d: Shape::Circle(3.0),
+    };
+    let payload = to_vec(&f, DataRepresentation::XCDR1).unwrap();
+    assert_eq!(from_slice::<Final>(&payload), Ok(f));
+
+    // The length of a sequence with a DHEADER is resolved by its first element, which may
+    // contain other collections
+    let nested = vec![vec![1u8, 2], vec![3]];
+    let payload = to_vec(&nested, DataRepresentation::XCDR2).unwrap();
+    assert_eq!(from_slice::<Vec<Vec<u8>>>(&payload), Ok(nested));
+
+    // Empty sequences, with a DHEADER for non-primitive types only, including enums and
+    // newtypes of primitive types which are primitive
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    enum Color {
+        Red,
+        Green,
+    }
+    impl Element for Color {
+        const PRIMITIVE: bool = true;
+    }
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    struct Id(u32);
+    impl Element for Id {
+        const PRIMITIVE: bool = u32::PRIMITIVE;
+    }
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Empty {
+        #[serde(with = "sequence")]
+        nested: Vec<Nested>,
+        #[serde(with = "sequence")]
+        names: Vec<String>,
+        #[serde(with = "sequence")]
+        colors: Vec<Color>,
+        #[serde(with = "sequence")]
+        ids: Vec<Id>,
+        #[serde(with = "sequence")]
+        optional: Option<Vec<Nested>>,
+        a: u8,
+    }
+    let empty = Empty {
+        nested: Vec::new(),
+        names: Vec::new(),
+        colors: Vec::new(),
+        ids: Vec::new(),
+        optional: Some(Vec::new()),
+        a: 1,
+    };
+    let payload = to_vec(&empty, DataRepresentation::XCDR2).unwrap();
+    assert_eq!(from_slice::<Empty>(&payload), Ok(empty));
+    let filled = Empty {
+        nested: vec![Nested {
+            a: 1,
+            b: None,
+            c: None,
+        }],
+        names: vec![String::from("a")],
+        colors: vec![Color::Green, Color::Red],
+        ids: vec![Id(2)],
+        optional: None,
+        a: 3,
+    };
+    for representation in [DataRepresentation::XCDR1, DataRepresentation::XCDR2] {
+        let payload = to_vec(&filled, representation).unwrap();
+        assert_eq!(from_slice::<Empty>(&payload).as_ref(), Ok(&filled));
+    }
+
+    // The sequences of unions only in XCDR1
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Unions {
+        shapes: Vec<Shape>,
+        k: u8,
+    }
+    let unions = Unions {
+        shapes: vec![Shape::Circle(1.0), Shape::Point],
+        k: 2,
+    };
+    assert_eq!(
+        to_vec(&unions, DataRepresentation::XCDR2),
+        Err(CdrError::Unsupported("unions in collections"))
+    );
+    let payload = to_vec(&unions, DataRepresentation::XCDR1).unwrap();
+    assert_eq!(from_slice::<Unions>(&payload), Ok(unions));
+
+    // The optional members and mutable types of XCDR1
+    for endianness in [Endianness::Big, Endianness::Little] {
+        for c in [Some(String::from("c")), None] {
+            let nested = Nested {
+                a: 1,
+                b: Some(2),
+                c,
+            };
+            let payload =
+                to_vec_with_endianness(&nested, DataRepresentation::XCDR1, endianness).unwrap();
+            assert_eq!(from_slice::<Nested>(&payload).as_ref(), Ok(&nested));
+            #[derive(Debug, PartialEq, Serialize, Deserialize)]
+            struct Outer {
+                nested: Mutable<Nested>,
+                large: Vec<u8>,
+                d: u64,
+            }
+            // Its large member has an extended parameter header
+            let outer = Mutable(Outer {
+                nested: Mutable(nested),
+                large: vec![3; 70000],
+                d: 4,
+            });
+            let payload =
+                to_vec_with_endianness(&outer, DataRepresentation::XCDR1, endianness).unwrap();
+            assert_eq!(from_slice::<Mutable<Outer>>(&payload), Ok(outer));
+        }
+    }
+}
+
+#[test]
+fn test_deserialize_evolution() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct V1 {
+        a: u32,
+        b: Option<u8>,
+    }
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct V2 {
+        a: u32,
+        b: Option<u8>,
+        c: Option<String>,
+    }
+
+    // Readers skip the members appended to the type, and the missing optional ones are absent
+    let v2 = to_vec(
+        &Appendable(V2 {
+            a: 1,
+            b: Some(2),
+            c: Some(String::from("c")),
+        }),
+        DataRepresentation::XCDR2,
+    )
+    .unwrap();
+    assert_eq!(
+        from_slice::<Appendable<V1>>(&v2),
+        Ok(Appendable(V1 { a: 1, b: Some(2) }))
+    );
+    let v1 = to_vec(&Mutable(V1 { a: 1, b: Some(2) }), DataRepresentation::XCDR2).unwrap();
+    assert_eq!(
+        from_slice::<Mutable<V2>>(&v1),
+        Ok(Mutable(V2 {
+            a: 1,
+            b: Some(2),
+            c: None
+        }))
+    );
+
+    let v1 = to_vec(&Mutable(V1 { a: 1, b: Some(2) }), DataRepresentation::XCDR1).unwrap();
+    assert_eq!(
+        from_slice::<Mutable<V2>>(&v1),
+        Ok(Mutable(V2 {
+            a: 1,
+            b: Some(2),
+            c: None
+        }))
+    );
+
+    // Members which are not optional can't be missing
+    #[derive(Debug, Deserialize)]
+    struct V3 {
+        _a: u32,
+        _b: Option<u8>,
+        _c: u8,
+    }
+    assert_eq!(
+        from_slice::<Mutable<V3>>(&v1).unwrap_err(),
+        CdrError::MissingMember
+    );
+
+    // Members of other lengths, written with the LC other than 4 of other implementations
+    let payload = [
+        0x00, 0x0a, 0x00, 0x00, // PL_CDR2_BE
+        0, 0, 0, 16, // DHEADER
+        0x20, 0, 0, 0, 0, 0, 0, 1, // a: LC 2, 4 bytes
+        0x50, 0, 0, 1, 0, 0, 0, 0, // b: LC 5, the length of an empty sequence
+    ];
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct L {
+        a: u32,
+        b: Vec<u8>,
+    }
+    assert_eq!(
+        from_slice::<Mutable<L>>(&payload),
+        Ok(Mutable(L {
+            a: 1,
+            b: Vec::new()
+        }))
+    );
+}
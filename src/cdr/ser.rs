@@ -0,0 +1,1037 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use super::*;
+use serde::ser::{self, Serialize};
+
+/// Serializes `value` in the given representation and the endianness of the host, with the
+/// encapsulation header, as written by blob writers.
+///
+/// In XCDR2, whether a sequence starts with a DHEADER depends on the type of its elements: it
+/// is decided from the first element, or for empty sequences from the [`Element`] type of
+/// those serialized with [`sequence`](super::sequence). Other empty sequences are written
+/// without DHEADER, as those of primitive types. The collections of unions (enums with data)
+/// are not supported in XCDR2, as their DHEADER couldn't be told from the length of a
+/// collection of enums by `from_slice`.
+pub fn to_vec<T>(value: &T, representation: DataRepresentation) -> Result<Vec<u8>, CdrError>
+where
+    T: Serialize + ?Sized,
+{
+    to_vec_with_endianness(value, representation, Endianness::NATIVE)
+}
+
+pub fn to_vec_with_endianness<T>(
+    value: &T,
+    representation: DataRepresentation,
+    endianness: Endianness,
+) -> Result<Vec<u8>, CdrError>
+where
+    T: Serialize + ?Sized,
+{
+    serialize(value, representation, endianness, Vec::new())
+}
+
+/// Serializes `value` as [`to_vec`], setting the must-understand flag of the members of the
+/// top-level struct with the given identifiers (their positions), as required for the key
+/// members of mutable types.
+pub fn to_vec_with_keys<T>(
+    value: &T,
+    representation: DataRepresentation,
+    keys: &[u32],
+) -> Result<Vec<u8>, CdrError>
+where
+    T: Serialize + ?Sized,
+{
+    serialize(value, representation, Endianness::NATIVE, keys.to_vec())
+}
+
+fn serialize<T>(
+    value: &T,
+    representation: DataRepresentation,
+    endianness: Endianness,
+    keys: Vec<u32>,
+) -> Result<Vec<u8>, CdrError>
+where
+    T: Serialize + ?Sized,
+{
+    let mut ser = Serializer {
+        buf: vec![0; ENCAPSULATION_HEADER_SIZE],
+        origin: ENCAPSULATION_HEADER_SIZE,
+        big_endian: endianness == Endianness::Big,
+        xcdr2: representation == DataRepresentation::XCDR2,
+        pending: None,
+        started: false,
+        top_level: Extensibility::Final,
+        mutable_member: false,
+        omitted: false,
+        member_id: 0,
+        in_array: false,
+        element: false,
+        kind: Kind::Primitive,
+        elements: None,
+        keys,
+    };
+    value.serialize(&mut ser)?;
+
+    let encapsulation = match (representation, ser.top_level) {
+        (DataRepresentation::XCDR1, Extensibility::Mutable) => Encapsulation::PL_CDR_BE,
+        (DataRepresentation::XCDR1, _) => Encapsulation::CDR_BE,
+        (DataRepresentation::XCDR2, Extensibility::Final) => Encapsulation::CDR2_BE,
+        (DataRepresentation::XCDR2, Extensibility::Appendable) => Encapsulation::D_CDR2_BE,
+        (DataRepresentation::XCDR2, Extensibility::Mutable) => Encapsulation::PL_CDR2_BE,
+    };
+    // The payload is padded to a multiple of 4 bytes, as declared in the options
+    let padding = (4 - (ser.buf.len() - ENCAPSULATION_HEADER_SIZE) % 4) % 4;
+    ser.buf.resize(ser.buf.len() + padding, 0);
+    let header = EncapsulationHeader {
+        encapsulation: encapsulation.with_endianness(endianness),
+        options: padding as u16,
+    };
+    ser.buf[..ENCAPSULATION_HEADER_SIZE].copy_from_slice(&header.to_bytes());
+    Ok(ser.buf)
+}
+
+struct Serializer {
+    buf: Vec<u8>,
+    /// The position the alignment is relative to: the start of the serialized sample, or of
+    /// the value of the parameter being serialized in XCDR1.
+    origin: usize,
+    big_endian: bool,
+    xcdr2: bool,
+    /// The extensibility of the next struct, set by the `Appendable` and `Mutable` wrappers.
+    pending: Option<Extensibility>,
+    started: bool,
+    top_level: Extensibility,
+    /// The value being serialized is a member of a mutable struct.
+    mutable_member: bool,
+    /// The member of a mutable struct was an absent optional.
+    omitted: bool,
+    /// The identifier of the member being serialized, for the optional ones in XCDR1.
+    member_id: u32,
+    /// The value being serialized is an element of an array.
+    in_array: bool,
+    /// The value being serialized is an element of a collection.
+    element: bool,
+    /// The kind of the type of the last value serialized, set at its end by the non-primitive
+    /// ones.
+    kind: Kind,
+    /// The kind of the elements of the next sequence, as told by `sequence`.
+    elements: Option<Kind>,
+    /// The identifiers of the members of the top-level struct that must be understood, taken
+    /// by its serializer.
+    keys: Vec<u32>,
+}
+
+/// The kinds of types, which decide of the DHEADER of the collections in XCDR2.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Primitive,
+    /// The arrays of primitive types, which are primitive as elements of the arrays they
+    /// form multidimensional arrays with, but not as elements of sequences.
+    PrimitiveArray,
+    NonPrimitive,
+}
+
+impl Serializer {
+    /// Called first by the serialization of each value, except the transparent ones.
+    fn value(&mut self) {
+        self.mutable_member = false;
+        self.member_id = 0;
+        self.in_array = false;
+        self.element = false;
+        self.started = true;
+    }
+
+    fn align(&mut self, size: usize) {
+        let alignment = size.min(if self.xcdr2 { 4 } else { 8 });
+        let offset = self.buf.len() - self.origin;
+        let padding = (alignment - offset % alignment) % alignment;
+        self.buf.resize(self.buf.len() + padding, 0);
+    }
+
+    fn write<const N: usize>(&mut self, be: [u8; N], le: [u8; N]) {
+        self.align(N);
+        self.buf
+            .extend_from_slice(if self.big_endian { &be } else { &le });
+    }
+
+    fn write_u16(&mut self, v: u16) {
+        self.write(v.to_be_bytes(), v.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, v: u32) {
+        self.write(v.to_be_bytes(), v.to_le_bytes());
+    }
+
+    fn u32_bytes(&self, v: u32) -> [u8; 4] {
+        if self.big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    }
+
+    fn write_u32_at(&mut self, pos: usize, v: u32) {
+        let bytes = self.u32_bytes(v);
+        self.buf[pos..pos + 4].copy_from_slice(&bytes);
+    }
+
+    fn write_len(&mut self, len: usize) -> Result<(), CdrError> {
+        let len = u32::try_from(len).map_err(|_| CdrError::Unsupported("length over 2^32"))?;
+        self.write_u32(len);
+        Ok(())
+    }
+
+    /// Writes a placeholder for a DHEADER, returning its position for `end_dheader`.
+    fn begin_dheader(&mut self) -> usize {
+        self.align(4);
+        let pos = self.buf.len();
+        self.buf.extend_from_slice(&[0; 4]);
+        pos
+    }
+
+    fn end_dheader(&mut self, pos: usize) {
+        let size = (self.buf.len() - pos - 4) as u32;
+        self.write_u32_at(pos, size);
+    }
+
+    /// Writes the header of a parameter of XCDR1 (a member of a mutable struct, or an optional
+    /// member), whose value is aligned relative to its start, until `end_parameter`.
+    fn begin_parameter(&mut self, member_id: u32, must_understand: bool) -> Parameter {
+        self.align(4);
+        let header = self.buf.len();
+        let extended = member_id >= u32::from(PID_EXTENDED);
+        let flags = if must_understand {
+            PID_MUST_UNDERSTAND
+        } else {
+            0
+        };
+        if extended {
+            self.write_u16(PID_EXTENDED | flags);
+            self.write_u16(8);
+            self.write_u32(member_id);
+            self.write_u32(0);
+        } else {
+            self.write_u16(member_id as u16 | flags);
+            self.write_u16(0);
+        }
+        let origin = std::mem::replace(&mut self.origin, self.buf.len());
+        Parameter {
+            header,
+            member_id,
+            extended,
+            must_understand,
+            origin,
+        }
+    }
+
+    /// Sets the length of the parameter, using the extended header if it is too long for
+    /// the short one.
+    fn end_parameter(&mut self, parameter: Parameter) -> Result<(), CdrError> {
+        let start = std::mem::replace(&mut self.origin, parameter.origin);
+        let size = u32::try_from(self.buf.len() - start)
+            .map_err(|_| CdrError::Unsupported("length over 2^32"))?;
+        if parameter.extended {
+            self.write_u32_at(parameter.header + 8, size);
+        } else if let Ok(short) = u16::try_from(size) {
+            let bytes = if self.big_endian {
+                short.to_be_bytes()
+            } else {
+                short.to_le_bytes()
+            };
+            self.buf[parameter.header + 2..start].copy_from_slice(&bytes);
+        } else {
+            // The value is moved after the extended header, its alignment being relative to
+            // its start
+            let extended = if parameter.must_understand {
+                PID_EXTENDED | PID_MUST_UNDERSTAND
+            } else {
+                PID_EXTENDED
+            };
+            let pid = if self.big_endian {
+                [extended.to_be_bytes(), 8u16.to_be_bytes()]
+            } else {
+                [extended.to_le_bytes(), 8u16.to_le_bytes()]
+            };
+            let header = [
+                pid.concat(),
+                self.u32_bytes(parameter.member_id).to_vec(),
+                self.u32_bytes(size).to_vec(),
+            ]
+            .concat();
+            self.buf.splice(parameter.header..start, header);
+        }
+        Ok(())
+    }
+
+    /// Checks that the union being serialized is not an element of a collection, as readers
+    /// couldn't tell if the collection starts with a DHEADER in XCDR2.
+    fn begin_union(&self) -> Result<(), CdrError> {
+        if self.xcdr2 && self.element {
+            return Err(CdrError::Unsupported("unions in collections"));
+        }
+        Ok(())
+    }
+
+    fn begin_struct(&mut self) -> Result<StructSerializer<'_>, CdrError> {
+        let extensibility = self.pending.take().unwrap_or(Extensibility::Final);
+        let mut keys = Vec::new();
+        if !self.started {
+            self.top_level = extensibility;
+            keys = std::mem::take(&mut self.keys);
+        }
+        self.value();
+        let dheader = match extensibility {
+            Extensibility::Final => None,
+            Extensibility::Appendable => self.xcdr2.then(|| self.begin_dheader()),
+            // The parameter list of XCDR1 ends with a sentinel instead
+            Extensibility::Mutable => self.xcdr2.then(|| self.begin_dheader()),
+        };
+        Ok(StructSerializer {
+            ser: self,
+            extensibility,
+            dheader,
+            member_id: 0,
+            keys,
+        })
+    }
+
+    fn begin_collection(
+        &mut self,
+        len: Option<usize>,
+    ) -> Result<CollectionSerializer<'_>, CdrError> {
+        // The DHEADER of a multidimensional array precedes its first dimension only
+        let nested = self.in_array;
+        let elements = self.elements.take();
+        self.value();
+        let mut dheader = None;
+        let len = match len {
+            // Without a first element, the DHEADER is decided from the type of the elements
+            Some(0) if self.xcdr2 && elements == Some(Kind::NonPrimitive) => {
+                self.align(4);
+                let start = self.buf.len();
+                dheader = Some(self.begin_dheader());
+                self.write_len(0)?;
+                Some((start, 0))
+            }
+            Some(len) => {
+                self.align(4);
+                let start = self.buf.len();
+                self.write_len(len)?;
+                Some((start, len))
+            }
+            None => None,
+        };
+        Ok(CollectionSerializer {
+            start: self.buf.len(),
+            ser: self,
+            len,
+            nested,
+            first: None,
+            dheader,
+        })
+    }
+}
+
+/// The header of a parameter being serialized, see `Serializer::begin_parameter`.
+struct Parameter {
+    header: usize,
+    member_id: u32,
+    extended: bool,
+    must_understand: bool,
+    /// The origin of the alignment before the parameter.
+    origin: usize,
+}
+
+/// Serializes the members of structs, and of the variants of unions.
+struct StructSerializer<'a> {
+    ser: &'a mut Serializer,
+    extensibility: Extensibility,
+    dheader: Option<usize>,
+    member_id: u32,
+    /// The identifiers of the members that must be understood.
+    keys: Vec<u32>,
+}
+
+impl StructSerializer<'_> {
+    fn member<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CdrError> {
+        let member_id = self.member_id;
+        self.member_id += 1;
+        let ser = &mut *self.ser;
+        if self.extensibility != Extensibility::Mutable {
+            ser.member_id = member_id;
+            return value.serialize(ser);
+        }
+
+        let must_understand = self.keys.contains(&member_id);
+        let previous = ser.buf.len();
+        if !ser.xcdr2 {
+            let parameter = ser.begin_parameter(member_id, must_understand);
+            ser.mutable_member = true;
+            ser.omitted = false;
+            value.serialize(&mut *ser)?;
+            ser.mutable_member = false;
+            if std::mem::take(&mut ser.omitted) {
+                ser.origin = parameter.origin;
+                ser.buf.truncate(previous);
+                return Ok(());
+            }
+            return ser.end_parameter(parameter);
+        }
+
+        ser.align(4);
+        let header = ser.buf.len();
+        // LC 4: the EMHEADER is followed by the size of the member (NEXTINT)
+        let flags = if must_understand {
+            EMHEADER_MUST_UNDERSTAND
+        } else {
+            0
+        };
+        ser.write_u32(flags | (4 << 28) | member_id);
+        ser.write_u32(0);
+        let start = ser.buf.len();
+        ser.mutable_member = true;
+        ser.omitted = false;
+        value.serialize(&mut *ser)?;
+        ser.mutable_member = false;
+        if std::mem::take(&mut ser.omitted) {
+            ser.buf.truncate(previous);
+        } else {
+            let size = (ser.buf.len() - start) as u32;
+            ser.write_u32_at(header + 4, size);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), CdrError> {
+        if let Some(pos) = self.dheader {
+            self.ser.end_dheader(pos);
+        } else if self.extensibility == Extensibility::Mutable {
+            self.ser.align(4);
+            self.ser.write_u16(PID_LIST_END);
+            self.ser.write_u16(0);
+        }
+        self.ser.kind = Kind::NonPrimitive;
+        Ok(())
+    }
+}
+
+/// Serializes sequences (with a length) and arrays (without).
+///
+/// In XCDR2 the collections of non-primitive types start with a DHEADER, which is decided
+/// when serializing their first element.
+struct CollectionSerializer<'a> {
+    ser: &'a mut Serializer,
+    /// The position and value of the length of sequences.
+    len: Option<(usize, usize)>,
+    start: usize,
+    /// An array element of an array.
+    nested: bool,
+    /// The kind of the first element.
+    first: Option<Kind>,
+    dheader: Option<usize>,
+}
+
+impl CollectionSerializer<'_> {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CdrError> {
+        self.ser.in_array = self.len.is_none();
+        self.ser.element = true;
+        if self.first.is_some() || !self.ser.xcdr2 {
+            return value.serialize(&mut *self.ser);
+        }
+        self.ser.kind = Kind::Primitive;
+        value.serialize(&mut *self.ser)?;
+        let kind = self.ser.kind;
+        self.first = Some(kind);
+        let dheader = match self.len {
+            Some(_) => kind != Kind::Primitive,
+            None => kind == Kind::NonPrimitive && !self.nested,
+        };
+        if dheader {
+            // Serialized again, after the DHEADER
+            match self.len {
+                Some((start, len)) => {
+                    self.ser.buf.truncate(start);
+                    self.dheader = Some(self.ser.begin_dheader());
+                    self.ser.write_len(len)?;
+                }
+                None => {
+                    self.ser.buf.truncate(self.start);
+                    self.dheader = Some(self.ser.begin_dheader());
+                }
+            }
+            self.ser.in_array = self.len.is_none();
+            self.ser.element = true;
+            value.serialize(&mut *self.ser)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), CdrError> {
+        if let Some(pos) = self.dheader {
+            self.ser.end_dheader(pos);
+        }
+        self.ser.kind = match (self.len, self.first) {
+            (None, Some(Kind::NonPrimitive)) => Kind::NonPrimitive,
+            (None, _) => Kind::PrimitiveArray,
+            (Some(_), _) => Kind::NonPrimitive,
+        };
+        Ok(())
+    }
+}
+
+/// Serializes maps as sequences of key and value pairs, which are not primitive.
+struct MapSerializer<'a> {
+    ser: &'a mut Serializer,
+    dheader: Option<usize>,
+}
+
+impl ser::Error for CdrError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CdrError::Message(msg.to_string())
+    }
+}
+
+macro_rules! serialize_primitive {
+    ($($method:ident: $type:ty,)*) => {
+        $(
+            fn $method(self, v: $type) -> Result<(), CdrError> {
+                self.value();
+                self.write(v.to_be_bytes(), v.to_le_bytes());
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = CdrError;
+    type SerializeSeq = CollectionSerializer<'a>;
+    type SerializeTuple = CollectionSerializer<'a>;
+    type SerializeTupleStruct = StructSerializer<'a>;
+    type SerializeTupleVariant = StructSerializer<'a>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = StructSerializer<'a>;
+    type SerializeStructVariant = StructSerializer<'a>;
+
+    serialize_primitive! {
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_f32: f32,
+        serialize_f64: f64,
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<(), CdrError> {
+        self.serialize_u8(v as u8)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), CdrError> {
+        // An IDL char is a single byte
+        let v = u8::try_from(v).map_err(|_| CdrError::Unsupported("char out of Latin-1"))?;
+        self.serialize_u8(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), CdrError> {
+        self.value();
+        // The length includes the terminating NUL
+        self.write_len(v.len() + 1)?;
+        self.buf.extend_from_slice(v.as_bytes());
+        self.buf.push(0);
+        self.kind = Kind::NonPrimitive;
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), CdrError> {
+        self.value();
+        self.write_len(v.len())?;
+        self.buf.extend_from_slice(v);
+        self.kind = Kind::NonPrimitive;
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), CdrError> {
+        // Absent members of mutable types are omitted
+        if std::mem::take(&mut self.mutable_member) {
+            self.omitted = true;
+            return Ok(());
+        }
+        if self.xcdr2 {
+            self.serialize_bool(false)?;
+        } else {
+            // An empty parameter in XCDR1
+            let member_id = self.member_id;
+            self.value();
+            let parameter = self.begin_parameter(member_id, false);
+            self.end_parameter(parameter)?;
+        }
+        self.kind = Kind::NonPrimitive;
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), CdrError> {
+        if std::mem::take(&mut self.mutable_member) {
+            return value.serialize(self);
+        }
+        if self.xcdr2 {
+            self.serialize_bool(true)?;
+            value.serialize(&mut *self)?;
+        } else {
+            // A parameter in XCDR1
+            let member_id = self.member_id;
+            self.value();
+            let parameter = self.begin_parameter(member_id, false);
+            value.serialize(&mut *self)?;
+            self.end_parameter(parameter)?;
+        }
+        self.kind = Kind::NonPrimitive;
+        Ok(())
+    }
+
+    fn serialize_unit(self) -> Result<(), CdrError> {
+        self.value();
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), CdrError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), CdrError> {
+        // An enum, as its 32 bits value
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), CdrError> {
+        match name {
+            APPENDABLE => self.pending = Some(Extensibility::Appendable),
+            MUTABLE => self.pending = Some(Extensibility::Mutable),
+            SEQUENCE => self.elements = Some(Kind::NonPrimitive),
+            PRIMITIVE_SEQUENCE => self.elements = Some(Kind::Primitive),
+            _ => (),
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), CdrError> {
+        // A union, as its discriminator and the value of the selected member
+        self.begin_union()?;
+        self.serialize_u32(variant_index)?;
+        value.serialize(&mut *self)?;
+        self.kind = Kind::NonPrimitive;
+        Ok(())
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<CollectionSerializer<'a>, CdrError> {
+        let len = len.ok_or(CdrError::Unsupported("sequences of unknown length"))?;
+        self.begin_collection(Some(len))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<CollectionSerializer<'a>, CdrError> {
+        self.begin_collection(None)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<StructSerializer<'a>, CdrError> {
+        self.begin_struct()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<StructSerializer<'a>, CdrError> {
+        self.begin_union()?;
+        self.serialize_u32(variant_index)?;
+        self.begin_struct()
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer<'a>, CdrError> {
+        let len = len.ok_or(CdrError::Unsupported("maps of unknown length"))?;
+        self.value();
+        let dheader = self.xcdr2.then(|| self.begin_dheader());
+        self.write_len(len)?;
+        Ok(MapSerializer { ser: self, dheader })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<StructSerializer<'a>, CdrError> {
+        self.begin_struct()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<StructSerializer<'a>, CdrError> {
+        self.begin_union()?;
+        self.serialize_u32(variant_index)?;
+        self.begin_struct()
+    }
+
+    fn serialize_i128(self, _v: i128) -> Result<(), CdrError> {
+        Err(CdrError::Unsupported("128 bits integers"))
+    }
+
+    fn serialize_u128(self, _v: u128) -> Result<(), CdrError> {
+        Err(CdrError::Unsupported("128 bits integers"))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl ser::SerializeSeq for CollectionSerializer<'_> {
+    type Ok = ();
+    type Error = CdrError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CdrError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CdrError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for CollectionSerializer<'_> {
+    type Ok = ();
+    type Error = CdrError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CdrError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CdrError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for StructSerializer<'_> {
+    type Ok = ();
+    type Error = CdrError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CdrError> {
+        self.member(value)
+    }
+
+    fn end(self) -> Result<(), CdrError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for StructSerializer<'_> {
+    type Ok = ();
+    type Error = CdrError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CdrError> {
+        self.member(value)
+    }
+
+    fn end(self) -> Result<(), CdrError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for StructSerializer<'_> {
+    type Ok = ();
+    type Error = CdrError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), CdrError> {
+        self.member(value)
+    }
+
+    fn end(self) -> Result<(), CdrError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for StructSerializer<'_> {
+    type Ok = ();
+    type Error = CdrError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), CdrError> {
+        self.member(value)
+    }
+
+    fn end(self) -> Result<(), CdrError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeMap for MapSerializer<'_> {
+    type Ok = ();
+    type Error = CdrError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), CdrError> {
+        key.serialize(&mut *self.ser)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CdrError> {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), CdrError> {
+        if let Some(pos) = self.dheader {
+            self.ser.end_dheader(pos);
+        }
+        self.ser.kind = Kind::NonPrimitive;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_serialize_xcdr1() {
+    #[derive(Serialize)]
+    struct S {
+        a: u8,
+        b: u64,
+        c: String,
+        d: Vec<u16>,
+    }
+    let s = S {
+        a: 1,
+        b: 2,
+        c: String::from("ab"),
+        d: vec![3],
+    };
+    assert_eq!(
+        to_vec_with_endianness(&s, DataRepresentation::XCDR1, Endianness::Little).unwrap(),
+        vec![
+            0x00, 0x01, 0x00, 0x02, // CDR_LE, 2 bytes of padding
+            1, 0, 0, 0, 0, 0, 0, 0, // a, aligned to 8 for b
+            2, 0, 0, 0, 0, 0, 0, 0, // b
+            3, 0, 0, 0, b'a', b'b', 0, 0, // c, with its NUL
+            1, 0, 0, 0, 3, 0, 0, 0, // d, and the final padding
+        ]
+    );
+
+    // Optional members are parameters, empty if absent
+    #[derive(Serialize)]
+    struct O {
+        a: u8,
+        b: Option<u64>,
+        c: Option<u8>,
+    }
+    let o = O {
+        a: 1,
+        b: Some(2),
+        c: None,
+    };
+    assert_eq!(
+        to_vec_with_endianness(&o, DataRepresentation::XCDR1, Endianness::Little).unwrap(),
+        vec![
+            0x00, 0x01, 0x00, 0x00, // CDR_LE
+            1, 0, 0, 0, // a, aligned to 4 for the parameter header
+            1, 0, 8, 0, 2, 0, 0, 0, 0, 0, 0, 0, // b: id 1, length 8
+            2, 0, 0, 0, // c: id 2, length 0
+        ]
+    );
+    assert_eq!(
+        to_vec_with_endianness(&Some(1u8), DataRepresentation::XCDR1, Endianness::Big).unwrap(),
+        vec![0x00, 0x00, 0x00, 0x03, 0, 0, 0, 1, 1, 0, 0, 0]
+    );
+
+    // Mutable types are parameter lists, whose values are aligned relative to their start
+    #[derive(Serialize)]
+    struct D {
+        a: u64,
+    }
+    assert_eq!(
+        to_vec_with_endianness(
+            &Mutable(D { a: 5 }),
+            DataRepresentation::XCDR1,
+            Endianness::Big
+        )
+        .unwrap(),
+        vec![
+            0x00, 0x02, 0x00, 0x00, // PL_CDR_BE
+            0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 5, // a: id 0, length 8
+            0x3f, 0x02, 0, 0, // PID_LIST_END
+        ]
+    );
+}
+
+#[test]
+fn test_serialize_xcdr2() {
+    #[derive(Serialize)]
+    struct Inner {
+        x: u8,
+    }
+    #[derive(Serialize)]
+    struct S {
+        a: u8,
+        b: u64,
+        c: Vec<String>,
+        d: Option<u16>,
+        e: Appendable<Inner>,
+    }
+    let s = Appendable(S {
+        a: 1,
+        b: 2,
+        c: vec![String::from("x")],
+        d: None,
+        e: Appendable(Inner { x: 4 }),
+    });
+    assert_eq!(
+        to_vec_with_endianness(&s, DataRepresentation::XCDR2, Endianness::Big).unwrap(),
+        vec![
+            0x00, 0x08, 0x00, 0x03, // D_CDR2_BE, 3 bytes of padding
+            0, 0, 0, 33, // DHEADER
+            1, 0, 0, 0, // a, aligned to 4 only for b
+            0, 0, 0, 0, 0, 0, 0, 2, // b
+            0, 0, 0, 10, 0, 0, 0, 1, // DHEADER of c, sequence of non-primitive, and length
+            0, 0, 0, 2, b'x', 0, // c[0]
+            0, 0, // d absent, alignment
+            0, 0, 0, 1, 4, // DHEADER of e, then e
+            0, 0, 0, // padding
+        ]
+    );
+
+    #[derive(Serialize, serde::Deserialize)]
+    struct M {
+        a: u16,
+        b: Option<u32>,
+        c: Option<u8>,
+    }
+    let m = Mutable(M {
+        a: 1,
+        b: None,
+        c: Some(2),
+    });
+    assert_eq!(
+        to_vec_with_endianness(&m, DataRepresentation::XCDR2, Endianness::Little).unwrap(),
+        vec![
+            0x00, 0x0b, 0x00, 0x03, // PL_CDR2_LE, 3 bytes of padding
+            21, 0, 0, 0, // DHEADER
+            0, 0, 0, 0x40, 2, 0, 0, 0, 1, 0, // a: EMHEADER (LC 4, id 0), NEXTINT, value
+            0, 0, // b omitted, alignment
+            2, 0, 0, 0x40, 1, 0, 0, 0, 2, // c: id 2
+            0, 0, 0, // padding
+        ]
+    );
+    // Arrays of arrays are multidimensional arrays, of primitive types here, but arrays are
+    // not primitive as elements of sequences
+    assert_eq!(
+        to_vec_with_endianness(
+            &[[1u8, 2], [3, 4]],
+            DataRepresentation::XCDR2,
+            Endianness::Big
+        )
+        .unwrap(),
+        vec![0x00, 0x06, 0x00, 0x00, 1, 2, 3, 4]
+    );
+    assert_eq!(
+        to_vec_with_endianness(&vec![[1u8, 2]], DataRepresentation::XCDR2, Endianness::Big)
+            .unwrap(),
+        vec![0x00, 0x06, 0x00, 0x02, 0, 0, 0, 6, 0, 0, 0, 1, 1, 2, 0, 0]
+    );
+    assert_eq!(
+        to_vec_with_endianness(&m, DataRepresentation::XCDR1, Endianness::Little).unwrap(),
+        vec![
+            0x00, 0x03, 0x00, 0x00, // PL_CDR_LE
+            0, 0, 2, 0, 1, 0, 0, 0, // a: id 0, length 2, then padding
+            2, 0, 1, 0, 2, 0, 0, 0, // b omitted, c: id 2, length 1
+            0x02, 0x3f, 0, 0, // PID_LIST_END
+        ]
+    );
+    // The key members of mutable types must be understood
+    let mut expected = to_vec(&m, DataRepresentation::XCDR2).unwrap();
+    let emheader = EMHEADER_MUST_UNDERSTAND | (4 << 28);
+    expected[8..12].copy_from_slice(&emheader.to_ne_bytes());
+    assert_eq!(
+        to_vec_with_keys(&m, DataRepresentation::XCDR2, &[0]).unwrap(),
+        expected
+    );
+    let mut expected = to_vec(&m, DataRepresentation::XCDR1).unwrap();
+    expected[4..6].copy_from_slice(&PID_MUST_UNDERSTAND.to_ne_bytes());
+    let keyed = to_vec_with_keys(&m, DataRepresentation::XCDR1, &[0]).unwrap();
+    assert_eq!(keyed, expected);
+    assert_eq!(from_slice::<Mutable<M>>(&keyed).unwrap().0.a, 1);
+
+    // Empty sequences have a DHEADER if their elements are of a non-primitive type, as told by
+    // `sequence`, including in sequences of sequences
+    impl Element for Inner {
+        const PRIMITIVE: bool = false;
+    }
+    #[derive(Serialize)]
+    struct Empty {
+        #[serde(with = "sequence")]
+        inner: Vec<Inner>,
+        #[serde(with = "sequence")]
+        names: Vec<String>,
+        #[serde(with = "sequence")]
+        values: Vec<u16>,
+        #[serde(with = "sequence")]
+        nested: Vec<Vec<Inner>>,
+        plain: Vec<Inner>,
+    }
+    let empty = Empty {
+        inner: Vec::new(),
+        names: Vec::new(),
+        values: Vec::new(),
+        nested: vec![Vec::new()],
+        plain: Vec::new(),
+    };
+    assert_eq!(
+        to_vec_with_endianness(&empty, DataRepresentation::XCDR2, Endianness::Little).unwrap(),
+        vec![
+            0x00, 0x07, 0x00, 0x00, // CDR2_LE
+            4, 0, 0, 0, 0, 0, 0, 0, // inner: DHEADER, length
+            4, 0, 0, 0, 0, 0, 0, 0, // names
+            0, 0, 0, 0, // values: length
+            12, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, // nested
+            0, 0, 0, 0, // plain, as a sequence of a primitive type
+        ]
+    );
+}
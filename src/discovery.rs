@@ -0,0 +1,292 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::entity::{read_loans, BuiltinTopic, Entity, Reader, ReaderParent};
+use crate::listener::Listener;
+use crate::qos::Qos;
+use crate::sample_info::SampleInfo;
+use crate::*;
+use serde::Serialize;
+use std::{ffi::CStr, fmt, marker::PhantomData, os::raw::c_char};
+
+mod graph;
+mod monitor;
+pub use graph::{Endpoints, Graph, IncompatiblePair};
+pub use monitor::{DiscoveryEvent, DiscoveryMonitor, LossReason};
+
+/// The GUID identifying a DDS participant or endpoint.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize)]
+pub struct Guid(pub [u8; 16]);
+
+impl From<dds_guid_t> for Guid {
+    fn from(guid: dds_guid_t) -> Self {
+        Guid(guid.v)
+    }
+}
+
+// Same format as in the Cyclone DDS traces
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, chunk) in self.0.chunks(4).enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            for b in chunk {
+                write!(f, "{b:02x}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A sample of the `DCPSParticipant` builtin topic.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiscoveredParticipant {
+    pub key: Guid,
+    pub qos: Qos,
+}
+
+/// A sample of the `DCPSTopic` builtin topic.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiscoveredTopic {
+    pub key: [u8; 16],
+    pub topic_name: String,
+    pub type_name: String,
+    pub qos: Qos,
+}
+
+/// A sample of the `DCPSPublication` or `DCPSSubscription` builtin topic.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiscoveredEndpoint {
+    pub key: Guid,
+    pub participant_key: Guid,
+    pub participant_instance_handle: dds_instance_handle_t,
+    pub topic_name: String,
+    pub type_name: String,
+    pub qos: Qos,
+}
+
+// Only the key is set in invalid samples, the pointers may be null
+unsafe fn string_from_native(s: *const c_char) -> String {
+    if s.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(s).to_string_lossy().into_owned()
+    }
+}
+
+unsafe fn qos_from_native(qos: *const dds_qos_t) -> Qos {
+    if qos.is_null() {
+        Qos::default()
+    } else {
+        Qos::from_qos_native(qos)
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// The types of the samples of the builtin topics.
+pub trait BuiltinSample: sealed::Sealed + Sized {
+    #[doc(hidden)]
+    type Native;
+
+    #[doc(hidden)]
+    unsafe fn from_native(sample: &Self::Native) -> Self;
+}
+
+impl sealed::Sealed for DiscoveredParticipant {}
+
+impl BuiltinSample for DiscoveredParticipant {
+    type Native = dds_builtintopic_participant_t;
+
+    unsafe fn from_native(sample: &Self::Native) -> Self {
+        DiscoveredParticipant {
+            key: sample.key.into(),
+            qos: qos_from_native(sample.qos),
+        }
+    }
+}
+
+impl sealed::Sealed for DiscoveredTopic {}
+
+impl BuiltinSample for DiscoveredTopic {
+    type Native = dds_builtintopic_topic_t;
+
+    unsafe fn from_native(sample: &Self::Native) -> Self {
+        DiscoveredTopic {
+            key: sample.key.d,
+            topic_name: string_from_native(sample.topic_name),
+            type_name: string_from_native(sample.type_name),
+            qos: qos_from_native(sample.qos),
+        }
+    }
+}
+
+impl sealed::Sealed for DiscoveredEndpoint {}
+
+impl BuiltinSample for DiscoveredEndpoint {
+    type Native = dds_builtintopic_endpoint_t;
+
+    unsafe fn from_native(sample: &Self::Native) -> Self {
+        DiscoveredEndpoint {
+            key: sample.key.into(),
+            participant_key: sample.participant_key.into(),
+            participant_instance_handle: sample.participant_instance_handle,
+            topic_name: string_from_native(sample.topic_name),
+            type_name: string_from_native(sample.type_name),
+            qos: qos_from_native(sample.qos),
+        }
+    }
+}
+
+/// A reader of a builtin topic, returning its samples as owned Rust structs.
+#[derive(Debug, Clone)]
+pub struct DiscoveryReader<T: BuiltinSample> {
+    reader: Reader,
+    _sample: PhantomData<fn() -> T>,
+}
+
+impl<T: BuiltinSample> DiscoveryReader<T> {
+    fn new<P: ReaderParent>(
+        parent: &P,
+        topic: BuiltinTopic,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        Ok(DiscoveryReader {
+            reader: Reader::new(parent, &topic, qos, listener)?,
+            _sample: PhantomData,
+        })
+    }
+
+    /// Returns the underlying reader, e.g. to set a listener on it.
+    pub fn reader(&self) -> &Reader {
+        &self.reader
+    }
+
+    /// Takes all the available samples from the reader cache.
+    pub fn take(&self) -> Result<Vec<(T, SampleInfo)>> {
+        self.read_or_take(true)
+    }
+
+    /// Returns the samples not read yet, leaving them in the reader cache.
+    pub fn read(&self) -> Result<Vec<(T, SampleInfo)>> {
+        self.read_or_take(false)
+    }
+
+    fn read_or_take(&self, take: bool) -> Result<Vec<(T, SampleInfo)>> {
+        unsafe { read_samples(self.reader.entity(), take) }
+    }
+}
+
+unsafe fn read_samples<T: BuiltinSample>(
+    reader: dds_entity_t,
+    take: bool,
+) -> Result<Vec<(T, SampleInfo)>> {
+    read_loans(reader, take, |sample, info| {
+        (
+            T::from_native(&*(sample as *const T::Native)),
+            SampleInfo::from(info),
+        )
+    })
+}
+
+impl DiscoveryReader<DiscoveredParticipant> {
+    pub fn participants<P: ReaderParent>(
+        parent: &P,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        Self::new(parent, BuiltinTopic::DcpsParticipant, qos, listener)
+    }
+}
+
+impl DiscoveryReader<DiscoveredTopic> {
+    /// Requires Cyclone DDS to be built with topic discovery enabled.
+    pub fn topics<P: ReaderParent>(
+        parent: &P,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        Self::new(parent, BuiltinTopic::DcpsTopic, qos, listener)
+    }
+}
+
+impl DiscoveryReader<DiscoveredEndpoint> {
+    pub fn publications<P: ReaderParent>(
+        parent: &P,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        Self::new(parent, BuiltinTopic::DcpsPublication, qos, listener)
+    }
+
+    pub fn subscriptions<P: ReaderParent>(
+        parent: &P,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        Self::new(parent, BuiltinTopic::DcpsSubscription, qos, listener)
+    }
+}
+
+#[test]
+fn test_guid_display() {
+    let guid = Guid([
+        0x01, 0x10, 0x2c, 0x4f, 0x00, 0x00, 0x00, 0x01, 0xab, 0xcd, 0xef, 0x00, 0x00, 0x00, 0x01,
+        0xc1,
+    ]);
+    assert_eq!(guid.to_string(), "01102c4f:00000001:abcdef00:000001c1");
+}
+
+#[test]
+fn test_discover_own_participant() {
+    use crate::entity::Participant;
+
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let mut guid = std::mem::MaybeUninit::<dds_guid_t>::uninit();
+    unsafe {
+        assert_eq!(dds_get_guid(participant.entity(), guid.as_mut_ptr()), 0);
+    }
+    let guid = Guid::from(unsafe { guid.assume_init() });
+
+    let reader = DiscoveryReader::participants(&participant, None, None).unwrap();
+    let samples = reader.read().unwrap();
+    assert!(samples
+        .iter()
+        .any(|(p, info)| p.key == guid && info.valid_data));
+    // Already read samples are not returned again
+    assert!(reader.read().unwrap().is_empty());
+}
+
+#[test]
+fn test_discover_publication() {
+    use crate::entity::{create_blob_topic_for_tests, Participant, Writer};
+
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = unsafe { create_blob_topic_for_tests(&participant, "test_discovery") };
+    let writer = Writer::new(&participant, &topic, None, None).unwrap();
+    let mut guid = std::mem::MaybeUninit::<dds_guid_t>::uninit();
+    unsafe {
+        assert_eq!(dds_get_guid(writer.entity(), guid.as_mut_ptr()), 0);
+    }
+    let guid = Guid::from(unsafe { guid.assume_init() });
+
+    let reader = DiscoveryReader::publications(&participant, None, None).unwrap();
+    let samples = reader.take().unwrap();
+    let (publication, _) = samples.iter().find(|(p, _)| p.key == guid).unwrap();
+    assert_eq!(publication.topic_name, "test_discovery");
+    assert_eq!(publication.type_name, "cyclors::test::Blob");
+}
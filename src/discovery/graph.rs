@@ -0,0 +1,286 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use super::*;
+use crate::qos::{check_compatibility, Incompatibility};
+#[cfg(test)]
+use crate::time::DdsDuration;
+use std::collections::BTreeMap;
+
+/// The writers and readers of a participant.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Endpoints<'a> {
+    pub writers: Vec<&'a DiscoveredEndpoint>,
+    pub readers: Vec<&'a DiscoveredEndpoint>,
+}
+
+/// A writer and a reader on the same topic whose types or QoS don't match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncompatiblePair<'a> {
+    pub writer: &'a DiscoveredEndpoint,
+    pub reader: &'a DiscoveredEndpoint,
+    pub incompatibilities: Vec<Incompatibility>,
+}
+
+/// The participants, writers and readers of a domain, maintained from [`DiscoveryEvent`]s
+/// (e.g. from a [`DiscoveryMonitor`]).
+///
+/// The query results are ordered by GUID.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    participants: BTreeMap<Guid, DiscoveredParticipant>,
+    writers: BTreeMap<Guid, DiscoveredEndpoint>,
+    readers: BTreeMap<Guid, DiscoveredEndpoint>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Graph::default()
+    }
+
+    /// Updates the graph with a discovery event.
+    pub fn apply(&mut self, event: DiscoveryEvent) {
+        match event {
+            DiscoveryEvent::ParticipantJoined(participant) => {
+                self.participants.insert(participant.key, participant);
+            }
+            DiscoveryEvent::ParticipantLeft { key, .. } => {
+                self.participants.remove(&key);
+                // Don't wait for the endpoints of the participant to be reported lost
+                self.writers.retain(|_, w| w.participant_key != key);
+                self.readers.retain(|_, r| r.participant_key != key);
+            }
+            DiscoveryEvent::PublicationDiscovered(writer)
+            | DiscoveryEvent::PublicationUpdated(writer) => {
+                self.writers.insert(writer.key, writer);
+            }
+            DiscoveryEvent::PublicationLost { key, .. } => {
+                self.writers.remove(&key);
+            }
+            DiscoveryEvent::SubscriptionDiscovered(reader)
+            | DiscoveryEvent::SubscriptionUpdated(reader) => {
+                self.readers.insert(reader.key, reader);
+            }
+            DiscoveryEvent::SubscriptionLost { key, .. } => {
+                self.readers.remove(&key);
+            }
+        }
+    }
+
+    pub fn participants(&self) -> impl Iterator<Item = &DiscoveredParticipant> {
+        self.participants.values()
+    }
+
+    pub fn participant(&self, key: &Guid) -> Option<&DiscoveredParticipant> {
+        self.participants.get(key)
+    }
+
+    pub fn writers_for_topic(&self, topic_name: &str) -> Vec<&DiscoveredEndpoint> {
+        self.writers
+            .values()
+            .filter(|w| w.topic_name == topic_name)
+            .collect()
+    }
+
+    pub fn readers_for_topic(&self, topic_name: &str) -> Vec<&DiscoveredEndpoint> {
+        self.readers
+            .values()
+            .filter(|r| r.topic_name == topic_name)
+            .collect()
+    }
+
+    pub fn endpoints_of_participant(&self, participant: &Guid) -> Endpoints<'_> {
+        Endpoints {
+            writers: self
+                .writers
+                .values()
+                .filter(|w| w.participant_key == *participant)
+                .collect(),
+            readers: self
+                .readers
+                .values()
+                .filter(|r| r.participant_key == *participant)
+                .collect(),
+        }
+    }
+
+    /// Returns the writers and readers on the same topic whose type names and QoS match.
+    pub fn matched_pairs(&self) -> Vec<(&DiscoveredEndpoint, &DiscoveredEndpoint)> {
+        self.topic_pairs()
+            .filter(|(w, r)| check_pair(w, r).is_ok())
+            .collect()
+    }
+
+    /// Returns the writers and readers on the same topic that will not match: either their
+    /// type names differ (reported as an incompatibility of the `"type_name"` policy), or their
+    /// QoS don't match according to the RxO rules of [`check_compatibility`].
+    pub fn incompatible_pairs(&self) -> Vec<IncompatiblePair<'_>> {
+        self.topic_pairs()
+            .filter_map(|(writer, reader)| {
+                check_pair(writer, reader)
+                    .err()
+                    .map(|incompatibilities| IncompatiblePair {
+                        writer,
+                        reader,
+                        incompatibilities,
+                    })
+            })
+            .collect()
+    }
+
+    fn topic_pairs(&self) -> impl Iterator<Item = (&DiscoveredEndpoint, &DiscoveredEndpoint)> {
+        self.writers.values().flat_map(move |w| {
+            self.readers
+                .values()
+                .filter(move |r| r.topic_name == w.topic_name)
+                .map(move |r| (w, r))
+        })
+    }
+}
+
+/// Checks whether a writer and a reader on the same topic match. Without the type information,
+/// the types are only assignable when their names are the same.
+fn check_pair(
+    writer: &DiscoveredEndpoint,
+    reader: &DiscoveredEndpoint,
+) -> Result<(), Vec<Incompatibility>> {
+    let mut incompatibilities = check_compatibility(&writer.qos, &reader.qos)
+        .err()
+        .unwrap_or_default();
+    if writer.type_name != reader.type_name {
+        incompatibilities.insert(
+            0,
+            Incompatibility {
+                policy: "type_name",
+                reason: format!(
+                    "offered type {} is not the requested type {}",
+                    writer.type_name, reader.type_name
+                ),
+            },
+        );
+    }
+    if incompatibilities.is_empty() {
+        Ok(())
+    } else {
+        Err(incompatibilities)
+    }
+}
+
+#[cfg(test)]
+fn endpoint_for_tests(
+    key: u8,
+    participant_key: u8,
+    topic_name: &str,
+    qos: Qos,
+) -> DiscoveredEndpoint {
+    DiscoveredEndpoint {
+        key: Guid([key; 16]),
+        participant_key: Guid([participant_key; 16]),
+        participant_instance_handle: participant_key as dds_instance_handle_t,
+        topic_name: String::from(topic_name),
+        type_name: String::from("M::T"),
+        qos,
+    }
+}
+
+#[test]
+fn test_graph_queries() {
+    let mut graph = Graph::new();
+    for key in [1, 2] {
+        graph.apply(DiscoveryEvent::ParticipantJoined(DiscoveredParticipant {
+            key: Guid([key; 16]),
+            qos: Qos::default(),
+        }));
+    }
+    let writer = endpoint_for_tests(10, 1, "T", Qos::default());
+    let other_writer = endpoint_for_tests(11, 1, "U", Qos::default());
+    let reader = endpoint_for_tests(
+        20,
+        2,
+        "T",
+        Qos::builder().reliable(DdsDuration::ZERO).build(),
+    );
+    let durable_reader = endpoint_for_tests(21, 2, "T", Qos::builder().transient_local().build());
+    graph.apply(DiscoveryEvent::PublicationDiscovered(writer.clone()));
+    graph.apply(DiscoveryEvent::PublicationDiscovered(other_writer.clone()));
+    graph.apply(DiscoveryEvent::SubscriptionDiscovered(reader.clone()));
+    graph.apply(DiscoveryEvent::SubscriptionDiscovered(
+        durable_reader.clone(),
+    ));
+
+    assert_eq!(graph.participants().count(), 2);
+    assert_eq!(graph.writers_for_topic("T"), vec![&writer]);
+    assert_eq!(graph.readers_for_topic("T"), vec![&reader, &durable_reader]);
+    assert!(graph.readers_for_topic("U").is_empty());
+    assert_eq!(
+        graph.endpoints_of_participant(&Guid([1; 16])),
+        Endpoints {
+            writers: vec![&writer, &other_writer],
+            readers: vec![],
+        }
+    );
+
+    assert_eq!(graph.matched_pairs(), vec![(&writer, &reader)]);
+    let incompatible = graph.incompatible_pairs();
+    assert_eq!(incompatible.len(), 1);
+    assert_eq!(incompatible[0].reader, &durable_reader);
+    assert_eq!(incompatible[0].incompatibilities[0].policy, "durability");
+
+    // An update of the writer QoS fixes the mismatch
+    let durable_writer = DiscoveredEndpoint {
+        qos: Qos::builder().transient_local().build(),
+        ..writer
+    };
+    graph.apply(DiscoveryEvent::PublicationUpdated(durable_writer.clone()));
+    assert_eq!(graph.matched_pairs().len(), 2);
+    assert!(graph.incompatible_pairs().is_empty());
+
+    graph.apply(DiscoveryEvent::SubscriptionLost {
+        key: reader.key,
+        reason: LossReason::Disposed,
+    });
+    assert_eq!(
+        graph.matched_pairs(),
+        vec![(&durable_writer, &durable_reader)]
+    );
+
+    // Leaving removes the endpoints of the participant
+    graph.apply(DiscoveryEvent::ParticipantLeft {
+        key: Guid([1; 16]),
+        reason: LossReason::NoWriters,
+    });
+    assert!(graph.participant(&Guid([1; 16])).is_none());
+    assert!(graph.writers_for_topic("T").is_empty());
+    assert_eq!(graph.readers_for_topic("T"), vec![&durable_reader]);
+
+    // A reader of another type on the topic doesn't match
+    let other_type_reader = DiscoveredEndpoint {
+        type_name: String::from("M::U"),
+        ..endpoint_for_tests(22, 2, "T", Qos::builder().transient_local().build())
+    };
+    graph.apply(DiscoveryEvent::PublicationDiscovered(
+        durable_writer.clone(),
+    ));
+    graph.apply(DiscoveryEvent::SubscriptionDiscovered(
+        other_type_reader.clone(),
+    ));
+    assert_eq!(
+        graph.matched_pairs(),
+        vec![(&durable_writer, &durable_reader)]
+    );
+    let incompatible = graph.incompatible_pairs();
+    assert_eq!(incompatible.len(), 1);
+    assert_eq!(incompatible[0].reader, &other_type_reader);
+    assert_eq!(incompatible[0].incompatibilities.len(), 1);
+    assert_eq!(incompatible[0].incompatibilities[0].policy, "type_name");
+}
@@ -0,0 +1,347 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use super::*;
+use crate::sample_info::InstanceState;
+use log::warn;
+use std::{
+    collections::HashMap,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Why a discovered participant or endpoint is no longer alive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+pub enum LossReason {
+    /// The instance was disposed (`NOT_ALIVE_DISPOSED`), e.g. the remote entity was deleted.
+    Disposed,
+    /// The instance has no live writer (`NOT_ALIVE_NO_WRITERS`), e.g. the lease of the
+    /// remote participant expired.
+    NoWriters,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum DiscoveryEvent {
+    ParticipantJoined(DiscoveredParticipant),
+    ParticipantLeft {
+        key: Guid,
+        reason: LossReason,
+    },
+    PublicationDiscovered(DiscoveredEndpoint),
+    /// The QoS of an already discovered publication changed.
+    PublicationUpdated(DiscoveredEndpoint),
+    PublicationLost {
+        key: Guid,
+        reason: LossReason,
+    },
+    SubscriptionDiscovered(DiscoveredEndpoint),
+    /// The QoS of an already discovered subscription changed.
+    SubscriptionUpdated(DiscoveredEndpoint),
+    SubscriptionLost {
+        key: Guid,
+        reason: LossReason,
+    },
+}
+
+/// Turns the samples of a builtin topic into events, keeping the last value of each
+/// alive instance to tell new instances from updates.
+struct Tracker<T> {
+    known: HashMap<Guid, T>,
+    key: fn(&T) -> Guid,
+    discovered: fn(T) -> DiscoveryEvent,
+    updated: Option<fn(T) -> DiscoveryEvent>,
+    lost: fn(Guid, LossReason) -> DiscoveryEvent,
+    events: Sender<DiscoveryEvent>,
+}
+
+impl<T: Clone + PartialEq> Tracker<T> {
+    fn process(&mut self, samples: Vec<(T, SampleInfo)>) {
+        for (sample, info) in samples {
+            let key = (self.key)(&sample);
+            let reason = match info.instance_state {
+                InstanceState::ALIVE => {
+                    // Without valid data the sample only notifies a state change
+                    if info.valid_data {
+                        self.alive(key, sample);
+                    }
+                    continue;
+                }
+                InstanceState::NOT_ALIVE_DISPOSED => LossReason::Disposed,
+                InstanceState::NOT_ALIVE_NO_WRITERS => LossReason::NoWriters,
+            };
+            if self.known.remove(&key).is_some() {
+                self.send((self.lost)(key, reason));
+            }
+        }
+    }
+
+    fn alive(&mut self, key: Guid, sample: T) {
+        let event = match self.known.insert(key, sample.clone()) {
+            None => (self.discovered)(sample),
+            Some(previous) if previous != sample => match self.updated {
+                Some(updated) => updated(sample),
+                None => return,
+            },
+            Some(_) => return,
+        };
+        self.send(event);
+    }
+
+    fn send(&self, event: DiscoveryEvent) {
+        // The receiver is only dropped with the monitor
+        let _ = self.events.send(event);
+    }
+}
+
+fn participant_tracker(events: Sender<DiscoveryEvent>) -> Tracker<DiscoveredParticipant> {
+    Tracker {
+        known: HashMap::new(),
+        key: |p| p.key,
+        discovered: DiscoveryEvent::ParticipantJoined,
+        updated: None,
+        lost: |key, reason| DiscoveryEvent::ParticipantLeft { key, reason },
+        events,
+    }
+}
+
+fn publication_tracker(events: Sender<DiscoveryEvent>) -> Tracker<DiscoveredEndpoint> {
+    Tracker {
+        known: HashMap::new(),
+        key: |e| e.key,
+        discovered: DiscoveryEvent::PublicationDiscovered,
+        updated: Some(DiscoveryEvent::PublicationUpdated),
+        lost: |key, reason| DiscoveryEvent::PublicationLost { key, reason },
+        events,
+    }
+}
+
+fn subscription_tracker(events: Sender<DiscoveryEvent>) -> Tracker<DiscoveredEndpoint> {
+    Tracker {
+        known: HashMap::new(),
+        key: |e| e.key,
+        discovered: DiscoveryEvent::SubscriptionDiscovered,
+        updated: Some(DiscoveryEvent::SubscriptionUpdated),
+        lost: |key, reason| DiscoveryEvent::SubscriptionLost { key, reason },
+        events,
+    }
+}
+
+fn take_and_process<T>(tracker: &Mutex<Tracker<T>>, reader: dds_entity_t)
+where
+    T: BuiltinSample + Clone + PartialEq,
+{
+    // Taking under the lock keeps the events in the order of the samples
+    let mut tracker = tracker.lock().unwrap();
+    match unsafe { read_samples::<T>(reader, true) } {
+        Ok(samples) => tracker.process(samples),
+        Err(e) => warn!("Failed to take discovery samples from reader {reader}: {e}"),
+    }
+}
+
+fn tracked_reader<P, T, F>(parent: &P, create: F, tracker: Tracker<T>) -> Result<DiscoveryReader<T>>
+where
+    P: ReaderParent,
+    T: BuiltinSample + Clone + PartialEq + Send + 'static,
+    F: FnOnce(&P, Option<&Qos>, Option<Listener>) -> Result<DiscoveryReader<T>>,
+{
+    let tracker = Arc::new(Mutex::new(tracker));
+    let listener = Listener::new().on_data_available({
+        let tracker = tracker.clone();
+        move |reader| take_and_process(&tracker, reader)
+    });
+    let reader = create(parent, None, Some(listener))?;
+    // Samples already known may be delivered before the listener is enabled
+    take_and_process(&tracker, reader.reader().entity());
+    Ok(reader)
+}
+
+/// Reports the participants, publications and subscriptions appearing, changing and
+/// disappearing in the domain, as a stream of [`DiscoveryEvent`].
+///
+/// The local entities, including the ones of the parent participant, are reported too.
+#[derive(Debug)]
+pub struct DiscoveryMonitor {
+    _participants: DiscoveryReader<DiscoveredParticipant>,
+    _publications: DiscoveryReader<DiscoveredEndpoint>,
+    _subscriptions: DiscoveryReader<DiscoveredEndpoint>,
+    events: Receiver<DiscoveryEvent>,
+}
+
+impl DiscoveryMonitor {
+    pub fn new<P: ReaderParent>(parent: &P) -> Result<Self> {
+        let (tx, events) = channel();
+        Ok(DiscoveryMonitor {
+            _participants: tracked_reader(
+                parent,
+                DiscoveryReader::participants,
+                participant_tracker(tx.clone()),
+            )?,
+            _publications: tracked_reader(
+                parent,
+                DiscoveryReader::publications,
+                publication_tracker(tx.clone()),
+            )?,
+            _subscriptions: tracked_reader(
+                parent,
+                DiscoveryReader::subscriptions,
+                subscription_tracker(tx),
+            )?,
+            events,
+        })
+    }
+
+    /// Waits for the next event.
+    pub fn recv(&self) -> Option<DiscoveryEvent> {
+        self.events.recv().ok()
+    }
+
+    /// Waits for the next event for at most `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<DiscoveryEvent> {
+        self.events.recv_timeout(timeout).ok()
+    }
+
+    /// Returns the next event if one is pending.
+    pub fn try_recv(&self) -> Option<DiscoveryEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+fn sample_info_for_tests(instance_state: InstanceState, valid_data: bool) -> SampleInfo {
+    use crate::sample_info::{SampleState, ViewState};
+    use crate::time::DdsTime;
+
+    SampleInfo {
+        sample_state: SampleState::NOT_READ,
+        view_state: ViewState::NEW,
+        instance_state,
+        valid_data,
+        source_timestamp: DdsTime::default(),
+        instance_handle: 0,
+        publication_handle: 0,
+        disposed_generation_count: 0,
+        no_writers_generation_count: 0,
+        sample_rank: 0,
+        generation_rank: 0,
+        absolute_generation_rank: 0,
+    }
+}
+
+#[test]
+fn test_tracker_events() {
+    let (tx, rx) = channel();
+    let mut tracker = publication_tracker(tx);
+    let endpoint = DiscoveredEndpoint {
+        key: Guid([1; 16]),
+        participant_key: Guid([2; 16]),
+        participant_instance_handle: 3,
+        topic_name: String::from("T"),
+        type_name: String::from("M::T"),
+        qos: Qos::default(),
+    };
+    let updated = DiscoveredEndpoint {
+        qos: Qos::builder().partitions(["P1"]).build(),
+        ..endpoint.clone()
+    };
+
+    tracker.process(vec![
+        (
+            endpoint.clone(),
+            sample_info_for_tests(InstanceState::ALIVE, true),
+        ),
+        // Republishing the same data is not an update
+        (
+            endpoint.clone(),
+            sample_info_for_tests(InstanceState::ALIVE, true),
+        ),
+        (
+            updated.clone(),
+            sample_info_for_tests(InstanceState::ALIVE, true),
+        ),
+        (
+            updated.clone(),
+            sample_info_for_tests(InstanceState::NOT_ALIVE_NO_WRITERS, false),
+        ),
+        // Already lost
+        (
+            updated.clone(),
+            sample_info_for_tests(InstanceState::NOT_ALIVE_DISPOSED, false),
+        ),
+        (
+            endpoint.clone(),
+            sample_info_for_tests(InstanceState::ALIVE, true),
+        ),
+        (
+            endpoint.clone(),
+            sample_info_for_tests(InstanceState::NOT_ALIVE_DISPOSED, true),
+        ),
+    ]);
+    drop(tracker);
+
+    let key = endpoint.key;
+    assert_eq!(
+        rx.iter().collect::<Vec<_>>(),
+        vec![
+            DiscoveryEvent::PublicationDiscovered(endpoint.clone()),
+            DiscoveryEvent::PublicationUpdated(updated),
+            DiscoveryEvent::PublicationLost {
+                key,
+                reason: LossReason::NoWriters
+            },
+            DiscoveryEvent::PublicationDiscovered(endpoint),
+            DiscoveryEvent::PublicationLost {
+                key,
+                reason: LossReason::Disposed
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_monitor_local_entities() {
+    use crate::entity::{create_blob_topic_for_tests, Participant, Writer};
+
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let monitor = DiscoveryMonitor::new(&participant).unwrap();
+
+    let other = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = unsafe { create_blob_topic_for_tests(&other, "test_monitor") };
+    let writer = Writer::new(&other, &topic, None, None).unwrap();
+    let mut guid = std::mem::MaybeUninit::<dds_guid_t>::uninit();
+    unsafe {
+        assert_eq!(dds_get_guid(writer.entity(), guid.as_mut_ptr()), 0);
+    }
+    let guid = Guid::from(unsafe { guid.assume_init() });
+
+    let next = || monitor.recv_timeout(Duration::from_secs(5)).unwrap();
+    loop {
+        if let DiscoveryEvent::PublicationDiscovered(publication) = next() {
+            if publication.key == guid {
+                assert_eq!(publication.topic_name, "test_monitor");
+                break;
+            }
+        }
+    }
+
+    drop(writer);
+    loop {
+        if let DiscoveryEvent::PublicationLost { key, .. } = next() {
+            if key == guid {
+                break;
+            }
+        }
+    }
+}
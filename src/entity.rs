@@ -0,0 +1,625 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::error::check;
+use crate::listener::{Listener, ListenerState};
+use crate::qos::{NativeQos, Qos};
+use crate::sample_info::SampleInfo;
+use crate::*;
+use log::warn;
+use std::{
+    any::Any,
+    ffi::CString,
+    os::raw::c_void,
+    sync::{Arc, Mutex},
+};
+
+mod sealed {
+    use super::*;
+
+    /// Owns a Cyclone DDS entity handle and deletes it when dropped.
+    ///
+    /// Entities keep a reference on the handles they depend on (parent, topic) so that
+    /// Cyclone DDS never sees a parent deleted while one of its children is still in use.
+    #[derive(Debug)]
+    pub struct EntityHandle {
+        pub(crate) entity: dds_entity_t,
+        // Dropped after the entity is deleted, as Cyclone DDS may call the listener until then
+        pub(crate) listener: Mutex<Option<ListenerState>>,
+        // Dropped after the entity is deleted too, e.g. the argument of a topic filter
+        pub(crate) attachment: Mutex<Option<Box<dyn Any + Send>>>,
+        pub(crate) _depends_on: Vec<Arc<EntityHandle>>,
+    }
+
+    impl Drop for EntityHandle {
+        fn drop(&mut self) {
+            let ret = unsafe { dds_delete(self.entity) };
+            if ret < 0 {
+                warn!("Failed to delete DDS entity {}: {}", self.entity, ret);
+            }
+        }
+    }
+
+    pub trait Sealed {
+        fn handle(&self) -> Option<Arc<EntityHandle>>;
+    }
+}
+
+pub(crate) use sealed::{EntityHandle, Sealed};
+
+/// Common operations on the entities wrapped by this module.
+pub trait Entity: Sealed {
+    /// Returns the raw Cyclone DDS handle, for use with the functions in [`crate`].
+    fn entity(&self) -> dds_entity_t;
+
+    /// Returns the QoS currently applied to the entity.
+    fn qos(&self) -> Result<Qos> {
+        let mut qos = NativeQos::new();
+        check(unsafe { dds_get_qos(self.entity(), qos.as_mut_ptr()) })?;
+        Ok(Qos::from(&qos))
+    }
+
+    /// Replaces the listener of the entity, or removes it if `listener` is `None`.
+    fn set_listener(&self, listener: Option<Listener>) -> Result<()> {
+        let handle = self.handle().ok_or(Error::IllegalOperation)?;
+        let mut current = handle.listener.lock().unwrap();
+        let state = listener.map(ListenerState::new);
+        let ret = match &state {
+            Some(state) => unsafe {
+                state.with_native(|listener| dds_set_listener(self.entity(), listener))
+            },
+            None => unsafe { dds_set_listener(self.entity(), std::ptr::null()) },
+        };
+        check(ret)?;
+        // Cyclone DDS waits for the callbacks in progress, so the previous closures can be dropped
+        *current = state;
+        Ok(())
+    }
+
+    /// Returns the instance handle identifying the entity.
+    fn instance_handle(&self) -> Result<dds_instance_handle_t> {
+        let mut handle: dds_instance_handle_t = 0;
+        check(unsafe { dds_get_instance_handle(self.entity(), &mut handle) })?;
+        Ok(handle)
+    }
+}
+
+/// Entities that can own a [`Reader`]: a [`Participant`] or a [`Subscriber`].
+pub trait ReaderParent: Entity {}
+
+/// Entities that can own a [`Writer`]: a [`Participant`] or a [`Publisher`].
+pub trait WriterParent: Entity {}
+
+/// Something a [`Reader`] can be created on: a [`Topic`] or a [`BuiltinTopic`].
+pub trait TopicDescription: Sealed {
+    fn topic_entity(&self) -> dds_entity_t;
+}
+
+macro_rules! impl_entity {
+    ($type:ty) => {
+        impl Sealed for $type {
+            fn handle(&self) -> Option<Arc<EntityHandle>> {
+                Some(self.handle.clone())
+            }
+        }
+
+        impl Entity for $type {
+            fn entity(&self) -> dds_entity_t {
+                self.handle.entity
+            }
+        }
+    };
+}
+pub(crate) use impl_entity;
+
+pub(crate) fn new_handle(
+    entity: dds_entity_t,
+    listener: Option<ListenerState>,
+    depends_on: Vec<Arc<EntityHandle>>,
+) -> Result<Arc<EntityHandle>> {
+    Ok(Arc::new(EntityHandle {
+        entity: check(entity)?,
+        listener: Mutex::new(listener),
+        attachment: Mutex::new(None),
+        _depends_on: depends_on,
+    }))
+}
+
+fn create_entity<F>(
+    qos: Option<&Qos>,
+    listener: Option<Listener>,
+    depends_on: Vec<Arc<EntityHandle>>,
+    f: F,
+) -> Result<Arc<EntityHandle>>
+where
+    F: FnOnce(*const dds_qos_t, *const dds_listener_t) -> dds_entity_t,
+{
+    let qos = qos.map(NativeQos::from);
+    let qos = qos.as_ref().map_or(std::ptr::null(), NativeQos::as_ptr);
+    let listener = listener.map(ListenerState::new);
+    let entity = match &listener {
+        Some(state) => unsafe { state.with_native(|listener| f(qos, listener)) },
+        None => f(qos, std::ptr::null()),
+    };
+    new_handle(entity, listener, depends_on)
+}
+
+/// A DDS domain participant.
+#[derive(Debug, Clone)]
+pub struct Participant {
+    handle: Arc<EntityHandle>,
+}
+
+impl_entity!(Participant);
+impl ReaderParent for Participant {}
+impl WriterParent for Participant {}
+
+impl Participant {
+    /// Creates a participant on `domain_id` (use [`DDS_DOMAIN_DEFAULT`] for the configured domain).
+    pub fn new(
+        domain_id: dds_domainid_t,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        let handle = create_entity(qos, listener, Vec::new(), |qos, listener| unsafe {
+            dds_create_participant(domain_id, qos, listener)
+        })?;
+        Ok(Participant { handle })
+    }
+}
+
+/// A DDS topic, owned by a [`Participant`].
+#[derive(Debug, Clone)]
+pub struct Topic {
+    handle: Arc<EntityHandle>,
+}
+
+impl_entity!(Topic);
+
+impl TopicDescription for Topic {
+    fn topic_entity(&self) -> dds_entity_t {
+        self.handle.entity
+    }
+}
+
+impl Topic {
+    /// Creates a topic from a type descriptor generated by the Cyclone DDS IDL compiler.
+    ///
+    /// # Safety
+    /// `descriptor` must point to a valid topic descriptor.
+    pub unsafe fn new(
+        participant: &Participant,
+        descriptor: *const dds_topic_descriptor_t,
+        name: &str,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        let cname = CString::new(name).map_err(|_| Error::BadParameter)?;
+        let depends_on = vec![participant.handle.clone()];
+        let handle = create_entity(qos, listener, depends_on, |qos, listener| {
+            dds_create_topic(
+                participant.entity(),
+                descriptor,
+                cname.as_ptr(),
+                qos,
+                listener,
+            )
+        })?;
+        Ok(Topic { handle })
+    }
+
+    /// Creates a topic of the type implemented by `sertype`.
+    ///
+    /// # Safety
+    /// `sertype` must be a valid sertype, whose reference is consumed even on failure.
+    pub(crate) unsafe fn with_sertype(
+        participant: &Participant,
+        sertype: *mut ddsi_sertype,
+        name: &str,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        let cname = match CString::new(name) {
+            Ok(cname) => cname,
+            Err(_) => {
+                ddsi_sertype_unref(sertype);
+                return Err(Error::BadParameter);
+            }
+        };
+        let depends_on = vec![participant.handle.clone()];
+        let handle = create_entity(qos, listener, depends_on, |qos, listener| {
+            let mut sertype = sertype;
+            dds_create_topic_sertype(
+                participant.entity(),
+                cname.as_ptr(),
+                &mut sertype,
+                qos,
+                listener,
+                std::ptr::null(),
+            )
+        })?;
+        Ok(Topic { handle })
+    }
+
+    /// Takes ownership of a topic created by other means (e.g. `cdds_create_blob_topic`).
+    ///
+    /// # Safety
+    /// `topic` must be a topic created on `participant` and not owned by anything else.
+    pub unsafe fn from_raw(participant: &Participant, topic: dds_entity_t) -> Result<Self> {
+        Ok(Topic {
+            handle: new_handle(topic, None, vec![participant.handle.clone()])?,
+        })
+    }
+}
+
+/// The builtin topics Cyclone DDS uses to publish discovery information.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BuiltinTopic {
+    DcpsParticipant,
+    DcpsTopic,
+    DcpsPublication,
+    DcpsSubscription,
+}
+
+impl Sealed for BuiltinTopic {
+    fn handle(&self) -> Option<Arc<EntityHandle>> {
+        None
+    }
+}
+
+impl TopicDescription for BuiltinTopic {
+    fn topic_entity(&self) -> dds_entity_t {
+        match self {
+            BuiltinTopic::DcpsParticipant => DDS_BUILTIN_TOPIC_DCPSPARTICIPANT,
+            BuiltinTopic::DcpsTopic => DDS_BUILTIN_TOPIC_DCPSTOPIC,
+            BuiltinTopic::DcpsPublication => DDS_BUILTIN_TOPIC_DCPSPUBLICATION,
+            BuiltinTopic::DcpsSubscription => DDS_BUILTIN_TOPIC_DCPSSUBSCRIPTION,
+        }
+    }
+}
+
+/// A DDS publisher, owned by a [`Participant`].
+#[derive(Debug, Clone)]
+pub struct Publisher {
+    handle: Arc<EntityHandle>,
+}
+
+impl_entity!(Publisher);
+impl WriterParent for Publisher {}
+
+impl Publisher {
+    pub fn new(
+        participant: &Participant,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        let depends_on = vec![participant.handle.clone()];
+        let handle = create_entity(qos, listener, depends_on, |qos, listener| unsafe {
+            dds_create_publisher(participant.entity(), qos, listener)
+        })?;
+        Ok(Publisher { handle })
+    }
+}
+
+/// A DDS subscriber, owned by a [`Participant`].
+#[derive(Debug, Clone)]
+pub struct Subscriber {
+    handle: Arc<EntityHandle>,
+}
+
+impl_entity!(Subscriber);
+impl ReaderParent for Subscriber {}
+
+impl Subscriber {
+    pub fn new(
+        participant: &Participant,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        let depends_on = vec![participant.handle.clone()];
+        let handle = create_entity(qos, listener, depends_on, |qos, listener| unsafe {
+            dds_create_subscriber(participant.entity(), qos, listener)
+        })?;
+        Ok(Subscriber { handle })
+    }
+}
+
+/// A DDS data reader, owned by a [`Participant`] or a [`Subscriber`].
+#[derive(Debug, Clone)]
+pub struct Reader {
+    handle: Arc<EntityHandle>,
+}
+
+impl_entity!(Reader);
+
+impl Reader {
+    pub fn new<P, T>(
+        parent: &P,
+        topic: &T,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self>
+    where
+        P: ReaderParent,
+        T: TopicDescription,
+    {
+        let depends_on = parent.handle().into_iter().chain(topic.handle()).collect();
+        let handle = create_entity(qos, listener, depends_on, |qos, listener| unsafe {
+            dds_create_reader(parent.entity(), topic.topic_entity(), qos, listener)
+        })?;
+        Ok(Reader { handle })
+    }
+
+    /// Takes up to `max_samples` samples in their serialized (CDR) representation,
+    /// whatever the type of the topic.
+    pub fn take_cdr(&self, max_samples: usize) -> Result<Vec<CdrSample>> {
+        Ok(self
+            .take_serdata(max_samples)?
+            .into_iter()
+            .map(|(data, info)| CdrSample {
+                data: data.to_vec(),
+                info,
+            })
+            .collect())
+    }
+
+    /// Takes up to `max_samples` samples in their serialized representation, without copying
+    /// their payloads out of Cyclone DDS.
+    pub fn take_serdata(&self, max_samples: usize) -> Result<Vec<(SerdataRef, SampleInfo)>> {
+        let mut samples = vec![std::ptr::null_mut::<ddsi_serdata>(); max_samples];
+        let mut infos = vec![unsafe { std::mem::zeroed::<dds_sample_info_t>() }; max_samples];
+        let count = check(unsafe {
+            dds_takecdr(
+                self.entity(),
+                samples.as_mut_ptr(),
+                max_samples as u32,
+                infos.as_mut_ptr(),
+                DDS_ANY_STATE,
+            )
+        })?;
+        Ok(samples
+            .into_iter()
+            .zip(infos.iter())
+            .take(count as usize)
+            .map(|(serdata, info)| (unsafe { SerdataRef::new(serdata) }, SampleInfo::from(info)))
+            .collect())
+    }
+}
+
+/// A sample in its serialized representation, as returned by [`Reader::take_cdr`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CdrSample {
+    /// The CDR encapsulation header followed by the serialized sample (or only its key if
+    /// `info.valid_data` is false).
+    pub data: Vec<u8>,
+    pub info: SampleInfo,
+}
+
+/// The serialized payload of a sample, borrowed from Cyclone DDS.
+///
+/// Dereferences to the CDR encapsulation header followed by the serialized sample (or only
+/// its key for invalid samples), and keeps the sample alive until dropped.
+pub struct SerdataRef {
+    serdata: *mut ddsi_serdata,
+    serdata_ref: *mut ddsi_serdata,
+    iov: ddsrt_iovec_t,
+    size: usize,
+}
+
+// The serdata is reference counted, and its payload is immutable
+unsafe impl Send for SerdataRef {}
+unsafe impl Sync for SerdataRef {}
+
+impl SerdataRef {
+    /// # Safety
+    /// `serdata` must be a valid serdata, whose reference is transferred to the guard.
+    pub(crate) unsafe fn new(serdata: *mut ddsi_serdata) -> Self {
+        let size = ddsi_serdata_size(serdata) as usize;
+        let mut iov: ddsrt_iovec_t = std::mem::zeroed();
+        let serdata_ref = ddsi_serdata_to_ser_ref(serdata, 0, size, &mut iov);
+        SerdataRef {
+            serdata,
+            serdata_ref,
+            iov,
+            size,
+        }
+    }
+}
+
+impl std::ops::Deref for SerdataRef {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.size == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.iov.iov_base as *const u8, self.size) }
+    }
+}
+
+impl AsRef<[u8]> for SerdataRef {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl std::fmt::Debug for SerdataRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SerdataRef").field(&&**self).finish()
+    }
+}
+
+impl Drop for SerdataRef {
+    fn drop(&mut self) {
+        unsafe {
+            ddsi_serdata_to_ser_unref(self.serdata_ref, &self.iov);
+            ddsi_serdata_unref(self.serdata);
+        }
+    }
+}
+
+/// Takes all the available samples of `reader`, or reads those not read yet if `take` is
+/// false, converting each loaned sample with `f`.
+///
+/// # Safety
+/// `f` is called with pointers to samples of the reader's topic type, which are only
+/// valid during the call.
+pub(crate) unsafe fn read_loans<F, R>(reader: dds_entity_t, take: bool, mut f: F) -> Result<Vec<R>>
+where
+    F: FnMut(*const c_void, &dds_sample_info_t) -> R,
+{
+    const BATCH_SIZE: usize = 32;
+    let mut result = Vec::new();
+    loop {
+        // A null first pointer makes Cyclone DDS loan its own buffers
+        let mut samples = [std::ptr::null_mut::<c_void>(); BATCH_SIZE];
+        let mut infos: [dds_sample_info_t; BATCH_SIZE] = std::mem::zeroed();
+        let ret = if take {
+            dds_take(
+                reader,
+                samples.as_mut_ptr(),
+                infos.as_mut_ptr(),
+                BATCH_SIZE,
+                BATCH_SIZE as u32,
+            )
+        } else {
+            dds_read_mask(
+                reader,
+                samples.as_mut_ptr(),
+                infos.as_mut_ptr(),
+                BATCH_SIZE,
+                BATCH_SIZE as u32,
+                dds_sample_state_DDS_SST_NOT_READ,
+            )
+        };
+        let count = check(ret)?;
+        for i in 0..count as usize {
+            result.push(f(samples[i], &infos[i]));
+        }
+        if count > 0 {
+            check(dds_return_loan(reader, samples.as_mut_ptr(), count))?;
+        }
+        if (count as usize) < BATCH_SIZE {
+            return Ok(result);
+        }
+    }
+}
+
+/// A DDS data writer, owned by a [`Participant`] or a [`Publisher`].
+#[derive(Debug, Clone)]
+pub struct Writer {
+    handle: Arc<EntityHandle>,
+}
+
+impl_entity!(Writer);
+
+impl Writer {
+    pub fn new<P>(
+        parent: &P,
+        topic: &Topic,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self>
+    where
+        P: WriterParent,
+    {
+        let depends_on = parent.handle().into_iter().chain(topic.handle()).collect();
+        let handle = create_entity(qos, listener, depends_on, |qos, listener| unsafe {
+            dds_create_writer(parent.entity(), topic.entity(), qos, listener)
+        })?;
+        Ok(Writer { handle })
+    }
+}
+
+#[cfg(test)]
+pub(crate) unsafe fn create_blob_topic_for_tests(participant: &Participant, name: &str) -> Topic {
+    let topic_name = CString::new(name).unwrap();
+    let type_name = CString::new("cyclors::test::Blob").unwrap();
+    let topic = cdds_create_blob_topic(
+        participant.entity(),
+        topic_name.as_ptr() as *mut std::os::raw::c_char,
+        type_name.as_ptr() as *mut std::os::raw::c_char,
+        true,
+    );
+    Topic::from_raw(participant, topic).unwrap()
+}
+
+/// Writes `data`, which must start with a CDR encapsulation header, on a blob topic writer.
+#[cfg(test)]
+pub(crate) unsafe fn write_blob_for_tests(writer: &Writer, data: &[u8]) {
+    let mut sertype: *const ddsi_sertype = std::ptr::null();
+    assert_eq!(dds_get_entity_sertype(writer.entity(), &mut sertype), 0);
+    let iov = ddsrt_iovec_t {
+        iov_base: data.as_ptr() as *mut c_void,
+        iov_len: data.len() as _,
+    };
+    let serdata =
+        ddsi_serdata_from_ser_iov(sertype, ddsi_serdata_kind_SDK_DATA, 1, &iov, data.len());
+    assert_eq!(dds_writecdr(writer.entity(), serdata), 0);
+}
+
+#[test]
+fn test_entities_create_and_drop() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = unsafe { create_blob_topic_for_tests(&participant, "test_entities") };
+    let publisher = Publisher::new(&participant, None, None).unwrap();
+    let subscriber = Subscriber::new(&participant, None, None).unwrap();
+    let writer = Writer::new(&publisher, &topic, None, None).unwrap();
+    let reader = Reader::new(&subscriber, &topic, None, None).unwrap();
+
+    let reader_entity = reader.entity();
+    drop(participant);
+    drop(topic);
+    drop(publisher);
+    drop(subscriber);
+
+    // Children keep their parents alive
+    assert!(writer.instance_handle().is_ok());
+    assert!(reader.instance_handle().is_ok());
+
+    drop(reader);
+    assert!(unsafe { dds_delete(reader_entity) } < 0);
+}
+
+#[test]
+fn test_builtin_topic_reader() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let reader = Reader::new(&participant, &BuiltinTopic::DcpsPublication, None, None).unwrap();
+    assert!(reader.entity() > 0);
+}
+
+#[test]
+fn test_entity_qos() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let qos = Qos {
+        partition: Some(vec![String::from("P1")]),
+        ..Default::default()
+    };
+    let subscriber = Subscriber::new(&participant, Some(&qos), None).unwrap();
+    assert_eq!(subscriber.qos().unwrap().partition, qos.partition);
+}
+
+#[test]
+fn test_take_cdr() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = unsafe { create_blob_topic_for_tests(&participant, "test_take_cdr") };
+    let writer = Writer::new(&participant, &topic, None, None).unwrap();
+    let reader = Reader::new(&participant, &topic, None, None).unwrap();
+    assert!(reader.take_cdr(1).unwrap().is_empty());
+
+    unsafe { write_blob_for_tests(&writer, &[0, 1, 0, 0, 1, 2, 3]) };
+    let samples = reader.take_cdr(10).unwrap();
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0].data, vec![0, 1, 0, 0, 1, 2, 3]);
+    assert!(samples[0].info.valid_data);
+}
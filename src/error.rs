@@ -0,0 +1,166 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::*;
+use std::{ffi::CStr, fmt};
+
+// The extended return codes are defined in dds/ddsrt/retcode.h through the DDS_XRETCODE()
+// function-like macro, which bindgen is unable to expand.
+const DDS_XRETCODE_BASE: dds_return_t = -50;
+pub const DDS_RETCODE_IN_PROGRESS: dds_return_t = DDS_XRETCODE_BASE - 1;
+pub const DDS_RETCODE_TRY_AGAIN: dds_return_t = DDS_XRETCODE_BASE - 2;
+pub const DDS_RETCODE_INTERRUPTED: dds_return_t = DDS_XRETCODE_BASE - 3;
+pub const DDS_RETCODE_NOT_ALLOWED: dds_return_t = DDS_XRETCODE_BASE - 4;
+pub const DDS_RETCODE_HOST_NOT_FOUND: dds_return_t = DDS_XRETCODE_BASE - 5;
+pub const DDS_RETCODE_NO_NETWORK: dds_return_t = DDS_XRETCODE_BASE - 6;
+pub const DDS_RETCODE_NO_CONNECTION: dds_return_t = DDS_XRETCODE_BASE - 7;
+pub const DDS_RETCODE_NOT_ENOUGH_SPACE: dds_return_t = DDS_XRETCODE_BASE - 8;
+pub const DDS_RETCODE_OUT_OF_RANGE: dds_return_t = DDS_XRETCODE_BASE - 9;
+pub const DDS_RETCODE_NOT_FOUND: dds_return_t = DDS_XRETCODE_BASE - 10;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A failed `dds_return_t`, one variant per `DDS_RETCODE_*` error value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Error {
+    Error,
+    Unsupported,
+    BadParameter,
+    PreconditionNotMet,
+    OutOfResources,
+    NotEnabled,
+    ImmutablePolicy,
+    InconsistentPolicy,
+    AlreadyDeleted,
+    Timeout,
+    NoData,
+    IllegalOperation,
+    NotAllowedBySecurity,
+    InProgress,
+    TryAgain,
+    Interrupted,
+    NotAllowed,
+    HostNotFound,
+    NoNetwork,
+    NoConnection,
+    NotEnoughSpace,
+    OutOfRange,
+    NotFound,
+    /// A negative return code not known to this version of cyclors.
+    Other(dds_return_t),
+}
+
+impl Error {
+    /// Maps a negative `dds_return_t` to the corresponding error.
+    pub fn from_retcode(ret: dds_return_t) -> Self {
+        #[allow(non_upper_case_globals)]
+        match ret {
+            DDS_RETCODE_ERROR => Error::Error,
+            DDS_RETCODE_UNSUPPORTED => Error::Unsupported,
+            DDS_RETCODE_BAD_PARAMETER => Error::BadParameter,
+            DDS_RETCODE_PRECONDITION_NOT_MET => Error::PreconditionNotMet,
+            DDS_RETCODE_OUT_OF_RESOURCES => Error::OutOfResources,
+            DDS_RETCODE_NOT_ENABLED => Error::NotEnabled,
+            DDS_RETCODE_IMMUTABLE_POLICY => Error::ImmutablePolicy,
+            DDS_RETCODE_INCONSISTENT_POLICY => Error::InconsistentPolicy,
+            DDS_RETCODE_ALREADY_DELETED => Error::AlreadyDeleted,
+            DDS_RETCODE_TIMEOUT => Error::Timeout,
+            DDS_RETCODE_NO_DATA => Error::NoData,
+            DDS_RETCODE_ILLEGAL_OPERATION => Error::IllegalOperation,
+            DDS_RETCODE_NOT_ALLOWED_BY_SECURITY => Error::NotAllowedBySecurity,
+            DDS_RETCODE_IN_PROGRESS => Error::InProgress,
+            DDS_RETCODE_TRY_AGAIN => Error::TryAgain,
+            DDS_RETCODE_INTERRUPTED => Error::Interrupted,
+            DDS_RETCODE_NOT_ALLOWED => Error::NotAllowed,
+            DDS_RETCODE_HOST_NOT_FOUND => Error::HostNotFound,
+            DDS_RETCODE_NO_NETWORK => Error::NoNetwork,
+            DDS_RETCODE_NO_CONNECTION => Error::NoConnection,
+            DDS_RETCODE_NOT_ENOUGH_SPACE => Error::NotEnoughSpace,
+            DDS_RETCODE_OUT_OF_RANGE => Error::OutOfRange,
+            DDS_RETCODE_NOT_FOUND => Error::NotFound,
+            x => Error::Other(x),
+        }
+    }
+
+    /// Returns the `DDS_RETCODE_*` value for this error.
+    pub fn retcode(&self) -> dds_return_t {
+        match self {
+            Error::Error => DDS_RETCODE_ERROR,
+            Error::Unsupported => DDS_RETCODE_UNSUPPORTED,
+            Error::BadParameter => DDS_RETCODE_BAD_PARAMETER,
+            Error::PreconditionNotMet => DDS_RETCODE_PRECONDITION_NOT_MET,
+            Error::OutOfResources => DDS_RETCODE_OUT_OF_RESOURCES,
+            Error::NotEnabled => DDS_RETCODE_NOT_ENABLED,
+            Error::ImmutablePolicy => DDS_RETCODE_IMMUTABLE_POLICY,
+            Error::InconsistentPolicy => DDS_RETCODE_INCONSISTENT_POLICY,
+            Error::AlreadyDeleted => DDS_RETCODE_ALREADY_DELETED,
+            Error::Timeout => DDS_RETCODE_TIMEOUT,
+            Error::NoData => DDS_RETCODE_NO_DATA,
+            Error::IllegalOperation => DDS_RETCODE_ILLEGAL_OPERATION,
+            Error::NotAllowedBySecurity => DDS_RETCODE_NOT_ALLOWED_BY_SECURITY,
+            Error::InProgress => DDS_RETCODE_IN_PROGRESS,
+            Error::TryAgain => DDS_RETCODE_TRY_AGAIN,
+            Error::Interrupted => DDS_RETCODE_INTERRUPTED,
+            Error::NotAllowed => DDS_RETCODE_NOT_ALLOWED,
+            Error::HostNotFound => DDS_RETCODE_HOST_NOT_FOUND,
+            Error::NoNetwork => DDS_RETCODE_NO_NETWORK,
+            Error::NoConnection => DDS_RETCODE_NO_CONNECTION,
+            Error::NotEnoughSpace => DDS_RETCODE_NOT_ENOUGH_SPACE,
+            Error::OutOfRange => DDS_RETCODE_OUT_OF_RANGE,
+            Error::NotFound => DDS_RETCODE_NOT_FOUND,
+            Error::Other(x) => *x,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ret = self.retcode();
+        // dds_strretcode returns a static string, also for unknown codes
+        let msg = unsafe { CStr::from_ptr(dds_strretcode(ret)) };
+        write!(f, "{} ({ret})", msg.to_string_lossy())
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Turns a `dds_return_t` (or a `dds_entity_t`) into a `Result`, keeping non-negative values.
+#[inline]
+pub fn check(ret: dds_return_t) -> Result<dds_return_t> {
+    if ret < 0 {
+        Err(Error::from_retcode(ret))
+    } else {
+        Ok(ret)
+    }
+}
+
+#[test]
+fn test_error_retcode_roundtrip() {
+    for ret in -60..0 {
+        let err = Error::from_retcode(ret);
+        assert_eq!(err.retcode(), ret);
+        if let Error::Other(x) = err {
+            assert!(x < DDS_RETCODE_NOT_ALLOWED_BY_SECURITY);
+            assert!(x >= DDS_XRETCODE_BASE);
+        }
+    }
+    assert_eq!(Error::from_retcode(DDS_RETCODE_TIMEOUT), Error::Timeout);
+}
+
+#[test]
+fn test_check() {
+    assert_eq!(check(0), Ok(0));
+    assert_eq!(check(42), Ok(42));
+    assert_eq!(check(DDS_RETCODE_BAD_PARAMETER), Err(Error::BadParameter));
+    assert!(!Error::NoData.to_string().is_empty());
+}
@@ -16,6 +16,10 @@
 #![allow(non_snake_case)]
 #![allow(improper_ctypes)]
 
+// The code generated by cyclors-derive refers to the crate as `cyclors`, also in its tests
+#[cfg(test)]
+extern crate self as cyclors;
+
 pub const DDS_MIN_PSEUDO_HANDLE: dds_entity_t = 0x7fff0000 as dds_entity_t;
 
 /* @defgroup builtintopic_constants Convenience constants for referring to builtin topics
@@ -38,7 +42,21 @@ pub const DDS_CYCLONEDDS_HANDLE: dds_entity_t = (DDS_MIN_PSEUDO_HANDLE + 256) as
 
 pub const DDS_DOMAIN_DEFAULT: u32 = 0xffffffff_u32;
 
+pub mod blob;
+pub mod cdr;
+pub mod discovery;
+pub mod entity;
+pub mod error;
+pub mod listener;
 pub mod qos;
+pub mod sample_info;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod time;
+pub mod typed;
+pub mod waitset;
+
+pub use error::{Error, Result};
 
 // deactivate clippy on bindgen generated code
 #[allow(clippy::all)]
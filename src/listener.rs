@@ -0,0 +1,329 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::*;
+use log::error;
+use std::{
+    fmt,
+    os::raw::c_void,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+macro_rules! status {
+    ($(#[$meta:meta])* $name:ident from $native:ty { $($field:ident: $type:ty),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+        pub struct $name {
+            $(pub $field: $type,)*
+        }
+
+        impl From<$native> for $name {
+            fn from(status: $native) -> Self {
+                $name {
+                    $($field: status.$field,)*
+                }
+            }
+        }
+    };
+}
+
+status!(InconsistentTopicStatus from dds_inconsistent_topic_status_t {
+    total_count: u32,
+    total_count_change: i32,
+});
+
+status!(LivelinessLostStatus from dds_liveliness_lost_status_t {
+    total_count: u32,
+    total_count_change: i32,
+});
+
+status!(OfferedDeadlineMissedStatus from dds_offered_deadline_missed_status_t {
+    total_count: u32,
+    total_count_change: i32,
+    last_instance_handle: dds_instance_handle_t,
+});
+
+status!(
+    /// `last_policy_id` is the `DDS_*_QOS_POLICY_ID` of the last incompatible policy.
+    OfferedIncompatibleQosStatus from dds_offered_incompatible_qos_status_t {
+        total_count: u32,
+        total_count_change: i32,
+        last_policy_id: u32,
+    }
+);
+
+status!(SampleLostStatus from dds_sample_lost_status_t {
+    total_count: u32,
+    total_count_change: i32,
+});
+
+status!(SampleRejectedStatus from dds_sample_rejected_status_t {
+    total_count: u32,
+    total_count_change: i32,
+    last_reason: dds_sample_rejected_status_kind,
+    last_instance_handle: dds_instance_handle_t,
+});
+
+status!(LivelinessChangedStatus from dds_liveliness_changed_status_t {
+    alive_count: u32,
+    not_alive_count: u32,
+    alive_count_change: i32,
+    not_alive_count_change: i32,
+    last_publication_handle: dds_instance_handle_t,
+});
+
+status!(RequestedDeadlineMissedStatus from dds_requested_deadline_missed_status_t {
+    total_count: u32,
+    total_count_change: i32,
+    last_instance_handle: dds_instance_handle_t,
+});
+
+status!(
+    /// `last_policy_id` is the `DDS_*_QOS_POLICY_ID` of the last incompatible policy.
+    RequestedIncompatibleQosStatus from dds_requested_incompatible_qos_status_t {
+        total_count: u32,
+        total_count_change: i32,
+        last_policy_id: u32,
+    }
+);
+
+status!(PublicationMatchedStatus from dds_publication_matched_status_t {
+    total_count: u32,
+    total_count_change: i32,
+    current_count: u32,
+    current_count_change: i32,
+    last_subscription_handle: dds_instance_handle_t,
+});
+
+status!(SubscriptionMatchedStatus from dds_subscription_matched_status_t {
+    total_count: u32,
+    total_count_change: i32,
+    current_count: u32,
+    current_count_change: i32,
+    last_publication_handle: dds_instance_handle_t,
+});
+
+type DataCallback = Box<dyn Fn(dds_entity_t) + Send + Sync>;
+type StatusCallback<S> = Box<dyn Fn(dds_entity_t, S) + Send + Sync>;
+
+// Cyclone DDS aborts the process if a panic unwinds into its listener thread
+fn invoke<F: FnOnce()>(name: &str, f: F) {
+    if catch_unwind(AssertUnwindSafe(f)).is_err() {
+        error!("Panic in the {name} listener callback");
+    }
+}
+
+macro_rules! listener {
+    (
+        data { $($data:ident, $on_data:ident, $lset_data:ident;)* }
+        status { $($name:ident, $on_name:ident, $lset:ident: $status:ident from $native:ty;)* }
+    ) => {
+        /// Rust closures to call on DDS status changes, to be set on an entity at creation
+        /// or with [`entity::Entity::set_listener`].
+        ///
+        /// The closures are called from Cyclone DDS threads with the handle of the entity whose
+        /// status changed, which can be a child of the entity the listener is set on. They are
+        /// dropped once the entity is deleted or its listener is replaced.
+        #[derive(Default)]
+        pub struct Listener {
+            $($data: Option<DataCallback>,)*
+            $($name: Option<StatusCallback<$status>>,)*
+        }
+
+        impl Listener {
+            pub fn new() -> Self {
+                Listener::default()
+            }
+
+            $(
+                pub fn $on_data<F>(mut self, callback: F) -> Self
+                where
+                    F: Fn(dds_entity_t) + Send + Sync + 'static,
+                {
+                    self.$data = Some(Box::new(callback));
+                    self
+                }
+            )*
+
+            $(
+                pub fn $on_name<F>(mut self, callback: F) -> Self
+                where
+                    F: Fn(dds_entity_t, $status) + Send + Sync + 'static,
+                {
+                    self.$name = Some(Box::new(callback));
+                    self
+                }
+            )*
+
+            /// Returns a native listener calling the closures of `state`, which must outlive
+            /// the entities the native listener is set on.
+            unsafe fn to_native(state: &ListenerState) -> *mut dds_listener_t {
+                let listener = &*state.0;
+                let native = dds_create_listener(listener as *const Listener as *mut c_void);
+                $(
+                    if listener.$data.is_some() {
+                        unsafe extern "C" fn callback(entity: dds_entity_t, arg: *mut c_void) {
+                            let listener = &*(arg as *const Listener);
+                            if let Some(f) = &listener.$data {
+                                invoke(stringify!($data), || f(entity));
+                            }
+                        }
+                        $lset_data(native, Some(callback));
+                    }
+                )*
+                $(
+                    if listener.$name.is_some() {
+                        unsafe extern "C" fn callback(
+                            entity: dds_entity_t,
+                            status: $native,
+                            arg: *mut c_void,
+                        ) {
+                            let listener = &*(arg as *const Listener);
+                            if let Some(f) = &listener.$name {
+                                invoke(stringify!($name), || f(entity, status.into()));
+                            }
+                        }
+                        $lset(native, Some(callback));
+                    }
+                )*
+                native
+            }
+        }
+
+        impl fmt::Debug for Listener {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut list = f.debug_list();
+                $(
+                    if self.$data.is_some() {
+                        list.entry(&stringify!($data));
+                    }
+                )*
+                $(
+                    if self.$name.is_some() {
+                        list.entry(&stringify!($name));
+                    }
+                )*
+                list.finish()
+            }
+        }
+    };
+}
+
+listener! {
+    data {
+        data_available, on_data_available, dds_lset_data_available;
+        data_on_readers, on_data_on_readers, dds_lset_data_on_readers;
+    }
+    status {
+        inconsistent_topic, on_inconsistent_topic, dds_lset_inconsistent_topic:
+            InconsistentTopicStatus from dds_inconsistent_topic_status_t;
+        liveliness_lost, on_liveliness_lost, dds_lset_liveliness_lost:
+            LivelinessLostStatus from dds_liveliness_lost_status_t;
+        offered_deadline_missed, on_offered_deadline_missed, dds_lset_offered_deadline_missed:
+            OfferedDeadlineMissedStatus from dds_offered_deadline_missed_status_t;
+        offered_incompatible_qos, on_offered_incompatible_qos, dds_lset_offered_incompatible_qos:
+            OfferedIncompatibleQosStatus from dds_offered_incompatible_qos_status_t;
+        sample_lost, on_sample_lost, dds_lset_sample_lost:
+            SampleLostStatus from dds_sample_lost_status_t;
+        sample_rejected, on_sample_rejected, dds_lset_sample_rejected:
+            SampleRejectedStatus from dds_sample_rejected_status_t;
+        liveliness_changed, on_liveliness_changed, dds_lset_liveliness_changed:
+            LivelinessChangedStatus from dds_liveliness_changed_status_t;
+        requested_deadline_missed, on_requested_deadline_missed, dds_lset_requested_deadline_missed:
+            RequestedDeadlineMissedStatus from dds_requested_deadline_missed_status_t;
+        requested_incompatible_qos, on_requested_incompatible_qos, dds_lset_requested_incompatible_qos:
+            RequestedIncompatibleQosStatus from dds_requested_incompatible_qos_status_t;
+        publication_matched, on_publication_matched, dds_lset_publication_matched:
+            PublicationMatchedStatus from dds_publication_matched_status_t;
+        subscription_matched, on_subscription_matched, dds_lset_subscription_matched:
+            SubscriptionMatchedStatus from dds_subscription_matched_status_t;
+    }
+}
+
+/// The closures of a [`Listener`] set on an entity, kept alive as long as the entity.
+///
+/// The listener is boxed so that its address, passed as argument to the native callbacks,
+/// doesn't change.
+#[derive(Debug)]
+pub(crate) struct ListenerState(Box<Listener>);
+
+impl ListenerState {
+    pub(crate) fn new(listener: Listener) -> Self {
+        ListenerState(Box::new(listener))
+    }
+
+    /// Calls `f` with a native listener calling the closures of this state.
+    ///
+    /// # Safety
+    /// The state must outlive the entities the native listener is set on.
+    pub(crate) unsafe fn with_native<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(*const dds_listener_t) -> R,
+    {
+        // Cyclone DDS copies the native listener when it is set on an entity
+        let native = Listener::to_native(self);
+        let result = f(native);
+        dds_delete_listener(native);
+        result
+    }
+}
+
+#[test]
+fn test_listener_matched() {
+    use crate::entity::*;
+    use std::{
+        sync::mpsc::{channel, RecvTimeoutError},
+        time::Duration,
+    };
+
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = unsafe { create_blob_topic_for_tests(&participant, "test_listener") };
+
+    let (tx, rx) = channel();
+    let writer_listener = Listener::new().on_publication_matched(move |_, status| {
+        tx.send(status.current_count).unwrap();
+    });
+    let writer = Writer::new(&participant, &topic, None, Some(writer_listener)).unwrap();
+
+    let (tx, rx2) = channel();
+    let reader_listener = Listener::new().on_subscription_matched(move |entity, status| {
+        tx.send((entity, status.current_count)).unwrap();
+    });
+    let reader = Reader::new(&participant, &topic, None, Some(reader_listener)).unwrap();
+
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(1));
+    assert_eq!(
+        rx2.recv_timeout(Duration::from_secs(5)),
+        Ok((reader.entity(), 1))
+    );
+
+    // Replacing the listener drops the previous closures
+    writer.set_listener(Some(Listener::new())).unwrap();
+    drop(reader);
+    assert_eq!(
+        rx.recv_timeout(Duration::from_millis(500)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+    writer.set_listener(None).unwrap();
+}
+
+#[test]
+fn test_listener_debug() {
+    let listener = Listener::new()
+        .on_data_available(|_| ())
+        .on_sample_lost(|_, _| ());
+    assert_eq!(
+        format!("{listener:?}"),
+        "[\"data_available\", \"sample_lost\"]"
+    );
+}
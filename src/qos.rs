@@ -11,6 +11,7 @@
 // Contributors:
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
+use crate::time::DdsDuration;
 use crate::*;
 use derivative::Derivative;
 use log::warn;
@@ -18,10 +19,21 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     ffi::{CStr, CString},
+    fmt,
     mem::ManuallyDrop,
     os::raw::c_char,
+    str::Utf8Error,
 };
 
+mod builder;
+mod compatibility;
+mod diff;
+mod validation;
+pub use builder::{EntityKind, QosBuilder};
+pub use compatibility::{check_compatibility, check_type_consistency, Incompatibility};
+pub use diff::{PolicyChange, PolicyChangeKind};
+pub use validation::PolicyViolation;
+
 pub const DDS_INFINITE_TIME: i64 = 0x7FFFFFFFFFFFFFFF;
 pub const DDS_100MS_DURATION: i64 = 100 * 1_000_000;
 pub const DDS_1S_DURATION: i64 = 1_000_000_000;
@@ -59,34 +71,72 @@ pub struct Qos {
 
 #[allow(clippy::missing_safety_doc)]
 impl Qos {
-    pub unsafe fn from_qos_native(qos: *mut dds_qos_t) -> Self {
+    /// Converts a native QoS, leaving unset (and logging) any policy that cannot be converted.
+    /// Use [`Qos::try_from_qos_native`] to get the conversion errors instead.
+    pub unsafe fn from_qos_native(qos: *const dds_qos_t) -> Self {
+        let mut errors = Vec::new();
+        let result = Qos::from_qos_native_collect_errors(qos, &mut errors);
+        for error in errors {
+            warn!("Ignoring invalid QoS policy: {error}");
+        }
+        result
+    }
+
+    /// Converts a native QoS, failing with the list of policies that hold invalid values
+    /// (e.g. an unknown kind or a non UTF-8 string received from a remote participant).
+    pub unsafe fn try_from_qos_native(qos: *const dds_qos_t) -> Result<Self, QosConversionError> {
+        let mut errors = Vec::new();
+        let result = Qos::from_qos_native_collect_errors(qos, &mut errors);
+        if errors.is_empty() {
+            Ok(result)
+        } else {
+            Err(QosConversionError { errors })
+        }
+    }
+
+    unsafe fn from_qos_native_collect_errors(
+        qos: *const dds_qos_t,
+        errors: &mut Vec<PolicyConversionError>,
+    ) -> Self {
         Qos {
             user_data: user_data_from_qos_native(qos),
             topic_data: topic_data_from_qos_native(qos),
             group_data: group_data_from_qos_native(qos),
-            durability: durability_from_qos_native(qos),
-            durability_service: durability_service_from_qos_native(qos),
-            presentation: presentation_from_qos_native(qos),
+            durability: collect_policy("durability", durability_from_qos_native(qos), errors),
+            durability_service: collect_policy(
+                "durability_service",
+                durability_service_from_qos_native(qos),
+                errors,
+            ),
+            presentation: collect_policy("presentation", presentation_from_qos_native(qos), errors),
             deadline: deadline_from_qos_native(qos),
             latency_budget: latency_budget_from_qos_native(qos),
-            ownership: ownership_from_qos_native(qos),
+            ownership: collect_policy("ownership", ownership_from_qos_native(qos), errors),
             ownership_strength: ownership_strength_from_qos_native(qos),
-            liveliness: liveliness_from_qos_native(qos),
+            liveliness: collect_policy("liveliness", liveliness_from_qos_native(qos), errors),
             time_based_filter: time_based_filter_from_qos_native(qos),
-            partition: partition_from_qos_native(qos),
-            reliability: reliability_from_qos_native(qos),
+            partition: collect_policy("partition", partition_from_qos_native(qos), errors),
+            reliability: collect_policy("reliability", reliability_from_qos_native(qos), errors),
             transport_priority: transport_priority_from_qos_native(qos),
             lifespan: lifespan_from_qos_native(qos),
-            destination_order: destination_order_from_qos_native(qos),
-            history: history_from_qos_native(qos),
+            destination_order: collect_policy(
+                "destination_order",
+                destination_order_from_qos_native(qos),
+                errors,
+            ),
+            history: collect_policy("history", history_from_qos_native(qos), errors),
             resource_limits: resource_limits_from_qos_native(qos),
             writer_data_lifecycle: writer_data_lifecycle_from_qos_native(qos),
             reader_data_lifecycle: reader_data_lifecycle_from_qos_native(qos),
             writer_batching: writer_batching_from_qos_native(qos),
-            type_consistency: type_consistency_from_qos_native(qos),
-            entity_name: entity_name_from_qos_native(qos),
-            properties: properties_from_qos_native(qos),
-            ignore_local: ignore_local_from_qos_native(qos),
+            type_consistency: collect_policy(
+                "type_consistency",
+                type_consistency_from_qos_native(qos),
+                errors,
+            ),
+            entity_name: collect_policy("entity_name", entity_name_from_qos_native(qos), errors),
+            properties: collect_policy("properties", properties_from_qos_native(qos), errors),
+            ignore_local: collect_policy("ignore_local", ignore_local_from_qos_native(qos), errors),
             data_representation: data_representation_from_qos_native(qos),
         }
     }
@@ -134,13 +184,157 @@ impl Qos {
     }
 }
 
+/// An owned native QoS, deleted when dropped.
+pub struct NativeQos {
+    qos: *mut dds_qos_t,
+}
+
+// A dds_qos_t is plain data that is only read through a shared reference.
+unsafe impl Send for NativeQos {}
+unsafe impl Sync for NativeQos {}
+
+impl NativeQos {
+    /// Creates a native QoS with no policy set.
+    pub fn new() -> Self {
+        NativeQos {
+            qos: unsafe { dds_create_qos() },
+        }
+    }
+
+    /// Takes ownership of a native QoS.
+    ///
+    /// # Safety
+    /// `qos` must have been allocated by `dds_create_qos` and not be deleted by anything else.
+    pub unsafe fn from_raw(qos: *mut dds_qos_t) -> Self {
+        NativeQos { qos }
+    }
+
+    /// Releases ownership of the native QoS, which must then be deleted with `dds_delete_qos`.
+    pub fn into_raw(self) -> *mut dds_qos_t {
+        let this = ManuallyDrop::new(self);
+        this.qos
+    }
+
+    pub fn as_ptr(&self) -> *const dds_qos_t {
+        self.qos
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut dds_qos_t {
+        self.qos
+    }
+}
+
+impl Default for NativeQos {
+    fn default() -> Self {
+        NativeQos::new()
+    }
+}
+
+impl Drop for NativeQos {
+    fn drop(&mut self) {
+        unsafe { dds_delete_qos(self.qos) };
+    }
+}
+
+impl Clone for NativeQos {
+    fn clone(&self) -> Self {
+        let mut copy = NativeQos::new();
+        unsafe { dds_copy_qos(copy.as_mut_ptr(), self.as_ptr()) };
+        copy
+    }
+}
+
+impl fmt::Debug for NativeQos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NativeQos").field(&Qos::from(self)).finish()
+    }
+}
+
+impl From<&Qos> for NativeQos {
+    fn from(qos: &Qos) -> Self {
+        unsafe { NativeQos::from_raw(qos.to_qos_native()) }
+    }
+}
+
+impl From<&NativeQos> for Qos {
+    fn from(qos: &NativeQos) -> Self {
+        unsafe { Qos::from_qos_native(qos.as_ptr()) }
+    }
+}
+
 impl Default for Qos {
     fn default() -> Self {
-        unsafe {
-            let native_qos = dds_create_qos();
-            let qos = Qos::from_qos_native(native_qos);
-            dds_delete_qos(native_qos);
-            qos
+        Qos::from(&NativeQos::new())
+    }
+}
+
+/// Reason why a value read from a native QoS could not be converted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionFailure {
+    InvalidKind { kind: &'static str, value: i64 },
+    InvalidString(Utf8Error),
+}
+
+impl fmt::Display for ConversionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionFailure::InvalidKind { kind, value } => {
+                write!(f, "invalid numeric value for {kind}: {value}")
+            }
+            ConversionFailure::InvalidString(e) => write!(f, "invalid string: {e}"),
+        }
+    }
+}
+
+impl From<Utf8Error> for ConversionFailure {
+    fn from(e: Utf8Error) -> Self {
+        ConversionFailure::InvalidString(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyConversionError {
+    pub policy: &'static str,
+    pub reason: ConversionFailure,
+}
+
+impl fmt::Display for PolicyConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.policy, self.reason)
+    }
+}
+
+/// Error returned by [`Qos::try_from_qos_native`], listing every policy that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QosConversionError {
+    pub errors: Vec<PolicyConversionError>,
+}
+
+impl fmt::Display for QosConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid QoS policies: ")?;
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for QosConversionError {}
+
+fn collect_policy<T>(
+    policy: &'static str,
+    result: Result<Option<T>, ConversionFailure>,
+    errors: &mut Vec<PolicyConversionError>,
+) -> Option<T> {
+    match result {
+        Ok(value) => value,
+        Err(reason) => {
+            errors.push(PolicyConversionError { policy, reason });
+            None
         }
     }
 }
@@ -160,15 +354,22 @@ pub enum DurabilityKind {
     PERSISTENT = dds_durability_kind_DDS_DURABILITY_PERSISTENT as isize,
 }
 
-impl From<&dds_durability_kind_t> for DurabilityKind {
-    fn from(from: &dds_durability_kind_t) -> Self {
+impl TryFrom<&dds_durability_kind_t> for DurabilityKind {
+    type Error = ConversionFailure;
+
+    fn try_from(from: &dds_durability_kind_t) -> Result<Self, Self::Error> {
         #[allow(non_upper_case_globals)]
         match from {
-            &dds_durability_kind_DDS_DURABILITY_VOLATILE => DurabilityKind::VOLATILE,
-            &dds_durability_kind_DDS_DURABILITY_TRANSIENT_LOCAL => DurabilityKind::TRANSIENT_LOCAL,
-            &dds_durability_kind_DDS_DURABILITY_TRANSIENT => DurabilityKind::TRANSIENT,
-            &dds_durability_kind_DDS_DURABILITY_PERSISTENT => DurabilityKind::PERSISTENT,
-            x => panic!("Invalid numeric value for DurabilityKind: {x}"),
+            &dds_durability_kind_DDS_DURABILITY_VOLATILE => Ok(DurabilityKind::VOLATILE),
+            &dds_durability_kind_DDS_DURABILITY_TRANSIENT_LOCAL => {
+                Ok(DurabilityKind::TRANSIENT_LOCAL)
+            }
+            &dds_durability_kind_DDS_DURABILITY_TRANSIENT => Ok(DurabilityKind::TRANSIENT),
+            &dds_durability_kind_DDS_DURABILITY_PERSISTENT => Ok(DurabilityKind::PERSISTENT),
+            x => Err(ConversionFailure::InvalidKind {
+                kind: "DurabilityKind",
+                value: *x as i64,
+            }),
         }
     }
 }
@@ -176,8 +377,8 @@ impl From<&dds_durability_kind_t> for DurabilityKind {
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Derivative)]
 #[derivative(Default)]
 pub struct DurabilityService {
-    #[derivative(Default(value = "0"))]
-    pub service_cleanup_delay: dds_duration_t,
+    #[derivative(Default(value = "DdsDuration::ZERO"))]
+    pub service_cleanup_delay: DdsDuration,
     #[derivative(Default(value = "HistoryKind::KEEP_LAST"))]
     pub history_kind: HistoryKind,
     #[derivative(Default(value = "1"))]
@@ -194,7 +395,7 @@ pub struct DurabilityService {
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct Reliability {
     pub kind: ReliabilityKind,
-    pub max_blocking_time: dds_duration_t,
+    pub max_blocking_time: DdsDuration,
 }
 
 #[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -204,13 +405,18 @@ pub enum ReliabilityKind {
     RELIABLE = dds_reliability_kind_DDS_RELIABILITY_RELIABLE as isize,
 }
 
-impl From<&dds_reliability_kind_t> for ReliabilityKind {
-    fn from(from: &dds_reliability_kind_t) -> Self {
+impl TryFrom<&dds_reliability_kind_t> for ReliabilityKind {
+    type Error = ConversionFailure;
+
+    fn try_from(from: &dds_reliability_kind_t) -> Result<Self, Self::Error> {
         #[allow(non_upper_case_globals)]
         match from {
-            &dds_reliability_kind_DDS_RELIABILITY_BEST_EFFORT => ReliabilityKind::BEST_EFFORT,
-            &dds_reliability_kind_DDS_RELIABILITY_RELIABLE => ReliabilityKind::RELIABLE,
-            x => panic!("Invalid numeric value for ReliabilityKind: {x}"),
+            &dds_reliability_kind_DDS_RELIABILITY_BEST_EFFORT => Ok(ReliabilityKind::BEST_EFFORT),
+            &dds_reliability_kind_DDS_RELIABILITY_RELIABLE => Ok(ReliabilityKind::RELIABLE),
+            x => Err(ConversionFailure::InvalidKind {
+                kind: "ReliabilityKind",
+                value: *x as i64,
+            }),
         }
     }
 }
@@ -218,15 +424,15 @@ impl From<&dds_reliability_kind_t> for ReliabilityKind {
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Derivative)]
 #[derivative(Default)]
 pub struct Deadline {
-    #[derivative(Default(value = "DDS_INFINITE_TIME"))]
-    pub period: dds_duration_t,
+    #[derivative(Default(value = "DdsDuration::Infinite"))]
+    pub period: DdsDuration,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Derivative)]
 #[derivative(Default)]
 pub struct LatencyBudget {
-    #[derivative(Default(value = "0"))]
-    pub duration: dds_duration_t,
+    #[derivative(Default(value = "DdsDuration::ZERO"))]
+    pub duration: DdsDuration,
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq, Derivative)]
@@ -244,17 +450,22 @@ pub enum DestinationOrderKind {
         dds_destination_order_kind_DDS_DESTINATIONORDER_BY_SOURCE_TIMESTAMP as isize,
 }
 
-impl From<&dds_destination_order_kind_t> for DestinationOrderKind {
-    fn from(from: &dds_destination_order_kind_t) -> Self {
+impl TryFrom<&dds_destination_order_kind_t> for DestinationOrderKind {
+    type Error = ConversionFailure;
+
+    fn try_from(from: &dds_destination_order_kind_t) -> Result<Self, Self::Error> {
         #[allow(non_upper_case_globals)]
         match from {
             &dds_destination_order_kind_DDS_DESTINATIONORDER_BY_RECEPTION_TIMESTAMP => {
-                DestinationOrderKind::BY_RECEPTION_TIMESTAMP
+                Ok(DestinationOrderKind::BY_RECEPTION_TIMESTAMP)
             }
             &dds_destination_order_kind_DDS_DESTINATIONORDER_BY_SOURCE_TIMESTAMP => {
-                DestinationOrderKind::BY_SOURCE_TIMESTAMP
+                Ok(DestinationOrderKind::BY_SOURCE_TIMESTAMP)
             }
-            x => panic!("Invalid numeric value for DestinationOrderKind: {x}"),
+            x => Err(ConversionFailure::InvalidKind {
+                kind: "DestinationOrderKind",
+                value: *x as i64,
+            }),
         }
     }
 }
@@ -264,8 +475,8 @@ impl From<&dds_destination_order_kind_t> for DestinationOrderKind {
 pub struct Liveliness {
     #[derivative(Default(value = "LivelinessKind::AUTOMATIC"))]
     pub kind: LivelinessKind,
-    #[derivative(Default(value = "DDS_INFINITE_TIME"))]
-    pub lease_duration: dds_duration_t,
+    #[derivative(Default(value = "DdsDuration::Infinite"))]
+    pub lease_duration: DdsDuration,
 }
 
 #[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -276,16 +487,23 @@ pub enum LivelinessKind {
     MANUAL_BY_TOPIC = dds_liveliness_kind_DDS_LIVELINESS_MANUAL_BY_TOPIC as isize,
 }
 
-impl From<&dds_liveliness_kind_t> for LivelinessKind {
-    fn from(from: &dds_liveliness_kind_t) -> Self {
+impl TryFrom<&dds_liveliness_kind_t> for LivelinessKind {
+    type Error = ConversionFailure;
+
+    fn try_from(from: &dds_liveliness_kind_t) -> Result<Self, Self::Error> {
         #[allow(non_upper_case_globals)]
         match from {
-            &dds_liveliness_kind_DDS_LIVELINESS_AUTOMATIC => LivelinessKind::AUTOMATIC,
+            &dds_liveliness_kind_DDS_LIVELINESS_AUTOMATIC => Ok(LivelinessKind::AUTOMATIC),
             &dds_liveliness_kind_DDS_LIVELINESS_MANUAL_BY_PARTICIPANT => {
-                LivelinessKind::MANUAL_BY_PARTICIPANT
+                Ok(LivelinessKind::MANUAL_BY_PARTICIPANT)
+            }
+            &dds_liveliness_kind_DDS_LIVELINESS_MANUAL_BY_TOPIC => {
+                Ok(LivelinessKind::MANUAL_BY_TOPIC)
             }
-            &dds_liveliness_kind_DDS_LIVELINESS_MANUAL_BY_TOPIC => LivelinessKind::MANUAL_BY_TOPIC,
-            x => panic!("Invalid numeric value for LivelinessKind: {x}"),
+            x => Err(ConversionFailure::InvalidKind {
+                kind: "LivelinessKind",
+                value: *x as i64,
+            }),
         }
     }
 }
@@ -303,13 +521,18 @@ pub enum OwnershipKind {
     EXCLUSIVE = dds_ownership_kind_DDS_OWNERSHIP_EXCLUSIVE as isize,
 }
 
-impl From<&dds_ownership_kind_t> for OwnershipKind {
-    fn from(from: &dds_ownership_kind_t) -> Self {
+impl TryFrom<&dds_ownership_kind_t> for OwnershipKind {
+    type Error = ConversionFailure;
+
+    fn try_from(from: &dds_ownership_kind_t) -> Result<Self, Self::Error> {
         #[allow(non_upper_case_globals)]
         match from {
-            &dds_ownership_kind_DDS_OWNERSHIP_SHARED => OwnershipKind::SHARED,
-            &dds_ownership_kind_DDS_OWNERSHIP_EXCLUSIVE => OwnershipKind::EXCLUSIVE,
-            x => panic!("Invalid numeric value for OwnershipKind: {x}"),
+            &dds_ownership_kind_DDS_OWNERSHIP_SHARED => Ok(OwnershipKind::SHARED),
+            &dds_ownership_kind_DDS_OWNERSHIP_EXCLUSIVE => Ok(OwnershipKind::EXCLUSIVE),
+            x => Err(ConversionFailure::InvalidKind {
+                kind: "OwnershipKind",
+                value: *x as i64,
+            }),
         }
     }
 }
@@ -330,13 +553,18 @@ pub enum HistoryKind {
     KEEP_ALL = dds_history_kind_DDS_HISTORY_KEEP_ALL as isize,
 }
 
-impl From<&dds_history_kind_t> for HistoryKind {
-    fn from(from: &dds_history_kind_t) -> Self {
+impl TryFrom<&dds_history_kind_t> for HistoryKind {
+    type Error = ConversionFailure;
+
+    fn try_from(from: &dds_history_kind_t) -> Result<Self, Self::Error> {
         #[allow(non_upper_case_globals)]
         match from {
-            &dds_history_kind_DDS_HISTORY_KEEP_LAST => HistoryKind::KEEP_LAST,
-            &dds_history_kind_DDS_HISTORY_KEEP_ALL => HistoryKind::KEEP_ALL,
-            x => panic!("Invalid numeric value for HistoryKind: {x}"),
+            &dds_history_kind_DDS_HISTORY_KEEP_LAST => Ok(HistoryKind::KEEP_LAST),
+            &dds_history_kind_DDS_HISTORY_KEEP_ALL => Ok(HistoryKind::KEEP_ALL),
+            x => Err(ConversionFailure::InvalidKind {
+                kind: "HistoryKind",
+                value: *x as i64,
+            }),
         }
     }
 }
@@ -371,20 +599,25 @@ pub enum PresentationAccessScopeKind {
     GROUP = dds_presentation_access_scope_kind_DDS_PRESENTATION_GROUP as isize,
 }
 
-impl From<&dds_presentation_access_scope_kind_t> for PresentationAccessScopeKind {
-    fn from(from: &dds_presentation_access_scope_kind_t) -> Self {
+impl TryFrom<&dds_presentation_access_scope_kind_t> for PresentationAccessScopeKind {
+    type Error = ConversionFailure;
+
+    fn try_from(from: &dds_presentation_access_scope_kind_t) -> Result<Self, Self::Error> {
         #[allow(non_upper_case_globals)]
         match from {
             &dds_presentation_access_scope_kind_DDS_PRESENTATION_INSTANCE => {
-                PresentationAccessScopeKind::INSTANCE
+                Ok(PresentationAccessScopeKind::INSTANCE)
             }
             &dds_presentation_access_scope_kind_DDS_PRESENTATION_TOPIC => {
-                PresentationAccessScopeKind::TOPIC
+                Ok(PresentationAccessScopeKind::TOPIC)
             }
             &dds_presentation_access_scope_kind_DDS_PRESENTATION_GROUP => {
-                PresentationAccessScopeKind::GROUP
+                Ok(PresentationAccessScopeKind::GROUP)
             }
-            x => panic!("Invalid numeric value for PresentationAccessScopeKind: {x}"),
+            x => Err(ConversionFailure::InvalidKind {
+                kind: "PresentationAccessScopeKind",
+                value: *x as i64,
+            }),
         }
     }
 }
@@ -392,8 +625,8 @@ impl From<&dds_presentation_access_scope_kind_t> for PresentationAccessScopeKind
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Derivative)]
 #[derivative(Default)]
 pub struct Lifespan {
-    #[derivative(Default(value = "DDS_INFINITE_TIME"))]
-    pub duration: dds_duration_t,
+    #[derivative(Default(value = "DdsDuration::Infinite"))]
+    pub duration: DdsDuration,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Derivative)]
@@ -406,8 +639,8 @@ pub struct OwnershipStrength {
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Derivative)]
 #[derivative(Default)]
 pub struct TimeBasedFilter {
-    #[derivative(Default(value = "0"))]
-    pub minimum_separation: dds_duration_t,
+    #[derivative(Default(value = "DdsDuration::ZERO"))]
+    pub minimum_separation: DdsDuration,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Derivative)]
@@ -427,10 +660,10 @@ pub struct WriterDataLifecycle {
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Derivative)]
 #[derivative(Default)]
 pub struct ReaderDataLifecycle {
-    #[derivative(Default(value = "DDS_INFINITE_TIME"))]
-    pub autopurge_nowriter_samples_delay: dds_duration_t,
-    #[derivative(Default(value = "DDS_INFINITE_TIME"))]
-    pub autopurge_disposed_samples_delay: dds_duration_t,
+    #[derivative(Default(value = "DdsDuration::Infinite"))]
+    pub autopurge_nowriter_samples_delay: DdsDuration,
+    #[derivative(Default(value = "DdsDuration::Infinite"))]
+    pub autopurge_disposed_samples_delay: DdsDuration,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Derivative)]
@@ -454,14 +687,19 @@ pub enum IgnoreLocalKind {
     PROCESS = dds_ignorelocal_kind_DDS_IGNORELOCAL_PROCESS as isize,
 }
 
-impl From<&dds_ignorelocal_kind_t> for IgnoreLocalKind {
-    fn from(from: &dds_ignorelocal_kind_t) -> Self {
+impl TryFrom<&dds_ignorelocal_kind_t> for IgnoreLocalKind {
+    type Error = ConversionFailure;
+
+    fn try_from(from: &dds_ignorelocal_kind_t) -> Result<Self, Self::Error> {
         #[allow(non_upper_case_globals)]
         match from {
-            &dds_ignorelocal_kind_DDS_IGNORELOCAL_NONE => IgnoreLocalKind::NONE,
-            &dds_ignorelocal_kind_DDS_IGNORELOCAL_PARTICIPANT => IgnoreLocalKind::PARTICIPANT,
-            &dds_ignorelocal_kind_DDS_IGNORELOCAL_PROCESS => IgnoreLocalKind::PROCESS,
-            x => panic!("Invalid numeric value for IgnoreLocalKind: {x}"),
+            &dds_ignorelocal_kind_DDS_IGNORELOCAL_NONE => Ok(IgnoreLocalKind::NONE),
+            &dds_ignorelocal_kind_DDS_IGNORELOCAL_PARTICIPANT => Ok(IgnoreLocalKind::PARTICIPANT),
+            &dds_ignorelocal_kind_DDS_IGNORELOCAL_PROCESS => Ok(IgnoreLocalKind::PROCESS),
+            x => Err(ConversionFailure::InvalidKind {
+                kind: "IgnoreLocalKind",
+                value: *x as i64,
+            }),
         }
     }
 }
@@ -492,17 +730,22 @@ pub enum TypeConsistencyKind {
         dds_type_consistency_kind_DDS_TYPE_CONSISTENCY_ALLOW_TYPE_COERCION as isize,
 }
 
-impl From<&dds_type_consistency_kind_t> for TypeConsistencyKind {
-    fn from(from: &dds_type_consistency_kind_t) -> Self {
+impl TryFrom<&dds_type_consistency_kind_t> for TypeConsistencyKind {
+    type Error = ConversionFailure;
+
+    fn try_from(from: &dds_type_consistency_kind_t) -> Result<Self, Self::Error> {
         #[allow(non_upper_case_globals)]
         match from {
             &dds_type_consistency_kind_DDS_TYPE_CONSISTENCY_DISALLOW_TYPE_COERCION => {
-                TypeConsistencyKind::DISALLOW_TYPE_COERCION
+                Ok(TypeConsistencyKind::DISALLOW_TYPE_COERCION)
             }
             &dds_type_consistency_kind_DDS_TYPE_CONSISTENCY_ALLOW_TYPE_COERCION => {
-                TypeConsistencyKind::ALLOW_TYPE_COERCION
+                Ok(TypeConsistencyKind::ALLOW_TYPE_COERCION)
             }
-            x => panic!("Invalid numeric value for TypeConsistencyKind: {x}"),
+            x => Err(ConversionFailure::InvalidKind {
+                kind: "TypeConsistencyKind",
+                value: *x as i64,
+            }),
         }
     }
 }
@@ -585,14 +828,16 @@ unsafe fn group_data_to_qos_native(qos: *mut dds_qos_t, group_data: &Option<Vec<
     }
 }
 
-unsafe fn durability_from_qos_native(qos: *const dds_qos_t) -> Option<Durability> {
+unsafe fn durability_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<Durability>, ConversionFailure> {
     let mut dur_kind: dds_durability_kind_t = dds_durability_kind_DDS_DURABILITY_VOLATILE;
     if dds_qget_durability(qos, &mut dur_kind) {
-        to_option(Durability {
-            kind: DurabilityKind::from(&dur_kind),
-        })
+        Ok(to_option(Durability {
+            kind: DurabilityKind::try_from(&dur_kind)?,
+        }))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -602,16 +847,18 @@ unsafe fn durability_to_qos_native(qos: *mut dds_qos_t, durability: &Option<Dura
     }
 }
 
-unsafe fn history_from_qos_native(qos: *const dds_qos_t) -> Option<History> {
+unsafe fn history_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<History>, ConversionFailure> {
     let mut hist_kind: dds_history_kind_t = dds_history_kind_DDS_HISTORY_KEEP_LAST;
     let mut depth: i32 = 1;
     if dds_qget_history(qos, &mut hist_kind, &mut depth) {
-        to_option(History {
-            kind: HistoryKind::from(&hist_kind),
+        Ok(to_option(History {
+            kind: HistoryKind::try_from(&hist_kind)?,
             depth,
-        })
+        }))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -655,7 +902,9 @@ unsafe fn resource_limits_to_qos_native(
     }
 }
 
-unsafe fn presentation_from_qos_native(qos: *const dds_qos_t) -> Option<Presentation> {
+unsafe fn presentation_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<Presentation>, ConversionFailure> {
     let mut pres_access_scope: dds_presentation_access_scope_kind_t =
         dds_presentation_access_scope_kind_DDS_PRESENTATION_INSTANCE;
     let mut coherent_access: bool = false;
@@ -666,13 +915,13 @@ unsafe fn presentation_from_qos_native(qos: *const dds_qos_t) -> Option<Presenta
         &mut coherent_access,
         &mut ordered_access,
     ) {
-        to_option(Presentation {
-            access_scope: PresentationAccessScopeKind::from(&pres_access_scope),
+        Ok(to_option(Presentation {
+            access_scope: PresentationAccessScopeKind::try_from(&pres_access_scope)?,
             coherent_access,
             ordered_access,
-        })
+        }))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -690,7 +939,9 @@ unsafe fn presentation_to_qos_native(qos: *mut dds_qos_t, presentation: &Option<
 unsafe fn lifespan_from_qos_native(qos: *const dds_qos_t) -> Option<Lifespan> {
     let mut duration: dds_duration_t = DDS_INFINITE_TIME;
     if dds_qget_lifespan(qos, &mut duration) {
-        to_option(Lifespan { duration })
+        to_option(Lifespan {
+            duration: duration.into(),
+        })
     } else {
         None
     }
@@ -698,14 +949,16 @@ unsafe fn lifespan_from_qos_native(qos: *const dds_qos_t) -> Option<Lifespan> {
 
 unsafe fn lifespan_to_qos_native(qos: *mut dds_qos_t, lifespan: &Option<Lifespan>) {
     if let Some(lifespan) = lifespan {
-        dds_qset_lifespan(qos, lifespan.duration);
+        dds_qset_lifespan(qos, lifespan.duration.as_nanos());
     }
 }
 
 unsafe fn deadline_from_qos_native(qos: *const dds_qos_t) -> Option<Deadline> {
     let mut period: dds_duration_t = DDS_INFINITE_TIME;
     if dds_qget_deadline(qos, &mut period) {
-        to_option(Deadline { period })
+        to_option(Deadline {
+            period: period.into(),
+        })
     } else {
         None
     }
@@ -713,14 +966,16 @@ unsafe fn deadline_from_qos_native(qos: *const dds_qos_t) -> Option<Deadline> {
 
 unsafe fn deadline_to_qos_native(qos: *mut dds_qos_t, deadline: &Option<Deadline>) {
     if let Some(deadline) = deadline {
-        dds_qset_deadline(qos, deadline.period);
+        dds_qset_deadline(qos, deadline.period.as_nanos());
     }
 }
 
 unsafe fn latency_budget_from_qos_native(qos: *const dds_qos_t) -> Option<LatencyBudget> {
     let mut duration: dds_duration_t = 0;
     if dds_qget_latency_budget(qos, &mut duration) {
-        to_option(LatencyBudget { duration })
+        to_option(LatencyBudget {
+            duration: duration.into(),
+        })
     } else {
         None
     }
@@ -731,18 +986,20 @@ unsafe fn latency_budget_to_qos_native(
     latency_budget: &Option<LatencyBudget>,
 ) {
     if let Some(latency_budget) = latency_budget {
-        dds_qset_latency_budget(qos, latency_budget.duration);
+        dds_qset_latency_budget(qos, latency_budget.duration.as_nanos());
     }
 }
 
-unsafe fn ownership_from_qos_native(qos: *const dds_qos_t) -> Option<Ownership> {
+unsafe fn ownership_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<Ownership>, ConversionFailure> {
     let mut own_kind: dds_ownership_kind_t = dds_ownership_kind_DDS_OWNERSHIP_SHARED;
     if dds_qget_ownership(qos, &mut own_kind) {
-        to_option(Ownership {
-            kind: OwnershipKind::from(&own_kind),
-        })
+        Ok(to_option(Ownership {
+            kind: OwnershipKind::try_from(&own_kind)?,
+        }))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -770,16 +1027,18 @@ unsafe fn ownership_strength_to_qos_native(
     }
 }
 
-unsafe fn liveliness_from_qos_native(qos: *const dds_qos_t) -> Option<Liveliness> {
+unsafe fn liveliness_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<Liveliness>, ConversionFailure> {
     let mut live_kind: dds_liveliness_kind_t = dds_liveliness_kind_DDS_LIVELINESS_AUTOMATIC;
     let mut lease_duration: dds_duration_t = DDS_INFINITE_TIME;
     if dds_qget_liveliness(qos, &mut live_kind, &mut lease_duration) {
-        to_option(Liveliness {
-            kind: LivelinessKind::from(&live_kind),
-            lease_duration,
-        })
+        Ok(to_option(Liveliness {
+            kind: LivelinessKind::try_from(&live_kind)?,
+            lease_duration: lease_duration.into(),
+        }))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -788,7 +1047,7 @@ unsafe fn liveliness_to_qos_native(qos: *mut dds_qos_t, liveliness: &Option<Live
         dds_qset_liveliness(
             qos,
             liveliness.kind as dds_liveliness_kind_t,
-            liveliness.lease_duration,
+            liveliness.lease_duration.as_nanos(),
         );
     }
 }
@@ -796,7 +1055,9 @@ unsafe fn liveliness_to_qos_native(qos: *mut dds_qos_t, liveliness: &Option<Live
 unsafe fn time_based_filter_from_qos_native(qos: *const dds_qos_t) -> Option<TimeBasedFilter> {
     let mut minimum_separation: dds_duration_t = 0;
     if dds_qget_time_based_filter(qos, &mut minimum_separation) {
-        to_option(TimeBasedFilter { minimum_separation })
+        to_option(TimeBasedFilter {
+            minimum_separation: minimum_separation.into(),
+        })
     } else {
         None
     }
@@ -807,30 +1068,41 @@ unsafe fn time_based_filter_to_qos_native(
     time_based_filter: &Option<TimeBasedFilter>,
 ) {
     if let Some(time_based_filter) = time_based_filter {
-        dds_qset_time_based_filter(qos, time_based_filter.minimum_separation);
+        dds_qset_time_based_filter(qos, time_based_filter.minimum_separation.as_nanos());
     }
 }
 
-unsafe fn partition_from_qos_native(qos: *const dds_qos_t) -> Option<Vec<String>> {
+unsafe fn partition_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<Vec<String>>, ConversionFailure> {
     let mut n: u32 = 0;
     let mut ps: *mut *mut ::std::os::raw::c_char = std::ptr::null_mut();
 
     if dds_qget_partition(qos, &mut n, &mut ps) {
         let mut partitions: Vec<String> = Vec::with_capacity(n as usize);
+        let mut error = None;
         for k in 0..n {
             let p_offset = *(ps.offset(k as isize));
 
-            let p = CStr::from_ptr(p_offset).to_str().unwrap();
-            partitions.push(String::from(p));
+            // Don't return early on error so that all the strings still get freed
+            match CStr::from_ptr(p_offset).to_str() {
+                Ok(p) => partitions.push(String::from(p)),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
 
             // Cyclone DDS returns a copy of the string so need to free the memory
             dds_free(p_offset as *mut ::std::os::raw::c_void);
         }
         // Cyclone DDS returns a copy of the pointer array so need to free the memory
         dds_free(ps as *mut ::std::os::raw::c_void);
-        to_option(partitions)
+        match error {
+            Some(e) => Err(e.into()),
+            None => Ok(to_option(partitions)),
+        }
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -851,16 +1123,18 @@ unsafe fn partition_to_qos_native(qos: *mut dds_qos_t, partitions: &Option<Vec<S
     }
 }
 
-unsafe fn reliability_from_qos_native(qos: *const dds_qos_t) -> Option<Reliability> {
+unsafe fn reliability_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<Reliability>, ConversionFailure> {
     let mut rel_kind: dds_reliability_kind_t = dds_reliability_kind_DDS_RELIABILITY_BEST_EFFORT;
     let mut max_blocking_time: dds_duration_t = DDS_100MS_DURATION;
     if dds_qget_reliability(qos, &mut rel_kind, &mut max_blocking_time) {
-        Some(Reliability {
-            kind: ReliabilityKind::from(&rel_kind),
-            max_blocking_time,
-        })
+        Ok(Some(Reliability {
+            kind: ReliabilityKind::try_from(&rel_kind)?,
+            max_blocking_time: max_blocking_time.into(),
+        }))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -869,7 +1143,7 @@ unsafe fn reliability_to_qos_native(qos: *mut dds_qos_t, reliability: &Option<Re
         dds_qset_reliability(
             qos,
             reliability.kind as dds_reliability_kind_t,
-            reliability.max_blocking_time,
+            reliability.max_blocking_time.as_nanos(),
         );
     }
 }
@@ -892,15 +1166,17 @@ unsafe fn transport_priority_to_qos_native(
     }
 }
 
-unsafe fn destination_order_from_qos_native(qos: *const dds_qos_t) -> Option<DestinationOrder> {
+unsafe fn destination_order_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<DestinationOrder>, ConversionFailure> {
     let mut dest_kind: dds_destination_order_kind_t =
         dds_destination_order_kind_DDS_DESTINATIONORDER_BY_RECEPTION_TIMESTAMP;
     if dds_qget_destination_order(qos, &mut dest_kind) {
-        to_option(DestinationOrder {
-            kind: DestinationOrderKind::from(&dest_kind),
-        })
+        Ok(to_option(DestinationOrder {
+            kind: DestinationOrderKind::try_from(&dest_kind)?,
+        }))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -949,8 +1225,8 @@ unsafe fn reader_data_lifecycle_from_qos_native(
         &mut autopurge_disposed_samples_delay,
     ) {
         to_option(ReaderDataLifecycle {
-            autopurge_nowriter_samples_delay,
-            autopurge_disposed_samples_delay,
+            autopurge_nowriter_samples_delay: autopurge_nowriter_samples_delay.into(),
+            autopurge_disposed_samples_delay: autopurge_disposed_samples_delay.into(),
         })
     } else {
         None
@@ -964,8 +1240,12 @@ unsafe fn reader_data_lifecycle_to_qos_native(
     if let Some(reader_data_lifecycle) = reader_data_lifecycle {
         dds_qset_reader_data_lifecycle(
             qos,
-            reader_data_lifecycle.autopurge_nowriter_samples_delay,
-            reader_data_lifecycle.autopurge_disposed_samples_delay,
+            reader_data_lifecycle
+                .autopurge_nowriter_samples_delay
+                .as_nanos(),
+            reader_data_lifecycle
+                .autopurge_disposed_samples_delay
+                .as_nanos(),
         );
     }
 }
@@ -988,7 +1268,9 @@ unsafe fn writer_batching_to_qos_native(
     }
 }
 
-unsafe fn durability_service_from_qos_native(qos: *const dds_qos_t) -> Option<DurabilityService> {
+unsafe fn durability_service_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<DurabilityService>, ConversionFailure> {
     let mut service_cleanup_delay: dds_duration_t = 0;
     let mut durability_history_kind: dds_history_kind_t = dds_history_kind_DDS_HISTORY_KEEP_LAST;
     let mut history_depth: i32 = 1;
@@ -1005,16 +1287,16 @@ unsafe fn durability_service_from_qos_native(qos: *const dds_qos_t) -> Option<Du
         &mut max_instances,
         &mut max_samples_per_instance,
     ) {
-        to_option(DurabilityService {
-            service_cleanup_delay,
-            history_kind: HistoryKind::from(&durability_history_kind),
+        Ok(to_option(DurabilityService {
+            service_cleanup_delay: service_cleanup_delay.into(),
+            history_kind: HistoryKind::try_from(&durability_history_kind)?,
             history_depth,
             max_samples,
             max_instances,
             max_samples_per_instance,
-        })
+        }))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -1025,7 +1307,7 @@ unsafe fn durability_service_to_qos_native(
     if let Some(durability_service) = durability_service {
         dds_qset_durability_service(
             qos,
-            durability_service.service_cleanup_delay,
+            durability_service.service_cleanup_delay.as_nanos(),
             durability_service.history_kind as dds_history_kind_t,
             durability_service.history_depth,
             durability_service.max_samples,
@@ -1035,14 +1317,16 @@ unsafe fn durability_service_to_qos_native(
     }
 }
 
-unsafe fn ignore_local_from_qos_native(qos: *const dds_qos_t) -> Option<IgnoreLocal> {
+unsafe fn ignore_local_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<IgnoreLocal>, ConversionFailure> {
     let mut ignore_kind: dds_ignorelocal_kind_t = dds_ignorelocal_kind_DDS_IGNORELOCAL_NONE;
     if dds_qget_ignorelocal(qos, &mut ignore_kind) {
-        to_option(IgnoreLocal {
-            kind: IgnoreLocalKind::from(&ignore_kind),
-        })
+        Ok(to_option(IgnoreLocal {
+            kind: IgnoreLocalKind::try_from(&ignore_kind)?,
+        }))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -1052,49 +1336,68 @@ unsafe fn ignore_local_to_qos_native(qos: *mut dds_qos_t, ignore_local: &Option<
     }
 }
 
-unsafe fn property_from_qos_native(qos: *const dds_qos_t, name: &str) -> Option<String> {
+unsafe fn property_from_qos_native(
+    qos: *const dds_qos_t,
+    name: &str,
+) -> Result<Option<String>, ConversionFailure> {
     let mut pvalue: *mut ::std::os::raw::c_char = std::ptr::null_mut();
     let cname = CString::new(name).unwrap();
 
     if dds_qget_prop(qos, cname.as_ptr(), &mut pvalue) {
-        let value = CStr::from_ptr(pvalue).to_str().unwrap();
-        let policy = to_option(String::from(value));
+        let policy = CStr::from_ptr(pvalue)
+            .to_str()
+            .map(|value| to_option(String::from(value)));
 
         // Cyclone DDS returns a copy of the string so need to free the memory
         dds_free(pvalue as *mut ::std::os::raw::c_void);
-        policy
+        Ok(policy?)
     } else {
-        None
+        Ok(None)
     }
 }
 
-unsafe fn properties_from_qos_native(qos: *const dds_qos_t) -> Option<HashMap<String, String>> {
+unsafe fn properties_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<HashMap<String, String>>, ConversionFailure> {
     let mut n: u32 = 0;
     let mut ps: *mut *mut ::std::os::raw::c_char = std::ptr::null_mut();
 
     if dds_qget_propnames(qos, &mut n, &mut ps) {
         let mut map: HashMap<String, String> = HashMap::new();
+        let mut error = None;
         for k in 0..n {
             let p_offset = *(ps.offset(k as isize));
 
-            let p = CStr::from_ptr(p_offset).to_str().unwrap();
-            let name = String::from(p);
+            let name = CStr::from_ptr(p_offset).to_str().map(String::from);
 
             // Cyclone DDS returns a copy of the string so need to free the memory
             dds_free(p_offset as *mut ::std::os::raw::c_void);
 
-            if let Some(value) = property_from_qos_native(qos, &name) {
-                map.insert(name, value);
-            } else {
-                warn!("Error retrieving QoS property: name={name}");
-                continue;
+            // Don't return early on error so that all the strings still get freed
+            let value = name
+                .map_err(ConversionFailure::from)
+                .and_then(|name| Ok((property_from_qos_native(qos, &name)?, name)));
+            match value {
+                Ok((Some(value), name)) => {
+                    map.insert(name, value);
+                }
+                Ok((None, name)) => {
+                    warn!("Error retrieving QoS property: name={name}");
+                    continue;
+                }
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
             }
         }
         // Cyclone DDS returns a copy of the pointer array so need to free the memory
         dds_free(ps as *mut ::std::os::raw::c_void);
-        to_option(map)
+        match error {
+            Some(e) => Err(e),
+            None => Ok(to_option(map)),
+        }
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -1112,7 +1415,9 @@ unsafe fn properties_to_qos_native(
     }
 }
 
-unsafe fn type_consistency_from_qos_native(qos: *const dds_qos_t) -> Option<TypeConsistency> {
+unsafe fn type_consistency_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<TypeConsistency>, ConversionFailure> {
     let mut type_kind: dds_type_consistency_kind_t =
         dds_type_consistency_kind_DDS_TYPE_CONSISTENCY_DISALLOW_TYPE_COERCION;
     let mut ignore_sequence_bounds: bool = false;
@@ -1129,16 +1434,16 @@ unsafe fn type_consistency_from_qos_native(qos: *const dds_qos_t) -> Option<Type
         &mut prevent_type_widening,
         &mut force_type_validation,
     ) {
-        to_option(TypeConsistency {
-            kind: TypeConsistencyKind::from(&type_kind),
+        Ok(to_option(TypeConsistency {
+            kind: TypeConsistencyKind::try_from(&type_kind)?,
             ignore_sequence_bounds,
             ignore_string_bounds,
             ignore_member_names,
             prevent_type_widening,
             force_type_validation,
-        })
+        }))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -1159,20 +1464,23 @@ unsafe fn type_consistency_to_qos_native(
     }
 }
 
-unsafe fn entity_name_from_qos_native(qos: *const dds_qos_t) -> Option<EntityName> {
+unsafe fn entity_name_from_qos_native(
+    qos: *const dds_qos_t,
+) -> Result<Option<EntityName>, ConversionFailure> {
     let mut ps: *mut ::std::os::raw::c_char = std::ptr::null_mut();
 
     if dds_qget_entity_name(qos, &mut ps) {
-        let p = CStr::from_ptr(ps).to_str().unwrap();
-        let policy = to_option(EntityName {
-            name: String::from(p),
+        let policy = CStr::from_ptr(ps).to_str().map(|p| {
+            to_option(EntityName {
+                name: String::from(p),
+            })
         });
 
         // Cyclone DDS returns a copy of the string so need to free the memory
         dds_free(ps as *mut ::std::os::raw::c_void);
-        policy
+        Ok(policy?)
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -1514,7 +1822,7 @@ fn test_durability_from_native() {
 
             dds_qset_durability(qos_native, kind.0);
 
-            let policy = durability_from_qos_native(qos_native);
+            let policy = durability_from_qos_native(qos_native).unwrap();
             if is_default_value(&kind.1) {
                 assert!(policy.is_none())
             } else {
@@ -1542,7 +1850,7 @@ fn test_durability_service_to_native() {
             let qos_native = dds_create_qos();
 
             let policy = DurabilityService {
-                service_cleanup_delay: 100,
+                service_cleanup_delay: DdsDuration::from_nanos(100),
                 history_kind: kind.0,
                 history_depth: 100,
                 max_samples: 100,
@@ -1570,7 +1878,10 @@ fn test_durability_service_to_native() {
                 &mut max_samples_per_instance,
             ));
 
-            assert_eq!(service_cleanup_delay, policy.service_cleanup_delay);
+            assert_eq!(
+                service_cleanup_delay,
+                policy.service_cleanup_delay.as_nanos()
+            );
             assert_eq!(durability_history_kind, kind.1);
             assert_eq!(history_depth, policy.history_depth);
             assert_eq!(max_samples, policy.max_samples);
@@ -1611,11 +1922,14 @@ fn test_durability_service_from_native() {
                 max_samples_per_instance,
             );
 
-            let policy = durability_service_from_qos_native(qos_native);
+            let policy = durability_service_from_qos_native(qos_native).unwrap();
 
             assert!(policy.is_some());
             let policy = policy.unwrap();
-            assert_eq!(policy.service_cleanup_delay, service_cleanup_delay);
+            assert_eq!(
+                policy.service_cleanup_delay,
+                DdsDuration::from_nanos(service_cleanup_delay)
+            );
             assert_eq!(policy.history_kind, kind.1);
             assert_eq!(policy.max_samples, max_samples);
             assert_eq!(policy.max_instances, max_instances);
@@ -1699,7 +2013,7 @@ fn test_presentation_from_native() {
             let ordered_access = true;
             dds_qset_presentation(qos_native, kind.0, coherent_access, ordered_access);
 
-            let policy = presentation_from_qos_native(qos_native);
+            let policy = presentation_from_qos_native(qos_native).unwrap();
             assert!(policy.is_some());
             let policy = policy.unwrap();
             assert_eq!(policy.access_scope, kind.1);
@@ -1716,13 +2030,15 @@ fn test_deadline_to_native() {
     unsafe {
         let qos_native = dds_create_qos();
 
-        let policy = Deadline { period: 1000 };
+        let policy = Deadline {
+            period: DdsDuration::from_nanos(1000),
+        };
         let input = Some(policy.clone());
         deadline_to_qos_native(qos_native, &input.clone());
 
         let mut period: i64 = 0;
         assert!(dds_qget_deadline(qos_native, &mut period,));
-        assert_eq!(period, policy.period);
+        assert_eq!(period, policy.period.as_nanos());
 
         dds_delete_qos(qos_native);
     }
@@ -1739,7 +2055,7 @@ fn test_deadline_from_native() {
         let policy = deadline_from_qos_native(qos_native);
         assert!(policy.is_some());
         let policy = policy.unwrap();
-        assert_eq!(policy.period, period);
+        assert_eq!(policy.period, DdsDuration::from_nanos(period));
 
         dds_delete_qos(qos_native);
     }
@@ -1750,13 +2066,15 @@ fn test_latency_budget_to_native() {
     unsafe {
         let qos_native = dds_create_qos();
 
-        let policy = LatencyBudget { duration: 1000 };
+        let policy = LatencyBudget {
+            duration: DdsDuration::from_nanos(1000),
+        };
         let input = Some(policy.clone());
         latency_budget_to_qos_native(qos_native, &input.clone());
 
         let mut duration: dds_duration_t = 0;
         assert!(dds_qget_latency_budget(qos_native, &mut duration,));
-        assert_eq!(duration, policy.duration);
+        assert_eq!(duration, policy.duration.as_nanos());
 
         dds_delete_qos(qos_native);
     }
@@ -1772,7 +2090,7 @@ fn test_latency_budget_from_native() {
         let policy = latency_budget_from_qos_native(qos_native);
         assert!(policy.is_some());
         let policy = policy.unwrap();
-        assert_eq!(policy.duration, duration);
+        assert_eq!(policy.duration, DdsDuration::from_nanos(duration));
 
         dds_delete_qos(qos_native);
     }
@@ -1826,7 +2144,7 @@ fn test_ownership_from_native() {
 
             dds_qset_ownership(qos_native, kind.0);
 
-            let policy = ownership_from_qos_native(qos_native);
+            let policy = ownership_from_qos_native(qos_native).unwrap();
             if is_default_value(&kind.1) {
                 assert!(policy.is_none())
             } else {
@@ -1896,7 +2214,7 @@ fn test_liveliness_to_native() {
 
             let policy = Liveliness {
                 kind: kind.0,
-                lease_duration: 1000,
+                lease_duration: DdsDuration::from_nanos(1000),
             };
             let input = Some(policy.clone());
             liveliness_to_qos_native(qos_native, &input.clone());
@@ -1909,7 +2227,7 @@ fn test_liveliness_to_native() {
                 &mut lease_duration,
             ));
             assert_eq!(own_kind, kind.1);
-            assert_eq!(lease_duration, policy.lease_duration);
+            assert_eq!(lease_duration, policy.lease_duration.as_nanos());
 
             dds_delete_qos(qos_native);
         }
@@ -1940,11 +2258,14 @@ fn test_liveliness_from_native() {
             let lease_duration: i64 = 1000;
             dds_qset_liveliness(qos_native, kind.0, lease_duration);
 
-            let policy = liveliness_from_qos_native(qos_native);
+            let policy = liveliness_from_qos_native(qos_native).unwrap();
             assert!(policy.is_some());
             let policy = policy.unwrap();
             assert_eq!(policy.kind, kind.1);
-            assert_eq!(policy.lease_duration, lease_duration);
+            assert_eq!(
+                policy.lease_duration,
+                DdsDuration::from_nanos(lease_duration)
+            );
 
             dds_delete_qos(qos_native);
         }
@@ -1957,7 +2278,7 @@ fn test_time_based_filter_to_native() {
         let qos_native = dds_create_qos();
 
         let policy = TimeBasedFilter {
-            minimum_separation: 1000,
+            minimum_separation: DdsDuration::from_nanos(1000),
         };
         let input = Some(policy.clone());
         time_based_filter_to_qos_native(qos_native, &input.clone());
@@ -1967,7 +2288,7 @@ fn test_time_based_filter_to_native() {
             qos_native,
             &mut minimum_separation,
         ));
-        assert_eq!(minimum_separation, policy.minimum_separation);
+        assert_eq!(minimum_separation, policy.minimum_separation.as_nanos());
 
         dds_delete_qos(qos_native);
     }
@@ -1983,7 +2304,10 @@ fn test_time_based_filter_from_native() {
         let policy = time_based_filter_from_qos_native(qos_native);
         assert!(policy.is_some());
         let policy = policy.unwrap();
-        assert_eq!(policy.minimum_separation, minimum_separation);
+        assert_eq!(
+            policy.minimum_separation,
+            DdsDuration::from_nanos(minimum_separation)
+        );
 
         dds_delete_qos(qos_native);
     }
@@ -2037,7 +2361,7 @@ fn test_partition_from_native() {
         dds_qset_partition(qos_native, len as u32, ptr);
         drop(Vec::from_raw_parts(ptr, len, cap));
 
-        let policy = partition_from_qos_native(qos_native);
+        let policy = partition_from_qos_native(qos_native).unwrap();
         assert!(policy.is_some());
         let policy = policy.unwrap();
         assert_eq!(policy.len(), partitions.len());
@@ -2069,7 +2393,7 @@ fn test_reliability_to_native() {
 
             let policy = Reliability {
                 kind: kind.0,
-                max_blocking_time: 1000,
+                max_blocking_time: DdsDuration::from_nanos(1000),
             };
             let input = Some(policy.clone());
             reliability_to_qos_native(qos_native, &input.clone());
@@ -2083,7 +2407,7 @@ fn test_reliability_to_native() {
                 &mut max_blocking_time,
             ));
             assert_eq!(reliability_kind, kind.1);
-            assert_eq!(max_blocking_time, policy.max_blocking_time);
+            assert_eq!(max_blocking_time, policy.max_blocking_time.as_nanos());
 
             dds_delete_qos(qos_native);
         }
@@ -2109,11 +2433,14 @@ fn test_reliability_from_native() {
             let max_blocking_time: i64 = DDS_100MS_DURATION;
             dds_qset_reliability(qos_native, kind.0, max_blocking_time);
 
-            let policy = reliability_from_qos_native(qos_native);
+            let policy = reliability_from_qos_native(qos_native).unwrap();
             assert!(policy.is_some());
             let policy = policy.unwrap();
             assert_eq!(policy.kind, kind.1);
-            assert_eq!(policy.max_blocking_time, max_blocking_time);
+            assert_eq!(
+                policy.max_blocking_time,
+                DdsDuration::from_nanos(max_blocking_time)
+            );
 
             dds_delete_qos(qos_native);
         }
@@ -2159,13 +2486,15 @@ fn test_lifespan_to_native() {
     unsafe {
         let qos_native = dds_create_qos();
 
-        let policy = Lifespan { duration: 1000 };
+        let policy = Lifespan {
+            duration: DdsDuration::from_nanos(1000),
+        };
         let input = Some(policy.clone());
         lifespan_to_qos_native(qos_native, &input.clone());
 
         let mut duration = 0;
         assert!(dds_qget_lifespan(qos_native, &mut duration,));
-        assert_eq!(duration, policy.duration);
+        assert_eq!(duration, policy.duration.as_nanos());
 
         dds_delete_qos(qos_native);
     }
@@ -2182,7 +2511,7 @@ fn test_lifespan_from_native() {
         let policy = lifespan_from_qos_native(qos_native);
         assert!(policy.is_some());
         let policy = policy.unwrap();
-        assert_eq!(policy.duration, duration);
+        assert_eq!(policy.duration, DdsDuration::from_nanos(duration));
 
         dds_delete_qos(qos_native);
     }
@@ -2238,7 +2567,7 @@ fn test_destination_order_from_native() {
 
             dds_qset_destination_order(qos_native, kind.0);
 
-            let policy = destination_order_from_qos_native(qos_native);
+            let policy = destination_order_from_qos_native(qos_native).unwrap();
             if is_default_value(&kind.1) {
                 assert!(policy.is_none())
             } else {
@@ -2301,7 +2630,7 @@ fn test_history_from_native() {
             let depth = 1000;
             dds_qset_history(qos_native, kind.0, depth);
 
-            let policy = history_from_qos_native(qos_native);
+            let policy = history_from_qos_native(qos_native).unwrap();
             assert!(policy.is_some());
             let policy = policy.unwrap();
             assert_eq!(policy.kind, kind.1);
@@ -2419,8 +2748,8 @@ fn test_reader_data_lifecycle_to_native() {
         let qos_native = dds_create_qos();
 
         let policy = ReaderDataLifecycle {
-            autopurge_nowriter_samples_delay: 1000,
-            autopurge_disposed_samples_delay: 1000,
+            autopurge_nowriter_samples_delay: DdsDuration::from_nanos(1000),
+            autopurge_disposed_samples_delay: DdsDuration::from_nanos(1000),
         };
         let input = Some(policy.clone());
         reader_data_lifecycle_to_qos_native(qos_native, &input.clone());
@@ -2434,11 +2763,11 @@ fn test_reader_data_lifecycle_to_native() {
         ));
         assert_eq!(
             autopurge_nowriter_samples_delay,
-            policy.autopurge_nowriter_samples_delay
+            policy.autopurge_nowriter_samples_delay.as_nanos()
         );
         assert_eq!(
             autopurge_disposed_samples_delay,
-            policy.autopurge_disposed_samples_delay
+            policy.autopurge_disposed_samples_delay.as_nanos()
         );
 
         dds_delete_qos(qos_native);
@@ -2463,11 +2792,11 @@ fn test_reader_data_lifecycle_from_native() {
         let policy = policy.unwrap();
         assert_eq!(
             policy.autopurge_nowriter_samples_delay,
-            autopurge_nowriter_samples_delay
+            DdsDuration::from_nanos(autopurge_nowriter_samples_delay)
         );
         assert_eq!(
             policy.autopurge_disposed_samples_delay,
-            autopurge_disposed_samples_delay
+            DdsDuration::from_nanos(autopurge_disposed_samples_delay)
         );
 
         dds_delete_qos(qos_native);
@@ -2598,7 +2927,7 @@ fn test_type_consistency_from_native() {
                 force_type_validation,
             );
 
-            let policy = type_consistency_from_qos_native(qos_native);
+            let policy = type_consistency_from_qos_native(qos_native).unwrap();
             assert!(policy.is_some());
             let policy = policy.unwrap();
             assert_eq!(policy.kind, kind.1);
@@ -2641,7 +2970,7 @@ fn test_entity_name_from_native() {
         let name = CString::new("TEST_ENTITY_NAME").unwrap();
         dds_qset_entity_name(qos_native, name.as_ptr());
 
-        let policy = entity_name_from_qos_native(qos_native);
+        let policy = entity_name_from_qos_native(qos_native).unwrap();
         assert!(policy.is_some());
         let policy = policy.unwrap();
         assert_eq!(name.to_str().unwrap(), policy.name);
@@ -2712,7 +3041,7 @@ fn test_properties_from_native() {
             dds_qset_prop(qos_native, cname.as_ptr(), cvalue.as_ptr());
         }
 
-        let policy = properties_from_qos_native(qos_native);
+        let policy = properties_from_qos_native(qos_native).unwrap();
         assert!(policy.is_some());
         let policy = policy.unwrap();
         assert_eq!(policy.len(), properties.len());
@@ -2783,7 +3112,7 @@ fn test_ignore_local_from_native() {
             let qos_native = dds_create_qos();
             dds_qset_ignorelocal(qos_native, kind.0);
 
-            let policy = ignore_local_from_qos_native(qos_native);
+            let policy = ignore_local_from_qos_native(qos_native).unwrap();
             if is_default_value(&kind.1) {
                 assert!(policy.is_none())
             } else {
@@ -2905,10 +3234,14 @@ fn test_fully_populated_qos_serialization() {
         });
         presentation_to_qos_native(qos_native, &presentation);
 
-        let deadline = Some(Deadline { period: 15 });
+        let deadline = Some(Deadline {
+            period: DdsDuration::from_nanos(15),
+        });
         deadline_to_qos_native(qos_native, &deadline);
 
-        let latency_budget = Some(LatencyBudget { duration: 42 });
+        let latency_budget = Some(LatencyBudget {
+            duration: DdsDuration::from_nanos(42),
+        });
         latency_budget_to_qos_native(qos_native, &latency_budget);
 
         let ownership = Some(Ownership {
@@ -2921,12 +3254,12 @@ fn test_fully_populated_qos_serialization() {
 
         let liveliness = Some(Liveliness {
             kind: LivelinessKind::MANUAL_BY_PARTICIPANT,
-            lease_duration: 3,
+            lease_duration: DdsDuration::from_nanos(3),
         });
         liveliness_to_qos_native(qos_native, &liveliness);
 
         let time_based_filter = Some(TimeBasedFilter {
-            minimum_separation: 56,
+            minimum_separation: DdsDuration::from_nanos(56),
         });
         time_based_filter_to_qos_native(qos_native, &time_based_filter);
 
@@ -2938,14 +3271,16 @@ fn test_fully_populated_qos_serialization() {
 
         let reliability = Some(Reliability {
             kind: ReliabilityKind::RELIABLE,
-            max_blocking_time: 500,
+            max_blocking_time: DdsDuration::from_nanos(500),
         });
         reliability_to_qos_native(qos_native, &reliability);
 
         let transport_priority = Some(TransportPriority { value: 3 });
         transport_priority_to_qos_native(qos_native, &transport_priority);
 
-        let lifespan = Some(Lifespan { duration: 10 });
+        let lifespan = Some(Lifespan {
+            duration: DdsDuration::from_nanos(10),
+        });
         lifespan_to_qos_native(qos_native, &lifespan);
 
         let destination_order = Some(DestinationOrder {
@@ -2971,7 +3306,7 @@ fn test_fully_populated_qos_serialization() {
         writer_data_lifecycle_to_qos_native(qos_native, &writer_data_lifecycle);
 
         let reader_data_lifecycle = Some(ReaderDataLifecycle {
-            autopurge_disposed_samples_delay: 30,
+            autopurge_disposed_samples_delay: DdsDuration::from_nanos(30),
             ..Default::default()
         });
         reader_data_lifecycle_to_qos_native(qos_native, &reader_data_lifecycle);
@@ -3028,3 +3363,92 @@ fn test_fully_populated_qos_serialization() {
         assert_all_policies_set(&qos3);
     }
 }
+
+#[test]
+fn test_kind_try_from_invalid_value() {
+    let invalid: dds_durability_kind_t = 42;
+    assert_eq!(
+        DurabilityKind::try_from(&invalid),
+        Err(ConversionFailure::InvalidKind {
+            kind: "DurabilityKind",
+            value: 42,
+        })
+    );
+    let invalid: dds_reliability_kind_t = 42;
+    assert!(ReliabilityKind::try_from(&invalid).is_err());
+    assert_eq!(
+        HistoryKind::try_from(&dds_history_kind_DDS_HISTORY_KEEP_ALL),
+        Ok(HistoryKind::KEEP_ALL)
+    );
+}
+
+#[test]
+fn test_try_from_qos_native() {
+    unsafe {
+        let qos_native = dds_create_qos();
+        dds_qset_durability(
+            qos_native,
+            dds_durability_kind_DDS_DURABILITY_TRANSIENT_LOCAL,
+        );
+        let qos = Qos::try_from_qos_native(qos_native).unwrap();
+        assert_eq!(qos, Qos::from_qos_native(qos_native));
+        assert!(qos.durability.is_some());
+        dds_delete_qos(qos_native);
+    }
+}
+
+#[test]
+fn test_try_from_qos_native_invalid_partition() {
+    unsafe {
+        let qos_native = dds_create_qos();
+
+        let partitions = [
+            CString::new("P1").unwrap(),
+            CString::new(vec![0xff, 0xfe]).unwrap(),
+        ];
+        let vptr: Vec<*const c_char> = partitions.iter().map(|p| p.as_ptr()).collect();
+        let (ptr, len, cap) = vec_into_raw_parts(vptr);
+        dds_qset_partition(qos_native, len as u32, ptr);
+        drop(Vec::from_raw_parts(ptr, len, cap));
+        dds_qset_durability(
+            qos_native,
+            dds_durability_kind_DDS_DURABILITY_TRANSIENT_LOCAL,
+        );
+
+        let error = Qos::try_from_qos_native(qos_native).unwrap_err();
+        assert_eq!(error.errors.len(), 1);
+        assert_eq!(error.errors[0].policy, "partition");
+        assert!(matches!(
+            error.errors[0].reason,
+            ConversionFailure::InvalidString(_)
+        ));
+
+        // The lenient conversion only drops the invalid policy
+        let qos = Qos::from_qos_native(qos_native);
+        assert!(qos.partition.is_none());
+        assert!(qos.durability.is_some());
+
+        dds_delete_qos(qos_native);
+    }
+}
+
+#[test]
+fn test_native_qos() {
+    let qos = Qos {
+        partition: Some(vec![String::from("P1"), String::from("P2")]),
+        history: Some(History {
+            kind: HistoryKind::KEEP_ALL,
+            depth: 1,
+        }),
+        ..Default::default()
+    };
+    let native = NativeQos::from(&qos);
+    let copy = native.clone();
+    drop(native);
+    assert_eq!(Qos::from(&copy), qos);
+    assert_eq!(Qos::from(&NativeQos::new()), Qos::default());
+
+    let raw = copy.into_raw();
+    let native = unsafe { NativeQos::from_raw(raw) };
+    assert_eq!(native.as_ptr(), raw as *const dds_qos_t);
+}
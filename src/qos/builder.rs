@@ -0,0 +1,377 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use super::*;
+
+/// The kinds of DDS entities a QoS can apply to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EntityKind {
+    Participant,
+    Topic,
+    Publisher,
+    Subscriber,
+    Writer,
+    Reader,
+}
+
+impl Qos {
+    /// Returns a QoS with every policy applicable to `kind` set to its DDS-spec default.
+    ///
+    /// The user, topic and group data and the partition policies are left unset, as their
+    /// default (empty) value is represented by `None`.
+    pub fn default_for(kind: EntityKind) -> Qos {
+        let reliability = |kind| {
+            Some(Reliability {
+                kind,
+                max_blocking_time: DdsDuration::from_millis(100),
+            })
+        };
+        match kind {
+            EntityKind::Participant => Qos::default(),
+            EntityKind::Publisher | EntityKind::Subscriber => Qos {
+                presentation: Some(Presentation::default()),
+                ..Default::default()
+            },
+            EntityKind::Topic => Qos {
+                durability: Some(Durability::default()),
+                durability_service: Some(DurabilityService::default()),
+                deadline: Some(Deadline::default()),
+                latency_budget: Some(LatencyBudget::default()),
+                liveliness: Some(Liveliness::default()),
+                reliability: reliability(ReliabilityKind::BEST_EFFORT),
+                destination_order: Some(DestinationOrder::default()),
+                history: Some(History::default()),
+                resource_limits: Some(ResourceLimits::default()),
+                transport_priority: Some(TransportPriority::default()),
+                lifespan: Some(Lifespan::default()),
+                ownership: Some(Ownership::default()),
+                ..Default::default()
+            },
+            EntityKind::Writer => Qos {
+                durability: Some(Durability::default()),
+                durability_service: Some(DurabilityService::default()),
+                deadline: Some(Deadline::default()),
+                latency_budget: Some(LatencyBudget::default()),
+                liveliness: Some(Liveliness::default()),
+                reliability: reliability(ReliabilityKind::RELIABLE),
+                destination_order: Some(DestinationOrder::default()),
+                history: Some(History::default()),
+                resource_limits: Some(ResourceLimits::default()),
+                transport_priority: Some(TransportPriority::default()),
+                lifespan: Some(Lifespan::default()),
+                ownership: Some(Ownership::default()),
+                ownership_strength: Some(OwnershipStrength::default()),
+                writer_data_lifecycle: Some(WriterDataLifecycle::default()),
+                ..Default::default()
+            },
+            EntityKind::Reader => Qos {
+                durability: Some(Durability::default()),
+                deadline: Some(Deadline::default()),
+                latency_budget: Some(LatencyBudget::default()),
+                liveliness: Some(Liveliness::default()),
+                reliability: reliability(ReliabilityKind::BEST_EFFORT),
+                destination_order: Some(DestinationOrder::default()),
+                history: Some(History::default()),
+                resource_limits: Some(ResourceLimits::default()),
+                ownership: Some(Ownership::default()),
+                time_based_filter: Some(TimeBasedFilter::default()),
+                reader_data_lifecycle: Some(ReaderDataLifecycle::default()),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn builder() -> QosBuilder {
+        QosBuilder::default()
+    }
+}
+
+/// Builds a [`Qos`] with chained setters, e.g.
+/// `QosBuilder::for_kind(EntityKind::Writer).transient_local().keep_last(10).build()`.
+#[derive(Debug, Clone, Default)]
+pub struct QosBuilder {
+    qos: Qos,
+}
+
+impl QosBuilder {
+    /// Starts from a QoS with no policy set.
+    pub fn new() -> Self {
+        QosBuilder::default()
+    }
+
+    /// Starts from the defaults of [`Qos::default_for`].
+    pub fn for_kind(kind: EntityKind) -> Self {
+        QosBuilder {
+            qos: Qos::default_for(kind),
+        }
+    }
+
+    pub fn build(self) -> Qos {
+        self.qos
+    }
+
+    pub fn user_data(mut self, user_data: Vec<u8>) -> Self {
+        self.qos.user_data = Some(user_data);
+        self
+    }
+
+    pub fn topic_data(mut self, topic_data: Vec<u8>) -> Self {
+        self.qos.topic_data = Some(topic_data);
+        self
+    }
+
+    pub fn group_data(mut self, group_data: Vec<u8>) -> Self {
+        self.qos.group_data = Some(group_data);
+        self
+    }
+
+    pub fn durability(mut self, kind: DurabilityKind) -> Self {
+        self.qos.durability = Some(Durability { kind });
+        self
+    }
+
+    pub fn volatile(self) -> Self {
+        self.durability(DurabilityKind::VOLATILE)
+    }
+
+    pub fn transient_local(self) -> Self {
+        self.durability(DurabilityKind::TRANSIENT_LOCAL)
+    }
+
+    pub fn transient(self) -> Self {
+        self.durability(DurabilityKind::TRANSIENT)
+    }
+
+    pub fn persistent(self) -> Self {
+        self.durability(DurabilityKind::PERSISTENT)
+    }
+
+    pub fn durability_service(mut self, durability_service: DurabilityService) -> Self {
+        self.qos.durability_service = Some(durability_service);
+        self
+    }
+
+    pub fn presentation(mut self, presentation: Presentation) -> Self {
+        self.qos.presentation = Some(presentation);
+        self
+    }
+
+    pub fn deadline(mut self, period: impl Into<DdsDuration>) -> Self {
+        self.qos.deadline = Some(Deadline {
+            period: period.into(),
+        });
+        self
+    }
+
+    pub fn latency_budget(mut self, duration: impl Into<DdsDuration>) -> Self {
+        self.qos.latency_budget = Some(LatencyBudget {
+            duration: duration.into(),
+        });
+        self
+    }
+
+    pub fn shared_ownership(mut self) -> Self {
+        self.qos.ownership = Some(Ownership {
+            kind: OwnershipKind::SHARED,
+        });
+        self
+    }
+
+    pub fn exclusive_ownership(mut self, strength: i32) -> Self {
+        self.qos.ownership = Some(Ownership {
+            kind: OwnershipKind::EXCLUSIVE,
+        });
+        self.qos.ownership_strength = Some(OwnershipStrength { value: strength });
+        self
+    }
+
+    pub fn liveliness(
+        mut self,
+        kind: LivelinessKind,
+        lease_duration: impl Into<DdsDuration>,
+    ) -> Self {
+        self.qos.liveliness = Some(Liveliness {
+            kind,
+            lease_duration: lease_duration.into(),
+        });
+        self
+    }
+
+    pub fn time_based_filter(mut self, minimum_separation: impl Into<DdsDuration>) -> Self {
+        self.qos.time_based_filter = Some(TimeBasedFilter {
+            minimum_separation: minimum_separation.into(),
+        });
+        self
+    }
+
+    pub fn partitions<I, S>(mut self, partitions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.qos.partition = Some(partitions.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn reliable(mut self, max_blocking_time: impl Into<DdsDuration>) -> Self {
+        self.qos.reliability = Some(Reliability {
+            kind: ReliabilityKind::RELIABLE,
+            max_blocking_time: max_blocking_time.into(),
+        });
+        self
+    }
+
+    pub fn best_effort(mut self) -> Self {
+        self.qos.reliability = Some(Reliability {
+            kind: ReliabilityKind::BEST_EFFORT,
+            max_blocking_time: DdsDuration::from_millis(100),
+        });
+        self
+    }
+
+    pub fn transport_priority(mut self, value: i32) -> Self {
+        self.qos.transport_priority = Some(TransportPriority { value });
+        self
+    }
+
+    pub fn lifespan(mut self, duration: impl Into<DdsDuration>) -> Self {
+        self.qos.lifespan = Some(Lifespan {
+            duration: duration.into(),
+        });
+        self
+    }
+
+    pub fn destination_order(mut self, kind: DestinationOrderKind) -> Self {
+        self.qos.destination_order = Some(DestinationOrder { kind });
+        self
+    }
+
+    pub fn keep_last(mut self, depth: i32) -> Self {
+        self.qos.history = Some(History {
+            kind: HistoryKind::KEEP_LAST,
+            depth,
+        });
+        self
+    }
+
+    pub fn keep_all(mut self) -> Self {
+        self.qos.history = Some(History {
+            kind: HistoryKind::KEEP_ALL,
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn resource_limits(mut self, resource_limits: ResourceLimits) -> Self {
+        self.qos.resource_limits = Some(resource_limits);
+        self
+    }
+
+    pub fn autodispose_unregistered_instances(mut self, autodispose: bool) -> Self {
+        self.qos.writer_data_lifecycle = Some(WriterDataLifecycle {
+            autodispose_unregistered_instances: autodispose,
+        });
+        self
+    }
+
+    pub fn reader_data_lifecycle(mut self, reader_data_lifecycle: ReaderDataLifecycle) -> Self {
+        self.qos.reader_data_lifecycle = Some(reader_data_lifecycle);
+        self
+    }
+
+    pub fn writer_batching(mut self, batch_updates: bool) -> Self {
+        self.qos.writer_batching = Some(WriterBatching { batch_updates });
+        self
+    }
+
+    pub fn type_consistency(mut self, type_consistency: TypeConsistency) -> Self {
+        self.qos.type_consistency = Some(type_consistency);
+        self
+    }
+
+    pub fn entity_name(mut self, name: impl Into<String>) -> Self {
+        self.qos.entity_name = Some(EntityName { name: name.into() });
+        self
+    }
+
+    pub fn property(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.qos
+            .properties
+            .get_or_insert_with(HashMap::new)
+            .insert(name.into(), value.into());
+        self
+    }
+
+    pub fn ignore_local(mut self, kind: IgnoreLocalKind) -> Self {
+        self.qos.ignore_local = Some(IgnoreLocal { kind });
+        self
+    }
+
+    pub fn data_representation(mut self, values: Vec<dds_data_representation_id_t>) -> Self {
+        self.qos.data_representation = Some(values);
+        self
+    }
+}
+
+#[test]
+fn test_qos_builder() {
+    let qos = Qos::builder()
+        .reliable(std::time::Duration::from_secs(1))
+        .keep_last(10)
+        .transient_local()
+        .partitions(["P1", "P2"])
+        .property("name", "value")
+        .build();
+    assert_eq!(
+        qos,
+        Qos {
+            reliability: Some(Reliability {
+                kind: ReliabilityKind::RELIABLE,
+                max_blocking_time: DdsDuration::from_secs(1),
+            }),
+            history: Some(History {
+                kind: HistoryKind::KEEP_LAST,
+                depth: 10,
+            }),
+            durability: Some(Durability {
+                kind: DurabilityKind::TRANSIENT_LOCAL,
+            }),
+            partition: Some(vec![String::from("P1"), String::from("P2")]),
+            properties: Some(HashMap::from([(
+                String::from("name"),
+                String::from("value")
+            )])),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_qos_default_for() {
+    let writer = Qos::default_for(EntityKind::Writer);
+    let reader = Qos::default_for(EntityKind::Reader);
+    assert_eq!(
+        writer.reliability.map(|r| r.kind),
+        Some(ReliabilityKind::RELIABLE)
+    );
+    assert_eq!(
+        reader.reliability.as_ref().map(|r| r.kind),
+        Some(ReliabilityKind::BEST_EFFORT)
+    );
+    assert!(reader.writer_data_lifecycle.is_none());
+    assert!(reader.reader_data_lifecycle.is_some());
+
+    let qos = QosBuilder::for_kind(EntityKind::Reader).keep_all().build();
+    assert_eq!(qos.history.map(|h| h.kind), Some(HistoryKind::KEEP_ALL));
+    assert_eq!(qos.durability, reader.durability);
+}
@@ -0,0 +1,357 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use super::*;
+
+/// A Request vs Offered (RxO) policy mismatch between a writer and a reader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Incompatibility {
+    pub policy: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.policy, self.reason)
+    }
+}
+
+/// Checks whether a writer with the `offered` QoS matches a reader with the `requested` QoS.
+///
+/// Unset policies take the DDS default for the role (e.g. RELIABLE for a writer, BEST_EFFORT
+/// for a reader). The types of the writer and the reader are not known here, so the type
+/// consistency policy is checked as by [`check_type_consistency`] without type information.
+pub fn check_compatibility(offered: &Qos, requested: &Qos) -> Result<(), Vec<Incompatibility>> {
+    let mut incompatibilities = Vec::new();
+    let mut incompatible = |policy: &'static str, reason: String| {
+        incompatibilities.push(Incompatibility { policy, reason })
+    };
+
+    let o = offered.durability.clone().unwrap_or_default();
+    let r = requested.durability.clone().unwrap_or_default();
+    if (o.kind as isize) < (r.kind as isize) {
+        incompatible(
+            "durability",
+            format!(
+                "offered {:?} is less durable than requested {:?}",
+                o.kind, r.kind
+            ),
+        );
+    }
+
+    let o = offered
+        .reliability
+        .as_ref()
+        .map_or(ReliabilityKind::RELIABLE, |p| p.kind);
+    let r = requested
+        .reliability
+        .as_ref()
+        .map_or(ReliabilityKind::BEST_EFFORT, |p| p.kind);
+    if (o as isize) < (r as isize) {
+        incompatible(
+            "reliability",
+            format!("offered {o:?} is less reliable than requested {r:?}"),
+        );
+    }
+
+    let o = offered.deadline.clone().unwrap_or_default();
+    let r = requested.deadline.clone().unwrap_or_default();
+    if o.period > r.period {
+        incompatible(
+            "deadline",
+            format!(
+                "offered period {} is longer than requested period {}",
+                o.period, r.period
+            ),
+        );
+    }
+
+    let o = offered.latency_budget.clone().unwrap_or_default();
+    let r = requested.latency_budget.clone().unwrap_or_default();
+    if o.duration > r.duration {
+        incompatible(
+            "latency_budget",
+            format!(
+                "offered duration {} is longer than requested duration {}",
+                o.duration, r.duration
+            ),
+        );
+    }
+
+    let o = offered.liveliness.clone().unwrap_or_default();
+    let r = requested.liveliness.clone().unwrap_or_default();
+    if (o.kind as isize) < (r.kind as isize) {
+        incompatible(
+            "liveliness",
+            format!("offered {:?} is weaker than requested {:?}", o.kind, r.kind),
+        );
+    }
+    if o.lease_duration > r.lease_duration {
+        incompatible(
+            "liveliness",
+            format!(
+                "offered lease duration {} is longer than requested lease duration {}",
+                o.lease_duration, r.lease_duration
+            ),
+        );
+    }
+
+    let o = offered.ownership.clone().unwrap_or_default();
+    let r = requested.ownership.clone().unwrap_or_default();
+    if o.kind != r.kind {
+        incompatible(
+            "ownership",
+            format!("offered {:?} differs from requested {:?}", o.kind, r.kind),
+        );
+    }
+
+    let o = offered.destination_order.clone().unwrap_or_default();
+    let r = requested.destination_order.clone().unwrap_or_default();
+    if (o.kind as isize) < (r.kind as isize) {
+        incompatible(
+            "destination_order",
+            format!("offered {:?} is weaker than requested {:?}", o.kind, r.kind),
+        );
+    }
+
+    let o = offered.presentation.clone().unwrap_or_default();
+    let r = requested.presentation.clone().unwrap_or_default();
+    if (o.access_scope as isize) < (r.access_scope as isize) {
+        incompatible(
+            "presentation",
+            format!(
+                "offered access scope {:?} is narrower than requested {:?}",
+                o.access_scope, r.access_scope
+            ),
+        );
+    }
+    if r.coherent_access && !o.coherent_access {
+        incompatible(
+            "presentation",
+            String::from("coherent access is requested but not offered"),
+        );
+    }
+    if r.ordered_access && !o.ordered_access {
+        incompatible(
+            "presentation",
+            String::from("ordered access is requested but not offered"),
+        );
+    }
+
+    let o = partitions_or_default(&offered.partition);
+    let r = partitions_or_default(&requested.partition);
+    if !o.iter().any(|o| r.iter().any(|r| partitions_match(o, r))) {
+        incompatible(
+            "partition",
+            format!("no offered partition in {o:?} matches the requested partitions {r:?}"),
+        );
+    }
+
+    // Cyclone DDS defaults to XCDR1 for writers and accepts both XCDR1 and XCDR2 for readers
+    // (for types that can be represented in XCDR1). A writer only uses its first representation.
+    let o = match &offered.data_representation {
+        Some(values) => values.first().copied(),
+        None => Some(DDS_DATA_REPRESENTATION_XCDR1 as dds_data_representation_id_t),
+    };
+    let r = match &requested.data_representation {
+        Some(values) => values.clone(),
+        None => vec![
+            DDS_DATA_REPRESENTATION_XCDR1 as dds_data_representation_id_t,
+            DDS_DATA_REPRESENTATION_XCDR2 as dds_data_representation_id_t,
+        ],
+    };
+    match o {
+        Some(o) if r.contains(&o) => (),
+        Some(o) => incompatible(
+            "data_representation",
+            format!("offered representation {o} is not in the requested representations {r:?}"),
+        ),
+        None => incompatible(
+            "data_representation",
+            String::from("no data representation offered"),
+        ),
+    }
+
+    if let Err(incompatibility) = check_type_consistency(requested, None) {
+        incompatibilities.push(incompatibility);
+    }
+
+    if incompatibilities.is_empty() {
+        Ok(())
+    } else {
+        Err(incompatibilities)
+    }
+}
+
+/// Checks the type consistency policy of a reader with the `requested` QoS, given whether the
+/// writer type is `assignable` to the reader type under that policy (`None` if the type
+/// information of either is missing).
+///
+/// As in Cyclone DDS, a reader forcing the type validation doesn't match a writer whose type
+/// can't be validated.
+pub fn check_type_consistency(
+    requested: &Qos,
+    assignable: Option<bool>,
+) -> Result<(), Incompatibility> {
+    let r = requested.type_consistency.clone().unwrap_or_default();
+    let reason = match assignable {
+        Some(true) => return Ok(()),
+        None if !r.force_type_validation => return Ok(()),
+        Some(false) => format!("the offered type is not assignable with {:?}", r.kind),
+        None => String::from("type validation is forced but the type information is missing"),
+    };
+    Err(Incompatibility {
+        policy: "type_consistency",
+        reason,
+    })
+}
+
+fn partitions_or_default(partitions: &Option<Vec<String>>) -> Vec<String> {
+    match partitions {
+        Some(partitions) if !partitions.is_empty() => partitions.clone(),
+        // The default partition is the empty string
+        _ => vec![String::new()],
+    }
+}
+
+// As in Cyclone DDS, a partition expression containing wildcards matches the plain partition
+// names it covers, but two different expressions never match each other.
+fn partitions_match(a: &str, b: &str) -> bool {
+    let is_expression = |p: &str| p.contains(['*', '?']);
+    match (is_expression(a), is_expression(b)) {
+        (false, false) | (true, true) => a == b,
+        (true, false) => wildcard_match(a.as_bytes(), b.as_bytes()),
+        (false, true) => wildcard_match(b.as_bytes(), a.as_bytes()),
+    }
+}
+
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| wildcard_match(rest, &name[i..])),
+        Some((b'?', rest)) => !name.is_empty() && wildcard_match(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && wildcard_match(rest, &name[1..]),
+    }
+}
+
+#[test]
+fn test_compatibility_defaults() {
+    assert_eq!(
+        check_compatibility(&Qos::default(), &Qos::default()),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_compatibility_rxo() {
+    let offered = Qos {
+        durability: Some(Durability {
+            kind: DurabilityKind::VOLATILE,
+        }),
+        reliability: Some(Reliability {
+            kind: ReliabilityKind::BEST_EFFORT,
+            max_blocking_time: DdsDuration::from_millis(100),
+        }),
+        deadline: Some(Deadline {
+            period: DdsDuration::from_secs(1),
+        }),
+        ownership: Some(Ownership {
+            kind: OwnershipKind::EXCLUSIVE,
+        }),
+        ..Default::default()
+    };
+    let requested = Qos {
+        durability: Some(Durability {
+            kind: DurabilityKind::TRANSIENT_LOCAL,
+        }),
+        reliability: Some(Reliability {
+            kind: ReliabilityKind::RELIABLE,
+            max_blocking_time: DdsDuration::from_millis(100),
+        }),
+        deadline: Some(Deadline {
+            period: DdsDuration::from_millis(100),
+        }),
+        ..Default::default()
+    };
+    let policies: Vec<&str> = check_compatibility(&offered, &requested)
+        .unwrap_err()
+        .iter()
+        .map(|i| i.policy)
+        .collect();
+    assert_eq!(
+        policies,
+        vec!["durability", "reliability", "deadline", "ownership"]
+    );
+
+    // The other way round everything is compatible but the ownership
+    let policies: Vec<&str> = check_compatibility(&requested, &offered)
+        .unwrap_err()
+        .iter()
+        .map(|i| i.policy)
+        .collect();
+    assert_eq!(policies, vec!["ownership"]);
+}
+
+#[test]
+fn test_compatibility_partition() {
+    let with_partitions = |partitions: &[&str]| Qos {
+        partition: Some(partitions.iter().map(|p| String::from(*p)).collect()),
+        ..Default::default()
+    };
+    assert!(check_compatibility(&with_partitions(&["A", "B"]), &with_partitions(&["B"])).is_ok());
+    assert!(check_compatibility(&with_partitions(&["A*"]), &with_partitions(&["AB"])).is_ok());
+    assert!(check_compatibility(&with_partitions(&["A?C"]), &with_partitions(&["ABC"])).is_ok());
+    assert!(check_compatibility(&with_partitions(&["A*"]), &with_partitions(&["B*"])).is_err());
+    assert!(check_compatibility(&with_partitions(&["A"]), &Qos::default()).is_err());
+    assert!(check_compatibility(&with_partitions(&[""]), &Qos::default()).is_ok());
+}
+
+#[test]
+fn test_compatibility_data_representation() {
+    let offered = Qos {
+        data_representation: Some(vec![
+            DDS_DATA_REPRESENTATION_XCDR2 as dds_data_representation_id_t,
+        ]),
+        ..Default::default()
+    };
+    let requested = Qos {
+        data_representation: Some(vec![
+            DDS_DATA_REPRESENTATION_XCDR1 as dds_data_representation_id_t,
+        ]),
+        ..Default::default()
+    };
+    assert!(check_compatibility(&offered, &Qos::default()).is_ok());
+    let incompatibilities = check_compatibility(&offered, &requested).unwrap_err();
+    assert_eq!(incompatibilities.len(), 1);
+    assert_eq!(incompatibilities[0].policy, "data_representation");
+}
+
+#[test]
+fn test_compatibility_type_consistency() {
+    let requested = Qos {
+        type_consistency: Some(TypeConsistency {
+            force_type_validation: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let incompatibilities = check_compatibility(&Qos::default(), &requested).unwrap_err();
+    assert_eq!(incompatibilities.len(), 1);
+    assert_eq!(incompatibilities[0].policy, "type_consistency");
+    // The type consistency of the writer is not an RxO policy
+    assert!(check_compatibility(&requested, &Qos::default()).is_ok());
+
+    assert!(check_type_consistency(&requested, Some(true)).is_ok());
+    assert!(check_type_consistency(&Qos::default(), None).is_ok());
+    assert!(check_type_consistency(&Qos::default(), Some(false)).is_err());
+}
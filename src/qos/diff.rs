@@ -0,0 +1,288 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use super::*;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PolicyChangeKind {
+    /// The policy is only set in the other QoS.
+    Set,
+    /// The policy is only set in this QoS.
+    Unset,
+    /// The policy is set in both QoS, with different values.
+    Modified,
+}
+
+/// A policy that differs between two [`Qos`], as returned by [`Qos::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyChange {
+    pub policy: &'static str,
+    pub kind: PolicyChangeKind,
+    /// A QoS with only the policy as set in this QoS.
+    pub old: Qos,
+    /// A QoS with only the policy as set in the other QoS.
+    pub new: Qos,
+}
+
+fn change_kind<T: PartialEq>(from: &Option<T>, to: &Option<T>) -> Option<PolicyChangeKind> {
+    match (from, to) {
+        (None, Some(_)) => Some(PolicyChangeKind::Set),
+        (Some(_), None) => Some(PolicyChangeKind::Unset),
+        (Some(from), Some(to)) if from != to => Some(PolicyChangeKind::Modified),
+        _ => None,
+    }
+}
+
+fn merge_policy<T: Clone>(policy: &mut Option<T>, overlay: &Option<T>) {
+    if overlay.is_some() {
+        policy.clone_from(overlay);
+    }
+}
+
+macro_rules! impl_diff_merge {
+    ($($policy:ident),* $(,)?) => {
+        impl Qos {
+            /// Returns the policies that differ from `self` to `other`, in declaration order.
+            ///
+            /// Defaults are not applied: an unset policy differs from a policy explicitly set
+            /// to its default, as the default of some policies depends on the entity (e.g. the
+            /// reliability of writers and readers).
+            pub fn diff(&self, other: &Qos) -> Vec<PolicyChange> {
+                // Fails to compile if a policy is missing from the list
+                let Qos { $($policy: _),* } = self;
+                let mut changes = Vec::new();
+                $(if let Some(kind) = change_kind(&self.$policy, &other.$policy) {
+                    changes.push(PolicyChange {
+                        policy: stringify!($policy),
+                        kind,
+                        old: Qos {
+                            $policy: self.$policy.clone(),
+                            ..Default::default()
+                        },
+                        new: Qos {
+                            $policy: other.$policy.clone(),
+                            ..Default::default()
+                        },
+                    });
+                })*
+                changes
+            }
+
+            /// Overrides the policies of `self` with those set in `overlay`.
+            pub fn merge(&mut self, overlay: &Qos) {
+                $(merge_policy(&mut self.$policy, &overlay.$policy);)*
+            }
+        }
+
+        #[cfg(test)]
+        fn single_policy_qos_for_tests(qos: &Qos) -> Vec<(&'static str, Qos)> {
+            vec![$((
+                stringify!($policy),
+                Qos {
+                    $policy: qos.$policy.clone(),
+                    ..Default::default()
+                },
+            )),*]
+        }
+    };
+}
+
+impl_diff_merge!(
+    user_data,
+    topic_data,
+    group_data,
+    durability,
+    durability_service,
+    presentation,
+    deadline,
+    latency_budget,
+    ownership,
+    ownership_strength,
+    liveliness,
+    time_based_filter,
+    partition,
+    reliability,
+    transport_priority,
+    lifespan,
+    destination_order,
+    history,
+    resource_limits,
+    writer_data_lifecycle,
+    reader_data_lifecycle,
+    writer_batching,
+    type_consistency,
+    entity_name,
+    properties,
+    ignore_local,
+    data_representation,
+);
+
+#[cfg(test)]
+fn all_policies_qos_for_tests() -> Qos {
+    Qos::builder()
+        .user_data(vec![1, 2, 3])
+        .topic_data(vec![4, 5, 6])
+        .group_data(vec![7, 8, 9])
+        .transient_local()
+        .durability_service(DurabilityService {
+            history_depth: 5,
+            ..Default::default()
+        })
+        .presentation(Presentation {
+            access_scope: PresentationAccessScopeKind::TOPIC,
+            coherent_access: true,
+            ordered_access: false,
+        })
+        .deadline(DdsDuration::from_millis(15))
+        .latency_budget(DdsDuration::from_millis(42))
+        .exclusive_ownership(13)
+        .liveliness(LivelinessKind::MANUAL_BY_TOPIC, DdsDuration::from_secs(3))
+        .time_based_filter(DdsDuration::from_millis(5))
+        .partitions(["P1", "P2"])
+        .reliable(DdsDuration::from_millis(500))
+        .transport_priority(7)
+        .lifespan(DdsDuration::from_secs(10))
+        .destination_order(DestinationOrderKind::BY_SOURCE_TIMESTAMP)
+        .keep_last(10)
+        .resource_limits(ResourceLimits {
+            max_samples: 100,
+            ..Default::default()
+        })
+        .autodispose_unregistered_instances(false)
+        .reader_data_lifecycle(ReaderDataLifecycle {
+            autopurge_disposed_samples_delay: DdsDuration::from_secs(30),
+            ..Default::default()
+        })
+        .writer_batching(true)
+        .type_consistency(TypeConsistency {
+            kind: TypeConsistencyKind::ALLOW_TYPE_COERCION,
+            ..Default::default()
+        })
+        .entity_name("TEST_ENTITY_NAME")
+        .property("PROP_1", "VALUE_1")
+        .ignore_local(IgnoreLocalKind::PARTICIPANT)
+        .data_representation(vec![
+            DDS_DATA_REPRESENTATION_XCDR2 as dds_data_representation_id_t,
+        ])
+        .build()
+}
+
+#[test]
+fn test_diff_each_policy() {
+    let empty = Qos::default();
+    let single_policies = single_policy_qos_for_tests(&all_policies_qos_for_tests());
+    assert_eq!(single_policies.len(), 27);
+    for (policy, qos) in single_policies {
+        assert!(qos != empty, "{policy} not set in the test QoS");
+        assert_eq!(
+            empty.diff(&qos),
+            vec![PolicyChange {
+                policy,
+                kind: PolicyChangeKind::Set,
+                old: empty.clone(),
+                new: qos.clone(),
+            }]
+        );
+        assert_eq!(
+            qos.diff(&empty),
+            vec![PolicyChange {
+                policy,
+                kind: PolicyChangeKind::Unset,
+                old: qos.clone(),
+                new: empty.clone(),
+            }]
+        );
+        assert!(qos.diff(&qos).is_empty());
+    }
+}
+
+#[test]
+fn test_diff_modified() {
+    let base = Qos::builder().keep_last(1).transient_local().build();
+    let other = Qos::builder()
+        .keep_last(10)
+        .transient_local()
+        .best_effort()
+        .build();
+    assert_eq!(
+        base.diff(&other),
+        vec![
+            PolicyChange {
+                policy: "reliability",
+                kind: PolicyChangeKind::Set,
+                old: Qos::default(),
+                new: Qos::builder().best_effort().build(),
+            },
+            PolicyChange {
+                policy: "history",
+                kind: PolicyChangeKind::Modified,
+                old: Qos::builder().keep_last(1).build(),
+                new: Qos::builder().keep_last(10).build(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_diff_defaults() {
+    // A policy explicitly set to its default still differs from the unset policy
+    let explicit = Qos::builder().volatile().build();
+    let changes = Qos::default().diff(&explicit);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].kind, PolicyChangeKind::Set);
+    assert_eq!(changes[0].new.durability, Some(Durability::default()));
+}
+
+#[test]
+fn test_merge_each_policy() {
+    let all = all_policies_qos_for_tests();
+    for (policy, overlay) in single_policy_qos_for_tests(&all) {
+        let mut qos = Qos::default();
+        qos.merge(&overlay);
+        assert_eq!(qos, overlay, "{policy} not merged");
+    }
+
+    // Merging unset policies keeps the base values
+    let mut qos = all.clone();
+    qos.merge(&Qos::default());
+    assert_eq!(qos, all);
+
+    let mut merged = Qos::default();
+    for (_, overlay) in single_policy_qos_for_tests(&all) {
+        merged.merge(&overlay);
+    }
+    assert_eq!(merged, all);
+}
+
+#[test]
+fn test_merge_overrides() {
+    let mut base = Qos::builder()
+        .keep_last(1)
+        .partitions(["P1"])
+        .reliable(DdsDuration::from_millis(100))
+        .build();
+    let overlay = Qos::builder().keep_all().partitions(["P2"]).build();
+    base.merge(&overlay);
+    assert_eq!(
+        base,
+        Qos::builder()
+            .keep_all()
+            .partitions(["P2"])
+            .reliable(DdsDuration::from_millis(100))
+            .build()
+    );
+    assert!(base
+        .diff(&overlay)
+        .iter()
+        .all(|c| c.policy == "reliability"));
+}
@@ -0,0 +1,230 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use super::*;
+
+/// A QoS policy value, or combination of values, that Cyclone DDS rejects as inconsistent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub policy: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.policy, self.reason)
+    }
+}
+
+impl Qos {
+    /// Checks the consistency rules between the policies applicable to an entity of `kind`,
+    /// using the DDS defaults for the unset policies.
+    ///
+    /// This reports all the violations at once, where Cyclone DDS would only fail the entity
+    /// creation with `DDS_RETCODE_INCONSISTENT_POLICY` (or `DDS_RETCODE_BAD_PARAMETER`).
+    pub fn validate(&self, kind: EntityKind) -> Result<(), Vec<PolicyViolation>> {
+        let mut violations = Vec::new();
+        let mut violation = |policy: &'static str, reason: String| {
+            violations.push(PolicyViolation { policy, reason })
+        };
+
+        // Topics, readers and writers
+        let has_data = matches!(
+            kind,
+            EntityKind::Topic | EntityKind::Reader | EntityKind::Writer
+        );
+        let has_durability_service = matches!(kind, EntityKind::Topic | EntityKind::Writer);
+
+        if has_data {
+            let history = self.history.clone().unwrap_or_default();
+            let limits = self.resource_limits.clone().unwrap_or_default();
+            check_history(
+                "history",
+                history.kind,
+                history.depth,
+                "resource_limits",
+                limits.max_samples,
+                limits.max_instances,
+                limits.max_samples_per_instance,
+                &mut violation,
+            );
+        }
+
+        if has_durability_service {
+            if let Some(service) = &self.durability_service {
+                check_history(
+                    "durability_service",
+                    service.history_kind,
+                    service.history_depth,
+                    "durability_service",
+                    service.max_samples,
+                    service.max_instances,
+                    service.max_samples_per_instance,
+                    &mut violation,
+                );
+            }
+        }
+
+        if has_data {
+            for value in self.data_representation.iter().flatten() {
+                let value = *value as u32;
+                if value != DDS_DATA_REPRESENTATION_XCDR1 && value != DDS_DATA_REPRESENTATION_XCDR2
+                {
+                    violation(
+                        "data_representation",
+                        format!("unsupported data representation {value}"),
+                    );
+                }
+            }
+        }
+
+        if kind == EntityKind::Reader {
+            let deadline = self.deadline.clone().unwrap_or_default();
+            let filter = self.time_based_filter.clone().unwrap_or_default();
+            if deadline.period < filter.minimum_separation {
+                violation(
+                    "deadline",
+                    format!(
+                        "period {} is shorter than the time based filter minimum separation {}",
+                        deadline.period, filter.minimum_separation
+                    ),
+                );
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_history<F>(
+    history_policy: &'static str,
+    history_kind: HistoryKind,
+    depth: i32,
+    limits_policy: &'static str,
+    max_samples: i32,
+    max_instances: i32,
+    max_samples_per_instance: i32,
+    violation: &mut F,
+) where
+    F: FnMut(&'static str, String),
+{
+    let is_valid_limit = |limit: i32| limit == DDS_LENGTH_UNLIMITED || limit > 0;
+    for (name, limit) in [
+        ("max_samples", max_samples),
+        ("max_instances", max_instances),
+        ("max_samples_per_instance", max_samples_per_instance),
+    ] {
+        if !is_valid_limit(limit) {
+            violation(
+                limits_policy,
+                format!("{name} is {limit} but must be positive or unlimited"),
+            );
+        }
+    }
+    if max_samples != DDS_LENGTH_UNLIMITED
+        && max_samples_per_instance != DDS_LENGTH_UNLIMITED
+        && max_samples < max_samples_per_instance
+    {
+        violation(
+            limits_policy,
+            format!(
+                "max_samples {max_samples} is less than max_samples_per_instance {max_samples_per_instance}"
+            ),
+        );
+    }
+
+    if history_kind == HistoryKind::KEEP_LAST {
+        if depth <= 0 {
+            violation(
+                history_policy,
+                format!("KEEP_LAST depth is {depth} but must be positive"),
+            );
+        } else if max_samples_per_instance != DDS_LENGTH_UNLIMITED
+            && depth > max_samples_per_instance
+        {
+            violation(
+                history_policy,
+                format!(
+                    "KEEP_LAST depth {depth} exceeds max_samples_per_instance {max_samples_per_instance}"
+                ),
+            );
+        }
+    }
+}
+
+#[test]
+fn test_validate_defaults() {
+    for kind in [
+        EntityKind::Participant,
+        EntityKind::Topic,
+        EntityKind::Publisher,
+        EntityKind::Subscriber,
+        EntityKind::Writer,
+        EntityKind::Reader,
+    ] {
+        assert_eq!(Qos::default().validate(kind), Ok(()));
+        assert_eq!(Qos::default_for(kind).validate(kind), Ok(()));
+    }
+}
+
+#[test]
+fn test_validate_violations() {
+    let qos = Qos::builder()
+        .keep_last(10)
+        .resource_limits(ResourceLimits {
+            max_samples: 5,
+            max_instances: 0,
+            max_samples_per_instance: 8,
+        })
+        .deadline(DdsDuration::from_millis(10))
+        .time_based_filter(DdsDuration::from_millis(100))
+        .build();
+
+    let violations = qos.validate(EntityKind::Reader).unwrap_err();
+    let policies: Vec<&str> = violations.iter().map(|v| v.policy).collect();
+    assert_eq!(
+        policies,
+        vec!["resource_limits", "resource_limits", "history", "deadline"]
+    );
+
+    // The time based filter doesn't apply to writers
+    assert_eq!(qos.validate(EntityKind::Writer).unwrap_err().len(), 3);
+    assert_eq!(qos.validate(EntityKind::Publisher), Ok(()));
+
+    let qos = Qos::builder().keep_last(0).build();
+    assert_eq!(
+        qos.validate(EntityKind::Writer).unwrap_err()[0].policy,
+        "history"
+    );
+}
+
+#[test]
+fn test_validate_durability_service() {
+    let qos = Qos::builder()
+        .durability_service(DurabilityService {
+            history_depth: 10,
+            max_samples_per_instance: 2,
+            ..Default::default()
+        })
+        .build();
+    assert_eq!(
+        qos.validate(EntityKind::Writer).unwrap_err()[0].policy,
+        "durability_service"
+    );
+    assert_eq!(qos.validate(EntityKind::Reader), Ok(()));
+}
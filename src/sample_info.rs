@@ -0,0 +1,86 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::time::DdsTime;
+use crate::*;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+pub enum SampleState {
+    READ = dds_sample_state_DDS_SST_READ as isize,
+    NOT_READ = dds_sample_state_DDS_SST_NOT_READ as isize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+pub enum ViewState {
+    NEW = dds_view_state_DDS_VST_NEW as isize,
+    OLD = dds_view_state_DDS_VST_OLD as isize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+pub enum InstanceState {
+    ALIVE = dds_instance_state_DDS_IST_ALIVE as isize,
+    NOT_ALIVE_DISPOSED = dds_instance_state_DDS_IST_NOT_ALIVE_DISPOSED as isize,
+    NOT_ALIVE_NO_WRITERS = dds_instance_state_DDS_IST_NOT_ALIVE_NO_WRITERS as isize,
+}
+
+/// The owned equivalent of `dds_sample_info_t`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SampleInfo {
+    pub sample_state: SampleState,
+    pub view_state: ViewState,
+    pub instance_state: InstanceState,
+    /// If false, the sample only holds the key of an instance whose state changed.
+    pub valid_data: bool,
+    pub source_timestamp: DdsTime,
+    pub instance_handle: dds_instance_handle_t,
+    pub publication_handle: dds_instance_handle_t,
+    pub disposed_generation_count: u32,
+    pub no_writers_generation_count: u32,
+    pub sample_rank: u32,
+    pub generation_rank: u32,
+    pub absolute_generation_rank: u32,
+}
+
+// Cyclone DDS always sets exactly one of the possible states
+impl From<&dds_sample_info_t> for SampleInfo {
+    fn from(info: &dds_sample_info_t) -> Self {
+        #[allow(non_upper_case_globals)]
+        SampleInfo {
+            sample_state: match info.sample_state {
+                dds_sample_state_DDS_SST_READ => SampleState::READ,
+                _ => SampleState::NOT_READ,
+            },
+            view_state: match info.view_state {
+                dds_view_state_DDS_VST_NEW => ViewState::NEW,
+                _ => ViewState::OLD,
+            },
+            instance_state: match info.instance_state {
+                dds_instance_state_DDS_IST_ALIVE => InstanceState::ALIVE,
+                dds_instance_state_DDS_IST_NOT_ALIVE_DISPOSED => InstanceState::NOT_ALIVE_DISPOSED,
+                _ => InstanceState::NOT_ALIVE_NO_WRITERS,
+            },
+            valid_data: info.valid_data,
+            source_timestamp: DdsTime::from_nanos(info.source_timestamp),
+            instance_handle: info.instance_handle,
+            publication_handle: info.publication_handle,
+            disposed_generation_count: info.disposed_generation_count,
+            no_writers_generation_count: info.no_writers_generation_count,
+            sample_rank: info.sample_rank,
+            generation_rank: info.generation_rank,
+            absolute_generation_rank: info.absolute_generation_rank,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::entity::{CdrSample, Entity, Reader};
+use crate::listener::Listener;
+use crate::*;
+use futures_core::{FusedStream, Stream};
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+const DEFAULT_CAPACITY: usize = 32;
+
+/// A [`Stream`] of the samples of a [`Reader`], as returned by [`Reader::into_stream`].
+///
+/// The stream only ends after failing to take samples from the reader (e.g. as it was deleted
+/// with its participant): the error is returned once, then the stream ends. It works with any
+/// async runtime, as it only relies on the `Waker` of the polling task.
+#[derive(Debug)]
+pub struct SampleStream {
+    reader: Reader,
+    waker: Arc<Mutex<Option<Waker>>>,
+    buffer: VecDeque<CdrSample>,
+    capacity: usize,
+    terminated: bool,
+}
+
+impl Reader {
+    /// Converts the reader into a [`SampleStream`] of its samples in their serialized
+    /// representation, buffering at most 32 samples.
+    pub fn into_stream(self) -> Result<SampleStream> {
+        self.into_stream_with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Converts the reader into a [`SampleStream`], buffering at most `capacity` samples.
+    ///
+    /// The samples are only taken from the reader when the stream is polled and its buffer
+    /// is empty, so the samples not consumed yet stay in the reader cache, as limited by the
+    /// history and resource limits QoS of the reader.
+    ///
+    /// This replaces the listener of the reader, and of its clones.
+    pub fn into_stream_with_capacity(self, capacity: usize) -> Result<SampleStream> {
+        if capacity == 0 {
+            return Err(Error::BadParameter);
+        }
+        let waker = Arc::new(Mutex::new(None::<Waker>));
+        let listener = Listener::new().on_data_available({
+            let waker = waker.clone();
+            move |_| {
+                let waker = waker.lock().unwrap().take();
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }
+        });
+        self.set_listener(Some(listener))?;
+        Ok(SampleStream {
+            reader: self,
+            waker,
+            buffer: VecDeque::with_capacity(capacity),
+            capacity,
+            terminated: false,
+        })
+    }
+}
+
+impl SampleStream {
+    pub fn reader(&self) -> &Reader {
+        &self.reader
+    }
+}
+
+impl Stream for SampleStream {
+    type Item = Result<CdrSample>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(sample) = self.buffer.pop_front() {
+            return Poll::Ready(Some(Ok(sample)));
+        }
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        // Registered before taking, so that a sample received meanwhile wakes the task
+        *self.waker.lock().unwrap() = Some(cx.waker().clone());
+        match self.reader.take_cdr(self.capacity) {
+            Ok(samples) => {
+                self.buffer.extend(samples);
+                match self.buffer.pop_front() {
+                    Some(sample) => Poll::Ready(Some(Ok(sample))),
+                    None => Poll::Pending,
+                }
+            }
+            Err(e) => {
+                // Taking fails for good once the reader is deleted, don't report it forever
+                self.terminated = true;
+                *self.waker.lock().unwrap() = None;
+                Poll::Ready(Some(Err(e)))
+            }
+        }
+    }
+}
+
+impl FusedStream for SampleStream {
+    fn is_terminated(&self) -> bool {
+        self.terminated && self.buffer.is_empty()
+    }
+}
+
+#[test]
+fn test_reader_stream() {
+    use crate::entity::{create_blob_topic_for_tests, write_blob_for_tests, Participant, Writer};
+    use crate::qos::Qos;
+    use crate::time::DdsDuration;
+    use futures::{executor::block_on, StreamExt};
+    use std::{thread, time::Duration};
+
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = unsafe { create_blob_topic_for_tests(&participant, "test_stream") };
+    let writer = Writer::new(&participant, &topic, None, None).unwrap();
+    let qos = Qos::builder()
+        .reliable(DdsDuration::from_secs(1))
+        .keep_all()
+        .build();
+    let reader = Reader::new(&participant, &topic, Some(&qos), None).unwrap();
+    let mut stream = reader.into_stream_with_capacity(2).unwrap();
+
+    for i in 0..3 {
+        unsafe { write_blob_for_tests(&writer, &[0, 1, 0, 0, i]) };
+    }
+    // The stream is woken up by the samples written after it starts waiting
+    let handle = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        for i in 3..5 {
+            unsafe { write_blob_for_tests(&writer, &[0, 1, 0, 0, i]) };
+        }
+    });
+
+    let samples = block_on(stream.by_ref().take(5).collect::<Vec<_>>());
+    handle.join().unwrap();
+    let payloads: Vec<u8> = samples.into_iter().map(|s| s.unwrap().data[4]).collect();
+    assert_eq!(payloads, vec![0, 1, 2, 3, 4]);
+
+    // The stream ends after failing to take samples
+    assert_eq!(unsafe { dds_delete(stream.reader().entity()) }, 0);
+    assert!(!stream.is_terminated());
+    assert!(block_on(stream.next()).unwrap().is_err());
+    assert!(stream.is_terminated());
+    assert!(block_on(stream.next()).is_none());
+}
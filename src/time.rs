@@ -0,0 +1,304 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::qos::DDS_INFINITE_TIME;
+use crate::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A DDS duration, either finite or infinite (`DDS_INFINITY` in Cyclone DDS).
+///
+/// Human-readable formats (e.g. JSON) serialize it as a string such as `"100ms"` or
+/// `"infinite"`, and also accept the number of nanoseconds. Other formats use the
+/// number of nanoseconds, as `dds_duration_t` does.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DdsDuration {
+    Finite(Duration),
+    Infinite,
+}
+
+impl DdsDuration {
+    pub const ZERO: DdsDuration = DdsDuration::Finite(Duration::ZERO);
+
+    /// Converts a `dds_duration_t`. Negative durations are invalid in DDS and saturate to zero.
+    pub fn from_nanos(nanos: dds_duration_t) -> Self {
+        match nanos {
+            DDS_INFINITE_TIME => DdsDuration::Infinite,
+            n if n <= 0 => DdsDuration::ZERO,
+            n => DdsDuration::Finite(Duration::from_nanos(n as u64)),
+        }
+    }
+
+    pub fn from_millis(millis: u64) -> Self {
+        DdsDuration::Finite(Duration::from_millis(millis))
+    }
+
+    pub fn from_secs(secs: u64) -> Self {
+        DdsDuration::Finite(Duration::from_secs(secs))
+    }
+
+    /// Returns the `dds_duration_t` value. Finite durations too long to be represented
+    /// saturate to the longest finite one.
+    pub fn as_nanos(&self) -> dds_duration_t {
+        match self {
+            DdsDuration::Finite(d) => d.as_nanos().min(DDS_INFINITE_TIME as u128 - 1) as i64,
+            DdsDuration::Infinite => DDS_INFINITE_TIME,
+        }
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        matches!(self, DdsDuration::Infinite)
+    }
+
+    /// Returns the duration as a [`Duration`], or `None` if infinite.
+    pub fn to_std(&self) -> Option<Duration> {
+        match self {
+            DdsDuration::Finite(d) => Some(*d),
+            DdsDuration::Infinite => None,
+        }
+    }
+}
+
+impl From<Duration> for DdsDuration {
+    fn from(d: Duration) -> Self {
+        DdsDuration::Finite(d)
+    }
+}
+
+impl From<dds_duration_t> for DdsDuration {
+    fn from(nanos: dds_duration_t) -> Self {
+        DdsDuration::from_nanos(nanos)
+    }
+}
+
+impl From<DdsDuration> for dds_duration_t {
+    fn from(d: DdsDuration) -> Self {
+        d.as_nanos()
+    }
+}
+
+impl fmt::Display for DdsDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nanos = match self {
+            DdsDuration::Infinite => return write!(f, "infinite"),
+            DdsDuration::Finite(d) => d.as_nanos(),
+        };
+        // Use the largest unit in which the duration is a whole number
+        match nanos {
+            0 => write!(f, "0s"),
+            n if n % 1_000_000_000 == 0 => write!(f, "{}s", n / 1_000_000_000),
+            n if n % 1_000_000 == 0 => write!(f, "{}ms", n / 1_000_000),
+            n if n % 1_000 == 0 => write!(f, "{}us", n / 1_000),
+            n => write!(f, "{n}ns"),
+        }
+    }
+}
+
+/// Error returned when parsing an invalid [`DdsDuration`] string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDurationError(String);
+
+impl fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid duration '{}' (expected e.g. \"100ms\", \"5s\" or \"infinite\")",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseDurationError {}
+
+impl FromStr for DdsDuration {
+    type Err = ParseDurationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("infinite") || s.eq_ignore_ascii_case("infinity") {
+            return Ok(DdsDuration::Infinite);
+        }
+        let error = || ParseDurationError(String::from(s));
+        let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(error)?;
+        let (value, unit) = s.split_at(split);
+        let value: u64 = value.parse().map_err(|_| error())?;
+        let d = match unit.trim() {
+            "ns" => Duration::from_nanos(value),
+            "us" | "µs" => Duration::from_micros(value),
+            "ms" => Duration::from_millis(value),
+            "s" => Duration::from_secs(value),
+            "min" => Duration::from_secs(value.checked_mul(60).ok_or_else(error)?),
+            "h" => Duration::from_secs(value.checked_mul(3600).ok_or_else(error)?),
+            _ => return Err(error()),
+        };
+        Ok(DdsDuration::Finite(d))
+    }
+}
+
+impl Serialize for DdsDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_i64(self.as_nanos())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DdsDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DurationVisitor;
+
+        impl de::Visitor<'_> for DurationVisitor {
+            type Value = DdsDuration;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a duration in nanoseconds or a string like \"100ms\"")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(DdsDuration::from_nanos(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(DdsDuration::from_nanos(
+                    v.min(DDS_INFINITE_TIME as u64) as i64
+                ))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        // Non self-describing formats (e.g. bincode) don't support deserialize_any
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(DurationVisitor)
+        } else {
+            deserializer.deserialize_i64(DurationVisitor)
+        }
+    }
+}
+
+/// A DDS timestamp, in nanoseconds since the UNIX epoch.
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct DdsTime(dds_time_t);
+
+impl DdsTime {
+    /// Returns the current time, as used by Cyclone DDS for source timestamps.
+    pub fn now() -> Self {
+        DdsTime(unsafe { dds_time() })
+    }
+
+    pub fn from_nanos(nanos: dds_time_t) -> Self {
+        DdsTime(nanos)
+    }
+
+    pub fn as_nanos(&self) -> dds_time_t {
+        self.0
+    }
+
+    /// Returns the time as a [`SystemTime`], or `None` if invalid (negative).
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        u64::try_from(self.0)
+            .ok()
+            .map(|nanos| UNIX_EPOCH + Duration::from_nanos(nanos))
+    }
+}
+
+impl From<SystemTime> for DdsTime {
+    fn from(t: SystemTime) -> Self {
+        let nanos = match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_nanos().min(DDS_INFINITE_TIME as u128) as i64,
+            Err(e) => -(e.duration().as_nanos().min(DDS_INFINITE_TIME as u128) as i64),
+        };
+        DdsTime(nanos)
+    }
+}
+
+impl From<DdsTime> for dds_time_t {
+    fn from(t: DdsTime) -> Self {
+        t.0
+    }
+}
+
+#[test]
+fn test_duration_conversions() {
+    assert_eq!(
+        DdsDuration::from_nanos(DDS_INFINITE_TIME),
+        DdsDuration::Infinite
+    );
+    assert_eq!(DdsDuration::from_nanos(-5), DdsDuration::ZERO);
+    assert_eq!(DdsDuration::from_millis(100).as_nanos(), 100_000_000);
+    assert_eq!(
+        DdsDuration::from(Duration::MAX).as_nanos(),
+        DDS_INFINITE_TIME - 1
+    );
+    assert!(DdsDuration::from_secs(1_000_000) < DdsDuration::Infinite);
+    assert_eq!(DdsDuration::Infinite.to_std(), None);
+}
+
+#[test]
+fn test_duration_display_parse() {
+    for (d, s) in [
+        (DdsDuration::Infinite, "infinite"),
+        (DdsDuration::ZERO, "0s"),
+        (DdsDuration::from_millis(100), "100ms"),
+        (DdsDuration::from_secs(5), "5s"),
+        (DdsDuration::from_nanos(1500), "1500ns"),
+        (DdsDuration::from_nanos(2000), "2us"),
+    ] {
+        assert_eq!(d.to_string(), s);
+        assert_eq!(s.parse::<DdsDuration>(), Ok(d));
+    }
+    assert_eq!("2min".parse(), Ok(DdsDuration::from_secs(120)));
+    assert!("100".parse::<DdsDuration>().is_err());
+    assert!("ms".parse::<DdsDuration>().is_err());
+    assert!("10 parsecs".parse::<DdsDuration>().is_err());
+}
+
+#[test]
+fn test_duration_serde() {
+    let d = DdsDuration::from_millis(100);
+    assert_eq!(serde_json::to_string(&d).unwrap(), "\"100ms\"");
+    assert_eq!(
+        serde_json::from_str::<DdsDuration>("\"infinite\"").unwrap(),
+        DdsDuration::Infinite
+    );
+    // The nanoseconds encoding is still accepted
+    assert_eq!(serde_json::from_str::<DdsDuration>("100000000").unwrap(), d);
+    assert_eq!(
+        serde_json::from_str::<DdsDuration>(&DDS_INFINITE_TIME.to_string()).unwrap(),
+        DdsDuration::Infinite
+    );
+
+    let bincode = bincode::serialize(&d).unwrap();
+    assert_eq!(bincode, bincode::serialize(&100_000_000i64).unwrap());
+    assert_eq!(bincode::deserialize::<DdsDuration>(&bincode).unwrap(), d);
+}
+
+#[test]
+fn test_time_conversions() {
+    let now = SystemTime::now();
+    let t = DdsTime::from(now);
+    assert_eq!(t.to_system_time(), Some(now));
+    assert!(DdsTime::now() >= t);
+    assert_eq!(DdsTime::from_nanos(-1).to_system_time(), None);
+}
@@ -0,0 +1,823 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::cdr::{CdrError, DataRepresentation, Encapsulation, EncapsulationHeader, Endianness};
+use crate::entity::{self, Entity, Participant, ReaderParent, WriterParent};
+use crate::error::check;
+use crate::listener::Listener;
+use crate::qos::Qos;
+use crate::*;
+use log::warn;
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    ffi::CString,
+    marker::PhantomData,
+    os::raw::c_void,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Mutex, OnceLock},
+};
+
+/// Derives [`TopicType`] for structs also deriving serde's `Serialize` and `Deserialize`.
+///
+/// The key fields are marked with `#[key]`, and the extensibility of the type with
+/// `#[appendable]` or `#[mutable]` (final by default, `#[r#final]` as `final` is reserved).
+/// The type name is the path of the type without the crate name (e.g. `"module::Type"`),
+/// unless set with `#[type_name = "..."]`.
+#[cfg(feature = "derive")]
+pub use cyclors_derive::TopicType;
+
+/// The size of the keys hashed with MD5 in the key hashes, instead of being zero-padded.
+const FIXED_KEY_MAX_SIZE: usize = 16;
+
+/// A Rust type published on DDS topics, through a sertype implemented in Rust: its samples
+/// are serialized with [`TopicType::serialize`] when written, and deserialized when taken.
+pub trait TopicType: Sized + Send + 'static {
+    /// The name of the type, as in IDL (e.g. `"module::Type"`).
+    fn type_name() -> String;
+
+    /// Serializes the sample, with its encapsulation header, e.g. with [`cdr::to_vec`].
+    fn serialize(&self) -> Result<Vec<u8>, CdrError>;
+
+    /// Deserializes a sample serialized with its encapsulation header, by this type or by
+    /// another implementation of the topic type.
+    fn deserialize(payload: &[u8]) -> Result<Self, CdrError>;
+
+    /// Whether the type has a key: all the samples of keyless types are of the same instance.
+    fn is_keyed() -> bool {
+        false
+    }
+
+    /// The key of the sample serialized in big-endian XCDR2, as in the key hashes.
+    fn key(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// The maximum size of the serialized keys, `None` if unbounded. The keys of up to 16
+    /// bytes are sent in the key hashes, and the others as their MD5 hash.
+    fn max_key_size() -> Option<usize> {
+        None
+    }
+
+    /// Reads the key of a key sample (as written by disposes and unregisters), serialized with
+    /// its encapsulation header in the representation of its writer, and returns it as
+    /// [`TopicType::key`] does. The key samples recovered from the key hashes are in big-endian
+    /// XCDR2, zero-padded to the max key size.
+    ///
+    /// By default, only the key samples in big-endian XCDR2 are accepted, as they hold the key
+    /// as in the key hashes.
+    fn key_from_payload(payload: &[u8]) -> Result<Vec<u8>, CdrError> {
+        match cdr::split(payload)? {
+            (header, key) if header.encapsulation == Encapsulation::CDR2_BE => Ok(key.to_vec()),
+            _ => Err(CdrError::Unsupported(
+                "key samples not in big-endian XCDR2 without the key type",
+            )),
+        }
+    }
+}
+
+/// Serializes the key fields of a sample as in the key hashes: in big-endian XCDR2, without
+/// encapsulation header nor final padding. The fields are given as a tuple struct, serialized
+/// as a final struct.
+pub fn key_to_vec<K: serde::Serialize + ?Sized>(key: &K) -> Result<Vec<u8>, CdrError> {
+    let payload = cdr::to_vec_with_endianness(key, DataRepresentation::XCDR2, Endianness::Big)?;
+    Ok(cdr::split(&payload)?.1.to_vec())
+}
+
+// Used by the code generated by cyclors-derive
+#[doc(hidden)]
+pub mod __private {
+    pub use serde;
+
+    pub fn type_name(module_path: &str, name: &str) -> String {
+        match module_path.split_once("::") {
+            Some((_, path)) => format!("{path}::{name}"),
+            None => name.to_string(),
+        }
+    }
+}
+
+/// A topic of the type `T`.
+#[derive(Debug)]
+pub struct Topic<T> {
+    topic: entity::Topic,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Topic<T> {
+    fn clone(&self) -> Self {
+        Topic {
+            topic: self.topic.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: TopicType> Topic<T> {
+    pub fn new(
+        participant: &Participant,
+        name: &str,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        let type_name = CString::new(T::type_name()).map_err(|_| Error::BadParameter)?;
+        let sertype = Sertype::new::<T>(&type_name) as *mut ddsi_sertype;
+        let topic =
+            unsafe { entity::Topic::with_sertype(participant, sertype, name, qos, listener)? };
+        Ok(Topic {
+            topic,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<T> Topic<T> {
+    pub fn topic(&self) -> &entity::Topic {
+        &self.topic
+    }
+}
+
+/// A writer of samples of type `T`.
+#[derive(Debug)]
+pub struct Writer<T> {
+    writer: entity::Writer,
+    phantom: PhantomData<fn(&T)>,
+}
+
+impl<T> Clone for Writer<T> {
+    fn clone(&self) -> Self {
+        Writer {
+            writer: self.writer.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: TopicType> Writer<T> {
+    pub fn new<P: WriterParent>(
+        parent: &P,
+        topic: &Topic<T>,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        let writer = entity::Writer::new(parent, &topic.topic, qos, listener)?;
+        Ok(Writer {
+            writer,
+            phantom: PhantomData,
+        })
+    }
+
+    pub fn writer(&self) -> &entity::Writer {
+        &self.writer
+    }
+
+    /// Fails if the sample can't be serialized.
+    pub fn write(&self, sample: &T) -> Result<()> {
+        check(unsafe { dds_write(self.writer.entity(), sample as *const T as *const c_void) })?;
+        Ok(())
+    }
+
+    /// Disposes the instance of `sample`.
+    pub fn dispose(&self, sample: &T) -> Result<()> {
+        check(unsafe { dds_dispose(self.writer.entity(), sample as *const T as *const c_void) })?;
+        Ok(())
+    }
+
+    /// Unregisters the writer from the instance of `sample`.
+    pub fn unregister(&self, sample: &T) -> Result<()> {
+        check(unsafe {
+            dds_unregister_instance(self.writer.entity(), sample as *const T as *const c_void)
+        })?;
+        Ok(())
+    }
+
+    /// Returns the handle of the instance of `sample`, if known by the writer.
+    pub fn lookup_instance(&self, sample: &T) -> Option<dds_instance_handle_t> {
+        let handle = unsafe {
+            dds_lookup_instance(self.writer.entity(), sample as *const T as *const c_void)
+        };
+        (handle != 0).then_some(handle)
+    }
+}
+
+/// A reader of samples of type `T`.
+#[derive(Debug)]
+pub struct Reader<T> {
+    reader: entity::Reader,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Reader<T> {
+    fn clone(&self) -> Self {
+        Reader {
+            reader: self.reader.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: TopicType> Reader<T> {
+    pub fn new<P: ReaderParent>(
+        parent: &P,
+        topic: &Topic<T>,
+        qos: Option<&Qos>,
+        listener: Option<Listener>,
+    ) -> Result<Self> {
+        let reader = entity::Reader::new(parent, &topic.topic, qos, listener)?;
+        Ok(Reader {
+            reader,
+            phantom: PhantomData,
+        })
+    }
+
+    pub fn reader(&self) -> &entity::Reader {
+        &self.reader
+    }
+
+    /// Takes up to `max_samples` samples, and returns the valid ones. The samples which can't
+    /// be deserialized are dropped.
+    pub fn take(&self, max_samples: usize) -> Result<Vec<T>> {
+        Ok(self
+            .reader
+            .take_serdata(max_samples)?
+            .into_iter()
+            .filter(|(_, info)| info.valid_data)
+            .filter_map(|(data, _)| match T::deserialize(&data) {
+                Ok(sample) => Some(sample),
+                Err(e) => {
+                    warn!("Failed to deserialize a sample of {}: {e}", T::type_name());
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+/// The operations of the sertype of a [`TopicType`], shared by its topics as Cyclone DDS
+/// compares them to tell if sertypes are equal.
+struct Ops {
+    sertype: ddsi_sertype_ops,
+    serdata: ddsi_serdata_ops,
+}
+
+// The tables are immutable, and only made of function pointers
+unsafe impl Send for Ops {}
+unsafe impl Sync for Ops {}
+
+impl Ops {
+    fn get<T: TopicType>() -> &'static Ops {
+        static OPS: OnceLock<Mutex<HashMap<TypeId, &'static Ops>>> = OnceLock::new();
+        let mut ops = OPS.get_or_init(Default::default).lock().unwrap();
+        ops.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::leak(Box::new(Ops::new::<T>())))
+    }
+
+    fn new<T: TopicType>() -> Self {
+        // The samples are only exchanged in their serialized representation, with dds_write and
+        // dds_takecdr: loans and dds_take aren't supported, nor the type information
+        let mut sertype: ddsi_sertype_ops = unsafe { std::mem::zeroed() };
+        sertype.version = Some(ddsi_sertype_v0);
+        sertype.free = Some(sertype_free);
+        sertype.equal = Some(sertype_equal);
+        sertype.hash = Some(sertype_hash);
+
+        let mut serdata: ddsi_serdata_ops = unsafe { std::mem::zeroed() };
+        serdata.eqkey = Some(serdata_eqkey);
+        serdata.get_size = Some(serdata_get_size);
+        serdata.from_ser = Some(serdata_from_ser::<T>);
+        serdata.from_ser_iov = Some(serdata_from_ser_iov::<T>);
+        serdata.from_keyhash = Some(serdata_from_keyhash::<T>);
+        serdata.from_sample = Some(serdata_from_sample::<T>);
+        serdata.to_ser = Some(serdata_to_ser);
+        serdata.to_ser_ref = Some(serdata_to_ser_ref);
+        serdata.to_ser_unref = Some(serdata_to_ser_unref);
+        serdata.to_sample = Some(serdata_to_sample);
+        serdata.to_untyped = Some(serdata_to_untyped);
+        serdata.untyped_to_sample = Some(serdata_untyped_to_sample);
+        serdata.free = Some(serdata_free);
+        serdata.get_keyhash = Some(serdata_get_keyhash);
+        Ops { sertype, serdata }
+    }
+}
+
+#[repr(C)]
+struct Sertype {
+    c: ddsi_sertype,
+    key_md5: bool,
+}
+
+impl Sertype {
+    /// Returns a new sertype, whose reference is to be consumed by `dds_create_topic_sertype`.
+    fn new<T: TopicType>(type_name: &CString) -> *mut Sertype {
+        let ops = Ops::get::<T>();
+        let sertype = Box::into_raw(Box::new(Sertype {
+            c: unsafe { std::mem::zeroed() },
+            key_md5: T::max_key_size().is_none_or(|size| size > FIXED_KEY_MAX_SIZE),
+        }));
+        // The type name is copied
+        unsafe {
+            ddsi_sertype_init(
+                &mut (*sertype).c,
+                type_name.as_ptr(),
+                &ops.sertype,
+                &ops.serdata,
+                !T::is_keyed(),
+            );
+        }
+        sertype
+    }
+}
+
+unsafe extern "C" fn sertype_free(sertype: *mut ddsi_sertype) {
+    ddsi_sertype_fini(sertype);
+    drop(Box::from_raw(sertype as *mut Sertype));
+}
+
+unsafe extern "C" fn sertype_equal(_a: *const ddsi_sertype, _b: *const ddsi_sertype) -> bool {
+    // The operations, and so the type, are checked for equality before this function is called
+    true
+}
+
+unsafe extern "C" fn sertype_hash(_sertype: *const ddsi_sertype) -> u32 {
+    // Nothing beyond the common fields
+    0
+}
+
+#[repr(C)]
+struct Serdata {
+    c: ddsi_serdata,
+    /// The encapsulation header followed by the serialized sample, or by its key.
+    payload: Vec<u8>,
+    /// The key serialized in big-endian XCDR2, as used for the key hash.
+    key: Vec<u8>,
+    key_md5: bool,
+}
+
+impl Serdata {
+    /// Returns a new serdata of `payload`, or null if its key can't be extracted.
+    unsafe fn create<T: TopicType>(
+        sertype: *const ddsi_sertype,
+        kind: ddsi_serdata_kind,
+        payload: Vec<u8>,
+        key: Option<Vec<u8>>,
+    ) -> *mut ddsi_serdata {
+        let key = match key {
+            Some(key) => key,
+            None if !T::is_keyed() || kind == ddsi_serdata_kind_SDK_EMPTY => Vec::new(),
+            None if kind == ddsi_serdata_kind_SDK_KEY => match T::key_from_payload(&payload) {
+                Ok(key) => key,
+                Err(_) => return std::ptr::null_mut(),
+            },
+            None => match T::deserialize(&payload) {
+                Ok(sample) => sample.key(),
+                Err(_) => return std::ptr::null_mut(),
+            },
+        };
+        let mut serdata = Box::new(Serdata {
+            c: std::mem::zeroed(),
+            payload,
+            key,
+            key_md5: (*(sertype as *const Sertype)).key_md5,
+        });
+        ddsi_serdata_init(&mut serdata.c, sertype, kind);
+        serdata.c.hash = ddsrt_mh3(serdata.key.as_ptr() as *const c_void, serdata.key.len(), 0)
+            ^ (*sertype).serdata_basehash;
+        Box::into_raw(serdata) as *mut ddsi_serdata
+    }
+}
+
+/// The payload of a key sample: the big-endian XCDR2 serialized key.
+fn key_payload(key: &[u8]) -> Vec<u8> {
+    let header = EncapsulationHeader::new(Encapsulation::CDR2_BE);
+    [&header.to_bytes()[..], key].concat()
+}
+
+/// Calls `f`, returning `default` if it panics, as unwinding into C is undefined behavior.
+fn guard<R>(default: R, f: impl FnOnce() -> R) -> R {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(default)
+}
+
+unsafe extern "C" fn serdata_eqkey(a: *const ddsi_serdata, b: *const ddsi_serdata) -> bool {
+    (*(a as *const Serdata)).key == (*(b as *const Serdata)).key
+}
+
+unsafe extern "C" fn serdata_get_size(serdata: *const ddsi_serdata) -> u32 {
+    (*(serdata as *const Serdata)).payload.len() as u32
+}
+
+unsafe extern "C" fn serdata_from_ser<T: TopicType>(
+    sertype: *const ddsi_sertype,
+    kind: ddsi_serdata_kind,
+    fragchain: *const ddsi_rdata,
+    size: usize,
+) -> *mut ddsi_serdata {
+    let mut payload = vec![0; size];
+    cdds_copy_fragchain(fragchain, size, payload.as_mut_ptr());
+    guard(std::ptr::null_mut(), || {
+        Serdata::create::<T>(sertype, kind, payload, None)
+    })
+}
+
+unsafe extern "C" fn serdata_from_ser_iov<T: TopicType>(
+    sertype: *const ddsi_sertype,
+    kind: ddsi_serdata_kind,
+    niov: ddsrt_msg_iovlen_t,
+    iov: *const ddsrt_iovec_t,
+    size: usize,
+) -> *mut ddsi_serdata {
+    let mut payload = Vec::with_capacity(size);
+    for iov in std::slice::from_raw_parts(iov, niov as _) {
+        payload.extend_from_slice(std::slice::from_raw_parts(
+            iov.iov_base as *const u8,
+            iov.iov_len as _,
+        ));
+    }
+    guard(std::ptr::null_mut(), || {
+        Serdata::create::<T>(sertype, kind, payload, None)
+    })
+}
+
+unsafe extern "C" fn serdata_from_keyhash<T: TopicType>(
+    sertype: *const ddsi_sertype,
+    keyhash: *const ddsi_keyhash_t,
+) -> *mut ddsi_serdata {
+    // The key can only be recovered if it is not an MD5 hash, it is zero-padded to its max size
+    // (Cyclone DDS sends the others serialized along with the key hash)
+    let Some(size) = T::max_key_size().filter(|size| *size <= FIXED_KEY_MAX_SIZE) else {
+        return std::ptr::null_mut();
+    };
+    let keyhash = &*keyhash;
+    let padded = key_payload(&keyhash.value[..size]);
+    guard(std::ptr::null_mut(), || {
+        match T::key_from_payload(&padded) {
+            Ok(key) => Serdata::create::<T>(
+                sertype,
+                ddsi_serdata_kind_SDK_KEY,
+                key_payload(&key),
+                Some(key),
+            ),
+            Err(_) => std::ptr::null_mut(),
+        }
+    })
+}
+
+unsafe extern "C" fn serdata_from_sample<T: TopicType>(
+    sertype: *const ddsi_sertype,
+    kind: ddsi_serdata_kind,
+    sample: *const c_void,
+) -> *mut ddsi_serdata {
+    let sample = &*(sample as *const T);
+    guard(std::ptr::null_mut(), || {
+        let key = sample.key();
+        if kind == ddsi_serdata_kind_SDK_KEY {
+            return Serdata::create::<T>(sertype, kind, key_payload(&key), Some(key));
+        }
+        match sample.serialize() {
+            Ok(payload) => Serdata::create::<T>(sertype, kind, payload, Some(key)),
+            Err(e) => {
+                warn!("Failed to serialize a sample of {}: {e}", T::type_name());
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+unsafe extern "C" fn serdata_to_ser(
+    serdata: *const ddsi_serdata,
+    off: usize,
+    sz: usize,
+    buf: *mut c_void,
+) {
+    let serdata = &*(serdata as *const Serdata);
+    let payload = &serdata.payload[off..off + sz];
+    std::ptr::copy_nonoverlapping(payload.as_ptr(), buf as *mut u8, sz);
+}
+
+unsafe extern "C" fn serdata_to_ser_ref(
+    serdata: *const ddsi_serdata,
+    off: usize,
+    sz: usize,
+    ref_: *mut ddsrt_iovec_t,
+) -> *mut ddsi_serdata {
+    let serdata = &*(serdata as *const Serdata);
+    let payload = &serdata.payload[off..off + sz];
+    (*ref_).iov_base = payload.as_ptr() as *mut c_void;
+    (*ref_).iov_len = sz as _;
+    ddsi_serdata_ref(&serdata.c)
+}
+
+unsafe extern "C" fn serdata_to_ser_unref(serdata: *mut ddsi_serdata, _ref: *const ddsrt_iovec_t) {
+    ddsi_serdata_unref(serdata);
+}
+
+unsafe extern "C" fn serdata_to_sample(
+    _serdata: *const ddsi_serdata,
+    _sample: *mut c_void,
+    _bufptr: *mut *mut c_void,
+    _buflim: *mut c_void,
+) -> bool {
+    false
+}
+
+unsafe extern "C" fn serdata_to_untyped(serdata: *const ddsi_serdata) -> *mut ddsi_serdata {
+    let serdata = &*(serdata as *const Serdata);
+    // The untyped serdata has no type, but still identifies the instance
+    let mut untyped = Box::new(Serdata {
+        c: std::mem::zeroed(),
+        payload: Vec::new(),
+        key: serdata.key.clone(),
+        key_md5: serdata.key_md5,
+    });
+    ddsi_serdata_init(&mut untyped.c, serdata.c.type_, ddsi_serdata_kind_SDK_KEY);
+    untyped.c.type_ = std::ptr::null();
+    untyped.c.hash = serdata.c.hash;
+    Box::into_raw(untyped) as *mut ddsi_serdata
+}
+
+unsafe extern "C" fn serdata_untyped_to_sample(
+    _sertype: *const ddsi_sertype,
+    _serdata: *const ddsi_serdata,
+    _sample: *mut c_void,
+    _bufptr: *mut *mut c_void,
+    _buflim: *mut c_void,
+) -> bool {
+    false
+}
+
+unsafe extern "C" fn serdata_free(serdata: *mut ddsi_serdata) {
+    drop(Box::from_raw(serdata as *mut Serdata));
+}
+
+unsafe extern "C" fn serdata_get_keyhash(
+    serdata: *const ddsi_serdata,
+    buf: *mut ddsi_keyhash_t,
+    force_md5: bool,
+) {
+    let serdata = &*(serdata as *const Serdata);
+    let value = &mut (*buf).value;
+    if force_md5 || serdata.key_md5 {
+        let mut md5st: ddsrt_md5_state_t = std::mem::zeroed();
+        ddsrt_md5_init(&mut md5st);
+        ddsrt_md5_append(&mut md5st, serdata.key.as_ptr(), serdata.key.len() as u32);
+        ddsrt_md5_finish(&mut md5st, value.as_mut_ptr());
+    } else {
+        let len = serdata.key.len().min(FIXED_KEY_MAX_SIZE);
+        value.fill(0);
+        value[..len].copy_from_slice(&serdata.key[..len]);
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct KeyedSample {
+    id: u32,
+    text: String,
+}
+
+#[cfg(test)]
+impl TopicType for KeyedSample {
+    fn type_name() -> String {
+        String::from("cyclors::test::KeyedSample")
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>, CdrError> {
+        cdr::to_vec(self, cdr::DataRepresentation::XCDR2)
+    }
+
+    fn deserialize(payload: &[u8]) -> Result<Self, CdrError> {
+        cdr::from_slice(payload)
+    }
+
+    fn is_keyed() -> bool {
+        true
+    }
+
+    fn key(&self) -> Vec<u8> {
+        self.id.to_be_bytes().to_vec()
+    }
+
+    fn max_key_size() -> Option<usize> {
+        Some(4)
+    }
+}
+
+#[test]
+fn test_typed_write_and_take() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = Topic::<KeyedSample>::new(&participant, "test_typed", None, None).unwrap();
+    let writer = Writer::new(&participant, &topic, None, None).unwrap();
+    let qos = Qos::builder().keep_all().build();
+    let reader = Reader::new(&participant, &topic, Some(&qos), None).unwrap();
+
+    let samples = vec![
+        KeyedSample {
+            id: 1,
+            text: String::from("one"),
+        },
+        KeyedSample {
+            id: 2,
+            text: String::from("two"),
+        },
+    ];
+    for sample in &samples {
+        writer.write(sample).unwrap();
+    }
+    assert_eq!(reader.take(10).unwrap(), samples);
+
+    // The samples are serialized as expected by the other implementations of the type
+    writer.write(&samples[0]).unwrap();
+    let data = reader.reader().take_cdr(10).unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0].data, samples[0].serialize().unwrap());
+}
+
+#[test]
+fn test_typed_instances() {
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = Topic::<KeyedSample>::new(&participant, "test_typed_keyed", None, None).unwrap();
+    // The sertype is shared by the topics of the same type
+    let other = Topic::<KeyedSample>::new(&participant, "test_typed_keyed", None, None).unwrap();
+    let writer = Writer::new(&participant, &topic, None, None).unwrap();
+    let qos = Qos::builder().keep_all().build();
+    let reader = Reader::new(&participant, &other, Some(&qos), None).unwrap();
+
+    let one = KeyedSample {
+        id: 1,
+        text: String::from("one"),
+    };
+    let also_one = KeyedSample {
+        id: 1,
+        text: String::from("also one"),
+    };
+    writer.write(&one).unwrap();
+    writer.write(&also_one).unwrap();
+    assert_eq!(
+        writer.lookup_instance(&one),
+        writer.lookup_instance(&also_one)
+    );
+    assert!(writer
+        .lookup_instance(&KeyedSample {
+            id: 2,
+            text: String::new(),
+        })
+        .is_none());
+
+    writer.dispose(&one).unwrap();
+    let samples = reader.reader().take_cdr(10).unwrap();
+    assert_eq!(samples.len(), 3);
+    assert!(samples[..2].iter().all(|s| s.info.valid_data));
+    assert_eq!(samples[2].data, key_payload(&one.key()));
+    assert_eq!(
+        samples[2].info.instance_state,
+        crate::sample_info::InstanceState::NOT_ALIVE_DISPOSED
+    );
+    assert!(samples
+        .iter()
+        .all(|s| s.info.instance_handle == samples[0].info.instance_handle));
+}
+
+#[test]
+fn test_typed_key_samples() {
+    let type_name = CString::new(KeyedSample::type_name()).unwrap();
+    let sertype = Sertype::new::<KeyedSample>(&type_name) as *const ddsi_sertype;
+    let from_ser_iov = |payload: &[u8]| unsafe {
+        let iov = ddsrt_iovec_t {
+            iov_base: payload.as_ptr() as *mut c_void,
+            iov_len: payload.len() as _,
+        };
+        serdata_from_ser_iov::<KeyedSample>(
+            sertype,
+            ddsi_serdata_kind_SDK_KEY,
+            1,
+            &iov,
+            payload.len(),
+        )
+    };
+
+    // The key samples in big-endian XCDR2 hold the key as in the key hashes
+    let serdata = from_ser_iov(&[0x00, 0x06, 0x00, 0x00, 0, 0, 0, 1]);
+    assert!(!serdata.is_null());
+    assert_eq!(
+        unsafe { &(*(serdata as *const Serdata)).key },
+        &[0, 0, 0, 1]
+    );
+    unsafe { serdata_free(serdata) };
+    // The others are rejected without the key type, rather than taken as another key
+    assert!(from_ser_iov(&[0x00, 0x07, 0x00, 0x00, 1, 0, 0, 0]).is_null());
+    assert!(from_ser_iov(&[0x00, 0x00, 0x00, 0x00, 0, 0, 0, 1]).is_null());
+
+    // The keys of up to 16 bytes are recovered from the key hashes
+    let mut keyhash: ddsi_keyhash_t = unsafe { std::mem::zeroed() };
+    keyhash.value[3] = 7;
+    let serdata = unsafe { serdata_from_keyhash::<KeyedSample>(sertype, &keyhash) };
+    assert!(!serdata.is_null());
+    assert_eq!(
+        unsafe { &(*(serdata as *const Serdata)).key },
+        &[0, 0, 0, 7]
+    );
+    unsafe { serdata_free(serdata) };
+    unsafe { sertype_free(sertype as *mut ddsi_sertype) };
+}
+
+#[cfg(all(test, feature = "derive"))]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, TopicType)]
+#[appendable]
+struct DerivedSample {
+    #[key]
+    id: u8,
+    text: String,
+    #[key]
+    tags: [u16; 2],
+}
+
+#[cfg(all(test, feature = "derive"))]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, TopicType)]
+#[mutable]
+#[type_name = "cyclors::test::Named"]
+struct NamedSample {
+    #[key]
+    name: String,
+    value: Option<u32>,
+}
+
+#[cfg(all(test, feature = "derive"))]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+enum Color {
+    Red,
+    Green,
+}
+
+#[cfg(all(test, feature = "derive"))]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, TopicType)]
+struct SizedSample {
+    #[key]
+    id: std::primitive::u16,
+    #[key(size = 4)]
+    color: Color,
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_derive_topic_type() {
+    let sample = DerivedSample {
+        id: 1,
+        text: String::from("one"),
+        tags: [2, 3],
+    };
+    assert_eq!(DerivedSample::type_name(), "typed::DerivedSample");
+    assert!(DerivedSample::is_keyed());
+    assert_eq!(DerivedSample::max_key_size(), Some(6));
+    assert_eq!(sample.key(), vec![1, 0, 0, 2, 0, 3]);
+    let payload = sample.serialize().unwrap();
+    assert_eq!(
+        payload,
+        cdr::to_vec(&cdr::Appendable(&sample), DataRepresentation::XCDR2).unwrap()
+    );
+    assert_eq!(DerivedSample::deserialize(&payload), Ok(sample.clone()));
+    // The key samples are read in any representation
+    for key_sample in [
+        [0x00, 0x07, 0x00, 0x00, 1, 0, 2, 0, 3, 0],
+        [0x00, 0x01, 0x00, 0x00, 1, 0, 2, 0, 3, 0],
+    ] {
+        assert_eq!(
+            DerivedSample::key_from_payload(&key_sample),
+            Ok(sample.key())
+        );
+    }
+
+    let named = NamedSample {
+        name: String::from("ab"),
+        value: None,
+    };
+    assert_eq!(NamedSample::type_name(), "cyclors::test::Named");
+    assert_eq!(NamedSample::max_key_size(), None);
+    assert_eq!(named.key(), vec![0, 0, 0, 3, b'a', b'b', 0]);
+    // The EMHEADER of the key member (id 0) has the must-understand flag
+    let payload = named.serialize().unwrap();
+    assert_eq!(payload[8..12], ((1u32 << 31) | (4 << 28)).to_ne_bytes());
+    assert_eq!(NamedSample::deserialize(&payload), Ok(named));
+
+    let sized = SizedSample {
+        id: 1,
+        color: Color::Green,
+    };
+    assert_eq!(SizedSample::max_key_size(), Some(8));
+    assert_eq!(sized.key(), vec![0, 1, 0, 0, 0, 0, 0, 1]);
+
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = Topic::<DerivedSample>::new(&participant, "test_derived", None, None).unwrap();
+    let writer = Writer::new(&participant, &topic, None, None).unwrap();
+    let reader = Reader::new(&participant, &topic, None, None).unwrap();
+    writer.write(&sample).unwrap();
+    assert_eq!(reader.take(10).unwrap(), vec![sample]);
+}
@@ -0,0 +1,397 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::entity::{impl_entity, new_handle, Entity, EntityHandle, Participant, Reader, Sealed};
+use crate::error::check;
+use crate::time::DdsDuration;
+use crate::*;
+use log::error;
+use std::{
+    collections::HashMap,
+    fmt,
+    marker::PhantomData,
+    os::raw::c_void,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, Mutex, RwLock},
+};
+
+macro_rules! impl_condition {
+    ($type:ident) => {
+        impl_entity!($type);
+
+        impl PartialEq for $type {
+            fn eq(&self, other: &Self) -> bool {
+                self.entity() == other.entity()
+            }
+        }
+
+        impl Eq for $type {}
+
+        impl From<$type> for Condition {
+            fn from(condition: $type) -> Self {
+                Condition::$type(condition)
+            }
+        }
+
+        impl Attachable for $type {
+            fn from_condition(condition: Condition) -> Option<Self> {
+                match condition {
+                    Condition::$type(condition) => Some(condition),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+/// A condition triggered by the samples of a [`Reader`] matching a state mask.
+#[derive(Debug, Clone)]
+pub struct ReadCondition {
+    handle: Arc<EntityHandle>,
+}
+
+impl ReadCondition {
+    /// Creates a condition on the samples whose states match `mask`, a combination of the
+    /// sample, view and instance state masks (e.g. [`DDS_ANY_STATE`]).
+    ///
+    /// The condition can be used instead of the reader to read or take the matching samples.
+    pub fn new(reader: &Reader, mask: u32) -> Result<Self> {
+        let entity = unsafe { dds_create_readcondition(reader.entity(), mask) };
+        Ok(ReadCondition {
+            handle: new_handle(entity, None, reader.handle().into_iter().collect())?,
+        })
+    }
+}
+
+impl_condition!(ReadCondition);
+
+type Filter = Box<dyn Fn(*const c_void) -> bool + Send + Sync>;
+
+// Cyclone DDS calls the query condition filters without any user argument, so each filter
+// gets its own trampoline and slot from a fixed size pool.
+macro_rules! filter_pool {
+    ($($index:literal)*) => {
+        const POOL_SIZE: usize = [$($index),*].len();
+
+        static FILTERS: [RwLock<Option<Filter>>; POOL_SIZE] = [$(filter_pool!(@none $index)),*];
+
+        const TRAMPOLINES: [unsafe extern "C" fn(*const c_void) -> bool; POOL_SIZE] = [$({
+            unsafe extern "C" fn trampoline(sample: *const c_void) -> bool {
+                call_filter($index, sample)
+            }
+            trampoline
+        }),*];
+    };
+    (@none $index:literal) => {
+        RwLock::new(None)
+    };
+}
+
+filter_pool!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31);
+
+fn call_filter(index: usize, sample: *const c_void) -> bool {
+    let filter = FILTERS[index].read().unwrap();
+    match filter.as_ref() {
+        // Cyclone DDS aborts the process if a panic unwinds into it
+        Some(f) => catch_unwind(AssertUnwindSafe(|| f(sample))).unwrap_or_else(|_| {
+            error!("Panic in a query condition filter, the sample is filtered out");
+            false
+        }),
+        None => false,
+    }
+}
+
+/// A slot of the filter pool, released once the query condition is deleted.
+struct FilterSlot(usize);
+
+impl FilterSlot {
+    fn acquire(filter: Filter) -> Result<Self> {
+        let mut filter = Some(filter);
+        for (index, slot) in FILTERS.iter().enumerate() {
+            let mut slot = slot.write().unwrap();
+            if slot.is_none() {
+                *slot = filter.take();
+                return Ok(FilterSlot(index));
+            }
+        }
+        Err(Error::OutOfResources)
+    }
+}
+
+impl Drop for FilterSlot {
+    fn drop(&mut self) {
+        *FILTERS[self.0].write().unwrap() = None;
+    }
+}
+
+impl fmt::Debug for FilterSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FilterSlot({})", self.0)
+    }
+}
+
+/// A [`ReadCondition`] further restricted to the samples accepted by a Rust closure.
+///
+/// At most 32 query conditions can exist at the same time.
+#[derive(Debug, Clone)]
+pub struct QueryCondition {
+    // Dropped first, so that the filter outlives the condition
+    handle: Arc<EntityHandle>,
+    _filter: Arc<FilterSlot>,
+}
+
+impl QueryCondition {
+    /// Creates a condition on the samples whose states match `mask` and accepted by `filter`.
+    ///
+    /// # Safety
+    /// `T` must be the sample type of the reader's topic (e.g. `dds_builtintopic_endpoint_t`
+    /// for a publications reader).
+    pub unsafe fn new<T, F>(reader: &Reader, mask: u32, filter: F) -> Result<Self>
+    where
+        T: 'static,
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        let slot = FilterSlot::acquire(Box::new(move |sample| filter(&*(sample as *const T))))?;
+        let entity = dds_create_querycondition(reader.entity(), mask, Some(TRAMPOLINES[slot.0]));
+        Ok(QueryCondition {
+            handle: new_handle(entity, None, reader.handle().into_iter().collect())?,
+            _filter: Arc::new(slot),
+        })
+    }
+}
+
+impl_condition!(QueryCondition);
+
+/// A condition triggered manually, e.g. to wake up a thread waiting on a [`WaitSet`].
+#[derive(Debug, Clone)]
+pub struct GuardCondition {
+    handle: Arc<EntityHandle>,
+}
+
+impl GuardCondition {
+    pub fn new(participant: &Participant) -> Result<Self> {
+        let entity = unsafe { dds_create_guardcondition(participant.entity()) };
+        Ok(GuardCondition {
+            handle: new_handle(entity, None, participant.handle().into_iter().collect())?,
+        })
+    }
+
+    pub fn set_triggered(&self, triggered: bool) -> Result<()> {
+        check(unsafe { dds_set_guardcondition(self.entity(), triggered) })?;
+        Ok(())
+    }
+
+    pub fn is_triggered(&self) -> Result<bool> {
+        let mut triggered = false;
+        check(unsafe { dds_read_guardcondition(self.entity(), &mut triggered) })?;
+        Ok(triggered)
+    }
+
+    /// Returns whether the condition is triggered and resets it.
+    pub fn take_triggered(&self) -> Result<bool> {
+        let mut triggered = false;
+        check(unsafe { dds_take_guardcondition(self.entity(), &mut triggered) })?;
+        Ok(triggered)
+    }
+}
+
+impl_condition!(GuardCondition);
+
+/// A condition attached to a [`WaitSet`].
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    ReadCondition(ReadCondition),
+    QueryCondition(QueryCondition),
+    GuardCondition(GuardCondition),
+}
+
+impl Condition {
+    pub fn entity(&self) -> dds_entity_t {
+        match self {
+            Condition::ReadCondition(c) => c.entity(),
+            Condition::QueryCondition(c) => c.entity(),
+            Condition::GuardCondition(c) => c.entity(),
+        }
+    }
+}
+
+/// The conditions that can be attached to a [`WaitSet`].
+pub trait Attachable: Entity + Clone + Into<Condition> {
+    fn from_condition(condition: Condition) -> Option<Self>;
+}
+
+/// Identifies a condition of type `C` attached to a [`WaitSet`].
+#[derive(Debug)]
+pub struct AttachToken<C> {
+    entity: dds_entity_t,
+    _condition: PhantomData<fn() -> C>,
+}
+
+impl<C> Clone for AttachToken<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for AttachToken<C> {}
+
+impl<C> PartialEq for AttachToken<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.entity == other.entity
+    }
+}
+
+impl<C> Eq for AttachToken<C> {}
+
+/// Blocks the calling thread until one of the attached conditions is triggered.
+///
+/// The attached conditions are kept alive until they are detached or the waitset is dropped.
+#[derive(Debug)]
+pub struct WaitSet {
+    handle: Arc<EntityHandle>,
+    conditions: Mutex<HashMap<dds_entity_t, Condition>>,
+}
+
+impl_entity!(WaitSet);
+
+impl WaitSet {
+    pub fn new(participant: &Participant) -> Result<Self> {
+        let entity = unsafe { dds_create_waitset(participant.entity()) };
+        Ok(WaitSet {
+            handle: new_handle(entity, None, participant.handle().into_iter().collect())?,
+            conditions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn attach<C: Attachable>(&self, condition: &C) -> Result<AttachToken<C>> {
+        let mut conditions = self.conditions.lock().unwrap();
+        let entity = condition.entity();
+        check(unsafe { dds_waitset_attach(self.entity(), entity, entity as dds_attach_t) })?;
+        conditions.insert(entity, condition.clone().into());
+        Ok(AttachToken {
+            entity,
+            _condition: PhantomData,
+        })
+    }
+
+    /// Detaches a condition, returning it.
+    pub fn detach<C: Attachable>(&self, token: AttachToken<C>) -> Result<C> {
+        let mut conditions = self.conditions.lock().unwrap();
+        check(unsafe { dds_waitset_detach(self.entity(), token.entity) })?;
+        conditions
+            .remove(&token.entity)
+            .and_then(C::from_condition)
+            .ok_or(Error::PreconditionNotMet)
+    }
+
+    /// Waits for at most `timeout` until some of the attached conditions are triggered, and
+    /// returns them (none if the timeout expired).
+    pub fn wait(&self, timeout: DdsDuration) -> Result<Vec<Condition>> {
+        // Not locked while waiting, so that conditions can be attached meanwhile
+        let capacity = self.conditions.lock().unwrap().len().max(1);
+        let mut triggered: Vec<dds_attach_t> = vec![0; capacity];
+        let count = check(unsafe {
+            dds_waitset_wait(
+                self.entity(),
+                triggered.as_mut_ptr(),
+                capacity,
+                timeout.as_nanos(),
+            )
+        })?;
+        triggered.truncate(count as usize);
+
+        let conditions = self.conditions.lock().unwrap();
+        Ok(triggered
+            .into_iter()
+            // A condition may have been detached since it was triggered
+            .filter_map(|x| conditions.get(&(x as dds_entity_t)).cloned())
+            .collect())
+    }
+
+    /// Wakes up the threads waiting on the waitset, or sets it as triggered until `false` is set.
+    pub fn set_trigger(&self, trigger: bool) -> Result<()> {
+        check(unsafe { dds_waitset_set_trigger(self.entity(), trigger) })?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_waitset_conditions() {
+    use crate::entity::{create_blob_topic_for_tests, write_blob_for_tests, Writer};
+
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let topic = unsafe { create_blob_topic_for_tests(&participant, "test_waitset") };
+    let writer = Writer::new(&participant, &topic, None, None).unwrap();
+    let reader = Reader::new(&participant, &topic, None, None).unwrap();
+
+    let waitset = WaitSet::new(&participant).unwrap();
+    let read_condition = ReadCondition::new(&reader, DDS_ANY_STATE).unwrap();
+    let guard = GuardCondition::new(&participant).unwrap();
+    let read_token = waitset.attach(&read_condition).unwrap();
+    waitset.attach(&guard).unwrap();
+    assert!(waitset.wait(DdsDuration::ZERO).unwrap().is_empty());
+
+    unsafe { write_blob_for_tests(&writer, &[0, 1, 0, 0, 42]) };
+    assert_eq!(
+        waitset.wait(DdsDuration::from_secs(5)).unwrap(),
+        vec![Condition::ReadCondition(read_condition.clone())]
+    );
+    assert_eq!(waitset.detach(read_token).unwrap(), read_condition);
+
+    guard.set_triggered(true).unwrap();
+    assert_eq!(
+        waitset.wait(DdsDuration::Infinite).unwrap(),
+        vec![Condition::GuardCondition(guard.clone())]
+    );
+    assert!(guard.take_triggered().unwrap());
+    assert!(!guard.is_triggered().unwrap());
+    assert!(waitset.wait(DdsDuration::ZERO).unwrap().is_empty());
+}
+
+#[test]
+fn test_query_condition() {
+    use crate::discovery::DiscoveryReader;
+    use crate::entity::{create_blob_topic_for_tests, Writer};
+    use std::ffi::CStr;
+
+    let participant = Participant::new(DDS_DOMAIN_DEFAULT, None, None).unwrap();
+    let publications = DiscoveryReader::publications(&participant, None, None).unwrap();
+    let query = unsafe {
+        QueryCondition::new(
+            publications.reader(),
+            DDS_ANY_STATE,
+            |p: &dds_builtintopic_endpoint_t| {
+                !p.topic_name.is_null()
+                    && CStr::from_ptr(p.topic_name).to_bytes() == b"test_query_condition"
+            },
+        )
+        .unwrap()
+    };
+    let waitset = WaitSet::new(&participant).unwrap();
+    waitset.attach(&query).unwrap();
+
+    let other_topic = unsafe { create_blob_topic_for_tests(&participant, "test_query_other") };
+    let _other_writer = Writer::new(&participant, &other_topic, None, None).unwrap();
+    assert!(waitset
+        .wait(DdsDuration::from_millis(100))
+        .unwrap()
+        .is_empty());
+
+    let topic = unsafe { create_blob_topic_for_tests(&participant, "test_query_condition") };
+    let _writer = Writer::new(&participant, &topic, None, None).unwrap();
+    assert_eq!(
+        waitset.wait(DdsDuration::from_secs(5)).unwrap(),
+        vec![Condition::QueryCondition(query)]
+    );
+}
@@ -3,7 +3,10 @@
 #include <dds/dds.h>
 #include <dds/ddsc/dds_loaned_sample.h>
 #include <dds/ddsc/dds_psmx.h>
+#include <dds/ddsc/dds_public_dynamic_type.h>
 #include <dds/ddsi/ddsi_serdata.h>
 #include <dds/ddsi/ddsi_typelib.h>
+#include <dds/ddsrt/md5.h>
+#include <dds/ddsrt/mh3.h>
 
 #include <cdds/cdds_util.h>